@@ -4,10 +4,15 @@ use anyhow::Result;
 use tauri::{AppHandle, Manager};
 
 use crate::domain::services::connection_service::ConnectionService;
-use crate::domain::services::session_service::{SessionRepository, SessionService};
+use crate::domain::services::script_service::ScriptService;
+use crate::domain::services::session_service::SessionService;
 use crate::domain::services::sync_service::SyncService;
-use crate::infra::db::in_memory::InMemorySessionRepository;
-use crate::infra::db::sqlite::SqliteConnectionRepository;
+use crate::domain::services::trigger_service::TriggerService;
+use crate::infra::db::sqlite::{
+    SqliteCommandHistoryRepository, SqliteConnectionRepository, SqliteScriptRepository,
+    SqliteSessionHistoryRepository, SqliteTriggerRepository,
+};
+use crate::infra::session::live::LiveSessionRepository;
 use crate::infra::session::SessionManager;
 use crate::infra::storage::local::LocalFileAdapter;
 use crate::infra::storage::StorageAdapter;
@@ -15,7 +20,9 @@ use crate::infra::storage::StorageAdapter;
 pub struct AppState {
     connection_service: ConnectionService,
     session_service: SessionService,
+    script_service: ScriptService,
     sync_service: SyncService,
+    trigger_service: TriggerService,
     session_manager: SessionManager,
 }
 
@@ -26,15 +33,32 @@ impl AppState {
         let db_path = db_dir.join("connections.sqlite3");
 
         let connection_repo = Arc::new(SqliteConnectionRepository::new(&db_path)?);
-        let session_repo: Arc<dyn SessionRepository> =
-            Arc::new(InMemorySessionRepository::default());
+        let script_repo = Arc::new(SqliteScriptRepository::new(&db_path)?);
+        let trigger_repo = Arc::new(SqliteTriggerRepository::new(&db_path)?);
+        let history_repo = Arc::new(SqliteSessionHistoryRepository::new(&db_path)?);
+        let command_repo = Arc::new(SqliteCommandHistoryRepository::new(&db_path)?);
         let storage_adapter: Arc<dyn StorageAdapter> = Arc::new(LocalFileAdapter::default());
+        let trigger_service = TriggerService::new(trigger_repo);
         let session_manager = SessionManager::new();
+        session_manager.set_trigger_rules(&trigger_service.list_rules()?);
+        session_manager.init_transcripts(&db_dir)?;
+        session_manager.init_session_policy(&db_dir)?;
+        session_manager.init_shell_profiles(&db_dir)?;
+        #[cfg(unix)]
+        session_manager.set_local_daemon(
+            db_dir.join(crate::infra::session::daemon::SOCKET_NAME),
+            std::env::current_exe().ok(),
+        );
+        session_manager.set_history_store(history_repo.clone());
+        session_manager.set_command_store(command_repo.clone());
+        let session_repo = Arc::new(LiveSessionRepository::new(session_manager.clone()));
 
         Ok(Self {
             connection_service: ConnectionService::new(connection_repo),
-            session_service: SessionService::new(session_repo),
+            session_service: SessionService::new(session_repo, history_repo, command_repo),
+            script_service: ScriptService::new(script_repo),
             sync_service: SyncService::new(storage_adapter),
+            trigger_service,
             session_manager,
         })
     }
@@ -47,10 +71,18 @@ impl AppState {
         &self.session_service
     }
 
+    pub fn script_service(&self) -> &ScriptService {
+        &self.script_service
+    }
+
     pub fn sync_service(&self) -> &SyncService {
         &self.sync_service
     }
 
+    pub fn trigger_service(&self) -> &TriggerService {
+        &self.trigger_service
+    }
+
     pub fn session_manager(&self) -> &SessionManager {
         &self.session_manager
     }
@@ -1,7 +1,7 @@
 use tauri::State;
 
 use crate::app_state::AppState;
-use crate::domain::models::Connection;
+use crate::domain::models::{AgentForwarding, Connection, StartupActions};
 
 #[tauri::command]
 pub async fn list_connections(state: State<'_, AppState>) -> Result<Vec<Connection>, String> {
@@ -67,6 +67,30 @@ pub async fn update_connection(
         .map_err(|err| err.to_string())
 }
 
+#[tauri::command]
+pub async fn update_connection_startup(
+    state: State<'_, AppState>,
+    id: String,
+    startup: StartupActions,
+) -> Result<Connection, String> {
+    state
+        .connection_service()
+        .update_startup_actions(&id, startup)
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn update_connection_agent_forwarding(
+    state: State<'_, AppState>,
+    id: String,
+    agent_forwarding: AgentForwarding,
+) -> Result<Connection, String> {
+    state
+        .connection_service()
+        .update_agent_forwarding(&id, agent_forwarding)
+        .map_err(|err| err.to_string())
+}
+
 #[tauri::command]
 pub async fn delete_connection(state: State<'_, AppState>, id: String) -> Result<(), String> {
     state
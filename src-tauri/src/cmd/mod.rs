@@ -1,5 +1,7 @@
 pub mod connections;
+pub mod scripts;
 pub mod sessions;
 pub mod settings;
 pub mod sync;
 pub mod terminal;
+pub mod triggers;
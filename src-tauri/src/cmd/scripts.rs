@@ -0,0 +1,126 @@
+use tauri::{AppHandle, State, WebviewWindow};
+
+use crate::app_state::AppState;
+use crate::cmd::sessions::SessionSecretPayload;
+use crate::domain::models::{AutomationScript, ScriptStep};
+use crate::domain::services::script_service::SaveScript;
+use crate::infra::session::events::Events;
+use crate::infra::session::SessionSecret;
+
+#[tauri::command]
+pub async fn list_scripts(state: State<'_, AppState>) -> Result<Vec<AutomationScript>, String> {
+    state
+        .script_service()
+        .list_scripts()
+        .map_err(|err| err.to_string())
+}
+
+#[derive(serde::Deserialize)]
+pub struct SaveScriptPayload {
+    pub id: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub steps: Vec<ScriptStep>,
+}
+
+#[tauri::command]
+pub async fn save_script(
+    state: State<'_, AppState>,
+    payload: SaveScriptPayload,
+) -> Result<AutomationScript, String> {
+    if payload.name.trim().is_empty() {
+        return Err("脚本名称不能为空".into());
+    }
+    state
+        .script_service()
+        .save_script(SaveScript {
+            id: payload.id,
+            name: payload.name,
+            description: payload.description,
+            steps: payload.steps,
+        })
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_script(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    state
+        .script_service()
+        .delete_script(&id)
+        .map_err(|err| err.to_string())
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunScriptPayload {
+    pub script_id: String,
+    /// 在已有会话上运行；为空时按 `connection_id` 新建会话。
+    pub session_id: Option<String>,
+    pub connection_id: Option<String>,
+    pub secret: Option<SessionSecretPayload>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptRunInfo {
+    pub run_id: String,
+    pub session_id: String,
+}
+
+/// 启动脚本并立即返回，进度通过 `script-progress` / `script-finished` 事件推送，
+/// 可用 `cancel_command` 传入 run id 取消。
+#[tauri::command]
+pub async fn run_script(
+    app: AppHandle,
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    payload: RunScriptPayload,
+) -> Result<ScriptRunInfo, String> {
+    let script = state
+        .script_service()
+        .get_script(&payload.script_id)
+        .map_err(|err| err.to_string())?
+        .ok_or_else(|| "脚本不存在".to_string())?;
+
+    let events = Events::from(app).for_target(window.label());
+    let manager = state.session_manager();
+    if let Some(session_id) = payload.session_id {
+        // 观察窗口不能在会话上运行脚本
+        let run_id = manager
+            .run_script_from(window.label(), events, &session_id, script)
+            .await
+            .map_err(|err| err.to_string())?;
+        return Ok(ScriptRunInfo { run_id, session_id });
+    }
+
+    let connection = match &payload.connection_id {
+        Some(id) => Some(
+            state
+                .connection_service()
+                .get_connection(id)
+                .map_err(|err| err.to_string())?
+                .ok_or_else(|| "连接不存在".to_string())?,
+        ),
+        None => None,
+    };
+    let session_id = manager
+        .create_shell_session(
+            events.clone(),
+            connection,
+            payload.secret.map(SessionSecret::from),
+        )
+        .await
+        .map_err(|err| err.to_string())?;
+    // 会话在脚本订阅之前可能已有输出（登录提示等），从头匹配；脚本没能启动时关闭刚创建的会话
+    match manager
+        .run_script_from_start(events, &session_id, script)
+        .await
+    {
+        Ok(run_id) => Ok(ScriptRunInfo { run_id, session_id }),
+        Err(err) => {
+            let _ = manager.close_session(&session_id).await;
+            Err(err.to_string())
+        }
+    }
+}
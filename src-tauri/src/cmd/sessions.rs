@@ -1,7 +1,16 @@
-use tauri::{AppHandle, State};
+use std::path::PathBuf;
+
+use tauri::{AppHandle, State, WebviewWindow};
 
 use crate::app_state::AppState;
-use crate::domain::models::SessionSummary;
+use crate::domain::models::{
+    ObserverAttachment, ScreenSnapshot, ScrollbackMatch, ScrollbackQuery, SessionPolicySettings,
+    SessionRecord, SessionSummary, ShellCommand, ShellProfile, ShellProfileSettings,
+    TranscriptFormat, TranscriptSettings,
+};
+use crate::infra::session::events::Events;
+use crate::infra::session::state::SessionStatePayload;
+use crate::infra::session::transfer::SessionTransferPayload;
 use crate::infra::session::SessionSecret;
 
 #[derive(Debug, serde::Deserialize)]
@@ -23,9 +32,11 @@ pub async fn list_session_summaries(
 #[tauri::command]
 pub async fn create_shell_session(
     app: AppHandle,
+    window: WebviewWindow,
     state: State<'_, AppState>,
     connection_id: Option<String>,
     secret: Option<SessionSecretPayload>,
+    profile_id: Option<String>,
 ) -> Result<String, String> {
     let connection = match connection_id {
         Some(id) => state
@@ -34,11 +45,18 @@ pub async fn create_shell_session(
             .map_err(|err| err.to_string())?,
         None => None,
     };
-    state
-        .session_manager()
-        .create_shell_session(app, connection, secret.map(SessionSecret::from))
-        .await
-        .map_err(|err| err.to_string())
+    let manager = state.session_manager();
+    // 会话事件只推给所属窗口，观察窗口经各自的附加收到输出，不会重复
+    let events = Events::from(app).for_target(window.label());
+    let result = match (connection, profile_id) {
+        (None, Some(profile_id)) => manager.create_local_shell(events, Some(&profile_id)).await,
+        (connection, _) => {
+            manager
+                .create_shell_session(events, connection, secret.map(SessionSecret::from))
+                .await
+        }
+    };
+    result.map_err(|err| err.to_string())
 }
 
 impl From<SessionSecretPayload> for SessionSecret {
@@ -51,25 +69,490 @@ impl From<SessionSecretPayload> for SessionSecret {
 
 #[tauri::command]
 pub async fn send_session_input(
+    window: WebviewWindow,
     state: State<'_, AppState>,
     session_id: String,
     data: String,
 ) -> Result<(), String> {
     state
         .session_manager()
-        .send_input(&session_id, &data)
+        .send_input_from(window.label(), &session_id, &data)
         .await
         .map_err(|err| err.to_string())
 }
 
 #[tauri::command]
 pub async fn close_shell_session(
+    window: WebviewWindow,
     state: State<'_, AppState>,
     session_id: String,
 ) -> Result<(), String> {
+    let manager = state.session_manager();
+    manager
+        .ensure_writable(window.label(), &session_id)
+        .await
+        .map_err(|err| err.to_string())?;
+    manager
+        .close_session(&session_id)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn get_session_state(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<SessionStatePayload, String> {
     state
         .session_manager()
-        .close_session(&session_id)
+        .session_state(&session_id)
+        .ok_or_else(|| "会话不存在".to_string())
+}
+
+#[tauri::command]
+pub async fn list_session_history(
+    state: State<'_, AppState>,
+) -> Result<Vec<SessionRecord>, String> {
+    Ok(state.session_manager().session_history())
+}
+
+/// 从数据库读取已结束的会话，可按连接过滤。
+#[tauri::command]
+pub async fn list_recent_sessions(
+    state: State<'_, AppState>,
+    connection_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<SessionRecord>, String> {
+    state
+        .session_service()
+        .recent_sessions(connection_id.as_deref(), limit.unwrap_or(50))
+        .map_err(|err| err.to_string())
+}
+
+/// 重新连接或切换窗口时用服务端屏幕模型还原终端。
+#[tauri::command]
+pub async fn get_screen_snapshot(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Option<ScreenSnapshot>, String> {
+    Ok(state.session_manager().screen_snapshot(&session_id))
+}
+
+/// 标签缩略图的文本预览。
+#[tauri::command]
+pub async fn get_screen_thumbnail(
+    state: State<'_, AppState>,
+    session_id: String,
+    max_rows: Option<usize>,
+    max_cols: Option<usize>,
+) -> Result<Option<Vec<String>>, String> {
+    Ok(state.session_manager().screen_thumbnail(
+        &session_id,
+        max_rows.unwrap_or(8),
+        max_cols.unwrap_or(40),
+    ))
+}
+
+/// 在服务端回滚缓冲中搜索；不指定会话时搜索全部运行中会话。
+#[tauri::command]
+pub async fn search_sessions(
+    state: State<'_, AppState>,
+    session_id: Option<String>,
+    query: ScrollbackQuery,
+) -> Result<Vec<ScrollbackMatch>, String> {
+    state
+        .session_manager()
+        .search_scrollback(session_id.as_deref(), &query)
+        .map_err(|err| err.to_string())
+}
+
+/// 运行中会话经 shell 集成识别出的命令。
+#[tauri::command]
+pub async fn list_session_commands(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<ShellCommand>, String> {
+    Ok(state.session_manager().session_commands(&session_id))
+}
+
+/// 某个连接保存在数据库中的命令历史。
+#[tauri::command]
+pub async fn list_connection_commands(
+    state: State<'_, AppState>,
+    connection_id: String,
+    limit: Option<usize>,
+) -> Result<Vec<ShellCommand>, String> {
+    state
+        .session_service()
+        .recent_commands(&connection_id, limit.unwrap_or(100))
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn rerun_last_command(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<String, String> {
+    let manager = state.session_manager();
+    manager
+        .ensure_writable(window.label(), &session_id)
+        .await
+        .map_err(|err| err.to_string())?;
+    manager
+        .rerun_last_command(&session_id)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+/// 安装 shell 集成脚本所需的 nonce；只读观察窗口拿不到，无法伪造命令上报。
+#[tauri::command]
+pub async fn get_shell_integration_nonce(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<String, String> {
+    let manager = state.session_manager();
+    manager
+        .ensure_writable(window.label(), &session_id)
+        .await
+        .map_err(|err| err.to_string())?;
+    manager
+        .shell_integration_nonce(&session_id)
+        .ok_or_else(|| "会话不存在".to_string())
+}
+
+/// 以同一连接打开新会话，并进入原会话最近上报的工作目录。
+#[tauri::command]
+pub async fn open_session_in_cwd(
+    app: AppHandle,
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    session_id: String,
+    secret: Option<SessionSecretPayload>,
+) -> Result<String, String> {
+    let manager = state.session_manager();
+    let events = Events::from(app).for_target(window.label());
+    let cwd = manager.session_cwd(&session_id);
+    let summary = manager
+        .live_sessions()
+        .into_iter()
+        .find(|summary| summary.id == session_id)
+        .ok_or_else(|| "会话不存在".to_string())?;
+    let result = match summary.connection_id {
+        Some(id) => {
+            let mut connection = state
+                .connection_service()
+                .get_connection(&id)
+                .map_err(|err| err.to_string())?
+                .ok_or_else(|| "连接不存在".to_string())?;
+            if cwd.is_some() {
+                connection.startup.working_dir = cwd;
+            }
+            manager
+                .create_shell_session(events, Some(connection), secret.map(SessionSecret::from))
+                .await
+        }
+        None => match cwd {
+            Some(dir) => manager.create_local_shell_in(events, &dir).await,
+            None => manager.create_shell_session(events, None, None).await,
+        },
+    };
+    result.map_err(|err| err.to_string())
+}
+
+/// 会话当前正在进行或等待确认的 ZMODEM / trzsz 传输。
+#[tauri::command]
+pub async fn get_active_transfer(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Option<SessionTransferPayload>, String> {
+    Ok(state.session_manager().active_transfer(&session_id))
+}
+
+/// 确认传输：上传时传入本地文件，下载时传入保存目录。
+#[tauri::command]
+pub async fn accept_file_transfer(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    session_id: String,
+    paths: Vec<String>,
+) -> Result<(), String> {
+    let manager = state.session_manager();
+    manager
+        .ensure_writable(window.label(), &session_id)
+        .await
+        .map_err(|err| err.to_string())?;
+    manager
+        .accept_transfer(&session_id, paths.into_iter().map(PathBuf::from).collect())
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn cancel_file_transfer(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<(), String> {
+    let manager = state.session_manager();
+    manager
+        .ensure_writable(window.label(), &session_id)
+        .await
+        .map_err(|err| err.to_string())?;
+    manager
+        .cancel_transfer(&session_id)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn respond_agent_request(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    session_id: String,
+    request_id: String,
+    allow: bool,
+) -> Result<(), String> {
+    let manager = state.session_manager();
+    manager
+        .ensure_writable(window.label(), &session_id)
+        .await
+        .map_err(|err| err.to_string())?;
+    manager
+        .respond_agent_request(&session_id, &request_id, allow)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+/// 以只读观察者身份把调用窗口附加到会话，输出只推送给该窗口。
+#[tauri::command]
+pub async fn attach_session_observer(
+    app: AppHandle,
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<ObserverAttachment, String> {
+    let events = Events::from(app).for_target(window.label());
+    state
+        .session_manager()
+        .attach_observer(&session_id, window.label(), events)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn detach_session_observer(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    attachment_id: String,
+) -> Result<(), String> {
+    state
+        .session_manager()
+        .detach_observer(window.label(), &attachment_id)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn list_session_observers(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<ObserverAttachment>, String> {
+    Ok(state.session_manager().list_observers(&session_id))
+}
+
+#[tauri::command]
+pub async fn create_broadcast_group(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    session_ids: Vec<String>,
+) -> Result<String, String> {
+    let manager = state.session_manager();
+    // 广播会向每个成员写入输入，观察窗口不能把只读会话拉进组
+    for session_id in &session_ids {
+        manager
+            .ensure_writable(window.label(), session_id)
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+    manager
+        .create_broadcast_group(session_ids)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn add_broadcast_member(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    group_id: String,
+    session_id: String,
+) -> Result<(), String> {
+    let manager = state.session_manager();
+    manager
+        .ensure_writable(window.label(), &session_id)
+        .await
+        .map_err(|err| err.to_string())?;
+    manager
+        .add_broadcast_member(&group_id, &session_id)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn remove_broadcast_member(
+    state: State<'_, AppState>,
+    group_id: String,
+    session_id: String,
+) -> Result<(), String> {
+    state
+        .session_manager()
+        .remove_broadcast_member(&group_id, &session_id)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn list_broadcast_members(
+    state: State<'_, AppState>,
+    group_id: String,
+) -> Result<Vec<String>, String> {
+    state
+        .session_manager()
+        .broadcast_members(&group_id)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn dissolve_broadcast_group(
+    state: State<'_, AppState>,
+    group_id: String,
+) -> Result<(), String> {
+    state
+        .session_manager()
+        .dissolve_broadcast_group(&group_id)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn get_transcript_settings(
+    state: State<'_, AppState>,
+) -> Result<TranscriptSettings, String> {
+    Ok(state.session_manager().transcript_settings())
+}
+
+#[tauri::command]
+pub async fn update_transcript_settings(
+    state: State<'_, AppState>,
+    settings: TranscriptSettings,
+) -> Result<(), String> {
+    state
+        .session_manager()
+        .update_transcript_settings(settings)
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn get_session_policy_settings(
+    state: State<'_, AppState>,
+) -> Result<SessionPolicySettings, String> {
+    Ok(state.session_manager().session_policy_settings())
+}
+
+#[tauri::command]
+pub async fn update_session_policy_settings(
+    state: State<'_, AppState>,
+    settings: SessionPolicySettings,
+) -> Result<(), String> {
+    state
+        .session_manager()
+        .update_session_policy_settings(settings)
+        .map_err(|err| err.to_string())
+}
+
+/// 已保存与从 `/etc/shells` 检测到的本地 shell 配置。
+#[tauri::command]
+pub async fn list_shell_profiles(
+    state: State<'_, AppState>,
+) -> Result<ShellProfileSettings, String> {
+    Ok(state.session_manager().shell_profiles())
+}
+
+#[tauri::command]
+pub async fn save_shell_profile(
+    state: State<'_, AppState>,
+    profile: ShellProfile,
+) -> Result<ShellProfile, String> {
+    state
+        .session_manager()
+        .save_shell_profile(profile)
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_shell_profile(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    state
+        .session_manager()
+        .delete_shell_profile(&id)
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn set_default_shell_profile(
+    state: State<'_, AppState>,
+    id: Option<String>,
+) -> Result<(), String> {
+    state
+        .session_manager()
+        .set_default_shell_profile(id.as_deref())
+        .map_err(|err| err.to_string())
+}
+
+/// 开启后新建的本地会话交给后台守护进程托管，应用退出后继续运行（仅 Unix）。
+#[tauri::command]
+pub async fn set_persistent_local_sessions(
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    state
+        .session_manager()
+        .set_persistent_local_sessions(enabled)
+        .map_err(|err| err.to_string())
+}
+
+/// 应用启动后调用：重新附加守护进程中仍在运行的本地会话，返回会话 id。
+#[tauri::command]
+pub async fn reattach_local_sessions(
+    app: AppHandle,
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    #[cfg(unix)]
+    return state
+        .session_manager()
+        .reattach_local_sessions(Events::from(app).for_target(window.label()))
         .await
+        .map_err(|err| err.to_string());
+    #[cfg(not(unix))]
+    {
+        let _ = (app, window, state);
+        Ok(Vec::new())
+    }
+}
+
+#[tauri::command]
+pub async fn export_session_transcript(
+    state: State<'_, AppState>,
+    session_id: String,
+    format: TranscriptFormat,
+    target_path: String,
+) -> Result<(), String> {
+    let path = PathBuf::from(target_path);
+    state
+        .session_manager()
+        .export_transcript(&session_id, format, path.as_path())
         .map_err(|err| err.to_string())
 }
@@ -1,50 +1,158 @@
-use tauri::State;
-use tokio::process::Command;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tauri::{AppHandle, State};
+use uuid::Uuid;
 
 use crate::app_state::AppState;
+use crate::cmd::sessions::SessionSecretPayload;
+use crate::domain::models::{CommandOutput, MultiExecSummary};
+use crate::infra::session::exec::ExecRequest;
+use crate::infra::session::multi_exec::MultiExecOptions;
+use crate::infra::session::SessionSecret;
 
 #[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TerminalCommandPayload {
     pub command: String,
     pub connection_id: Option<String>,
+    /// 供 `cancel_command` 与 `command-output` 事件使用，未提供时自动生成。
+    pub job_id: Option<String>,
+    #[serde(default)]
+    pub stream: bool,
+    pub stdin: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub cwd: Option<String>,
+    pub timeout_ms: Option<u64>,
+    pub secret: Option<SessionSecretPayload>,
 }
 
 #[tauri::command]
 pub async fn run_local_command(
-    _state: State<'_, AppState>,
+    app: AppHandle,
+    state: State<'_, AppState>,
     payload: TerminalCommandPayload,
-) -> Result<String, String> {
+) -> Result<CommandOutput, String> {
     if payload.command.trim().is_empty() {
         return Err("命令不能为空".into());
     }
 
-    #[cfg(target_os = "windows")]
-    let mut cmd = Command::new("cmd");
-
-    #[cfg(not(target_os = "windows"))]
-    let mut cmd = Command::new("sh");
+    let connection = match &payload.connection_id {
+        Some(connection_id) => Some(
+            state
+                .connection_service()
+                .get_connection(connection_id)
+                .map_err(|err| err.to_string())?
+                .ok_or_else(|| "连接不存在".to_string())?,
+        ),
+        None => None,
+    };
+    let request = ExecRequest {
+        command: payload.command,
+        stdin: payload.stdin,
+        env: payload.env,
+        cwd: payload.cwd,
+        timeout: payload.timeout_ms.map(Duration::from_millis),
+        cancel: None,
+    };
+    let job_id = payload
+        .job_id
+        .unwrap_or_else(|| format!("cmd-{}", Uuid::new_v4().simple()));
+    state
+        .session_manager()
+        .run_command_job(
+            app.into(),
+            job_id,
+            connection,
+            payload.secret.map(SessionSecret::from),
+            request,
+            payload.stream,
+        )
+        .await
+        .map_err(|err| err.to_string())
+}
 
-    #[cfg(target_os = "windows")]
-    cmd.arg("/C").arg(&payload.command);
+#[tauri::command]
+pub async fn cancel_command(state: State<'_, AppState>, job_id: String) -> Result<bool, String> {
+    Ok(state.session_manager().cancel_command(&job_id))
+}
 
-    #[cfg(not(target_os = "windows"))]
-    cmd.arg("-c").arg(&payload.command);
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiHostCommandPayload {
+    pub command: String,
+    pub job_id: Option<String>,
+    pub group_id: Option<String>,
+    #[serde(default)]
+    pub connection_ids: Vec<String>,
+    pub stdin: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub concurrency: usize,
+    #[serde(default)]
+    pub stop_on_failure: bool,
+    /// 以连接 id 为键的凭据，未提供的主机走 ssh-agent。
+    #[serde(default)]
+    pub secrets: HashMap<String, SessionSecretPayload>,
+}
 
-    let output = cmd.output().await.map_err(|err| err.to_string())?;
-    let mut result = String::new();
-    if !output.stdout.is_empty() {
-        result.push_str(&String::from_utf8_lossy(&output.stdout));
+#[tauri::command]
+pub async fn run_multi_host_command(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    payload: MultiHostCommandPayload,
+) -> Result<MultiExecSummary, String> {
+    if payload.command.trim().is_empty() {
+        return Err("命令不能为空".into());
     }
-    if !output.stderr.is_empty() {
-        if !result.is_empty() {
-            result.push_str("\n");
+
+    let mut targets = match &payload.group_id {
+        Some(group_id) => state
+            .connection_service()
+            .list_group_connections(group_id)
+            .map_err(|err| err.to_string())?,
+        None => Vec::new(),
+    };
+    for id in &payload.connection_ids {
+        if targets.iter().any(|conn| &conn.id == id) {
+            continue;
         }
-        result.push_str(&String::from_utf8_lossy(&output.stderr));
+        let connection = state
+            .connection_service()
+            .get_connection(id)
+            .map_err(|err| err.to_string())?
+            .ok_or_else(|| format!("连接不存在: {id}"))?;
+        targets.push(connection);
     }
-
-    if result.trim().is_empty() {
-        result.push_str("(命令执行成功，但无输出)");
+    if targets.is_empty() {
+        return Err("没有可执行的目标主机".into());
     }
 
-    Ok(result)
+    let request = ExecRequest {
+        command: payload.command,
+        stdin: payload.stdin,
+        env: payload.env,
+        cwd: None,
+        timeout: payload.timeout_ms.map(Duration::from_millis),
+        cancel: None,
+    };
+    let options = MultiExecOptions {
+        concurrency: payload.concurrency,
+        stop_on_failure: payload.stop_on_failure,
+    };
+    let secrets = payload
+        .secrets
+        .into_iter()
+        .map(|(id, secret)| (id, SessionSecret::from(secret)))
+        .collect();
+    let job_id = payload
+        .job_id
+        .unwrap_or_else(|| format!("exec-{}", Uuid::new_v4().simple()));
+    Ok(state
+        .session_manager()
+        .exec_many(app.into(), job_id, targets, secrets, request, options)
+        .await)
 }
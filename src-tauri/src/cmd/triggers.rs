@@ -0,0 +1,70 @@
+use tauri::State;
+
+use crate::app_state::AppState;
+use crate::domain::models::{TriggerAction, TriggerRule, TriggerScope};
+use crate::domain::services::trigger_service::SaveTrigger;
+
+#[tauri::command]
+pub async fn list_trigger_rules(state: State<'_, AppState>) -> Result<Vec<TriggerRule>, String> {
+    state
+        .trigger_service()
+        .list_rules()
+        .map_err(|err| err.to_string())
+}
+
+#[derive(serde::Deserialize)]
+pub struct SaveTriggerPayload {
+    pub id: Option<String>,
+    pub name: String,
+    pub pattern: String,
+    pub scope: TriggerScope,
+    pub action: TriggerAction,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[tauri::command]
+pub async fn save_trigger_rule(
+    state: State<'_, AppState>,
+    payload: SaveTriggerPayload,
+) -> Result<TriggerRule, String> {
+    if payload.pattern.is_empty() {
+        return Err("匹配规则不能为空".into());
+    }
+    let rule = state
+        .trigger_service()
+        .save_rule(SaveTrigger {
+            id: payload.id,
+            name: payload.name,
+            pattern: payload.pattern,
+            scope: payload.scope,
+            action: payload.action,
+            enabled: payload.enabled,
+        })
+        .map_err(|err| err.to_string())?;
+    reload_rules(&state)?;
+    Ok(rule)
+}
+
+#[tauri::command]
+pub async fn delete_trigger_rule(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    state
+        .trigger_service()
+        .delete_rule(&id)
+        .map_err(|err| err.to_string())?;
+    reload_rules(&state)
+}
+
+/// 规则变更后同步到 SessionManager，已打开的会话立即生效。
+fn reload_rules(state: &AppState) -> Result<(), String> {
+    let rules = state
+        .trigger_service()
+        .list_rules()
+        .map_err(|err| err.to_string())?;
+    state.session_manager().set_trigger_rules(&rules);
+    Ok(())
+}
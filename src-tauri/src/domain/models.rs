@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,15 +66,537 @@ pub struct Connection {
     #[serde(default)]
     pub status: ConnectionHealth,
     pub last_connected_at: Option<String>,
+    #[serde(default)]
+    pub startup: StartupActions,
+    #[serde(default)]
+    pub agent_forwarding: AgentForwarding,
+}
+
+/// SSH agent 转发（`ssh -A`）策略。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AgentForwarding {
+    #[default]
+    Disabled,
+    /// 远端的签名请求直接交给本地 agent。
+    Allow,
+    /// 每次签名前推送 `session-agent-request`，由用户确认。
+    Confirm,
+}
+
+/// 建立 SSH shell 后依次执行的启动动作。
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupActions {
+    /// 通过 channel setenv 发送；被 sshd 拒绝时退化为 `export`。
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub working_dir: Option<String>,
+    #[serde(default)]
+    pub steps: Vec<StartupStep>,
+}
+
+impl StartupActions {
+    pub fn is_empty(&self) -> bool {
+        self.env.is_empty() && self.working_dir.is_none() && self.steps.is_empty()
+    }
+}
+
+/// 环境变量名须形如 `[A-Za-z_][A-Za-z0-9_]*`：被 sshd 拒绝的变量会拼进 `export` 命令，不能带入 shell 语法。
+pub fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum StartupStep {
+    /// 发送一行命令（自动追加换行）。
+    #[serde(rename_all = "camelCase")]
+    Command { command: String },
+    /// 等待输出匹配 `pattern`（正则）后发送 `send`（自动追加换行）。
+    #[serde(rename_all = "camelCase")]
+    Expect {
+        pattern: String,
+        send: String,
+        timeout_ms: Option<u64>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SessionSummary {
     pub id: String,
-    pub connection_id: String,
+    /// 本地 shell 没有关联连接。
+    pub connection_id: Option<String>,
     pub title: String,
-    pub latency_ms: u32,
+    pub state: SessionState,
+    pub started_at: String,
     pub status_label: String,
     pub updated_at: String,
+    /// 运行中的 SSH 会话实测的往返时延，没有样本时为空。
+    #[serde(default)]
+    pub latency: Option<LatencyStats>,
+}
+
+/// 最近若干次往返时延的统计（毫秒）。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyStats {
+    pub last_ms: f64,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+    pub samples: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionState {
+    Connecting,
+    Authenticating,
+    Connected,
+    Reconnecting,
+    Closed,
+    Failed,
+}
+
+impl SessionState {
+    /// Closed / Failed 为终态，之后不再接受任何迁移。
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Self::Closed | Self::Failed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStateReason {
+    UserClosed,
+    RemoteClosed,
+    ChannelDisconnected,
+    ProcessExited,
+    Error,
+    /// 超过会话策略的空闲时长被自动关闭。
+    IdleTimeout,
+    /// 超过会话策略的最长存活时长被自动关闭。
+    MaxDuration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionErrorCode {
+    ConnectFailed,
+    HandshakeFailed,
+    AuthFailed,
+    ChannelFailed,
+    IoError,
+    Unknown,
+}
+
+impl SessionErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ConnectFailed => "connect_failed",
+            Self::HandshakeFailed => "handshake_failed",
+            Self::AuthFailed => "auth_failed",
+            Self::ChannelFailed => "channel_failed",
+            Self::IoError => "io_error",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for SessionErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionRecord {
+    pub session_id: String,
+    pub connection_id: Option<String>,
+    pub title: String,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub exit_code: Option<i32>,
+    pub exit_signal: Option<String>,
+    pub close_reason: Option<SessionStateReason>,
+    pub message: Option<String>,
+    /// 会话结束后才有值。
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    /// 从远端（或本地进程）读到的字节数。
+    #[serde(default)]
+    pub bytes_in: u64,
+    /// 用户输入写入会话的字节数。
+    #[serde(default)]
+    pub bytes_out: u64,
+}
+
+/// 服务端屏幕模型的快照，坐标从 0 开始。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenSnapshot {
+    pub rows: u16,
+    pub cols: u16,
+    pub cursor_row: u16,
+    pub cursor_col: u16,
+    pub cursor_visible: bool,
+    pub alternate_screen: bool,
+    pub title: String,
+    /// 可见区域的纯文本，每行一项，已去掉行尾空白。
+    pub lines: Vec<String>,
+    /// 重绘整个屏幕（含颜色与光标位置）的转义序列。
+    pub formatted: String,
+}
+
+/// 附加到运行中会话的只读观察者。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObserverAttachment {
+    pub attachment_id: String,
+    pub session_id: String,
+    /// 接收输出事件的窗口标签。
+    pub window: String,
+    pub attached_at: String,
+    /// 附加时的屏幕快照，观察者窗口据此还原当前画面；列表查询时为空。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<ScreenSnapshot>,
+}
+
+/// 回滚缓冲搜索条件。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollbackQuery {
+    pub pattern: String,
+    /// 为 false 时按普通文本匹配。
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// 命中行前后各带回的行数，默认 2。
+    pub context_lines: Option<usize>,
+    /// 最多返回的命中数，默认 200。
+    pub limit: Option<usize>,
+}
+
+/// 回滚缓冲中的一处命中，行号从 1 开始，列为字符偏移。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollbackMatch {
+    pub session_id: String,
+    pub session_title: String,
+    pub line_number: usize,
+    pub line: String,
+    pub match_start: usize,
+    pub match_end: usize,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// 通过 shell 集成（OSC 133）识别出的一条交互命令。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellCommand {
+    pub id: String,
+    pub session_id: String,
+    pub connection_id: Option<String>,
+    pub command: String,
+    /// 执行时的工作目录（来自 OSC 7），shell 未上报时为空。
+    pub cwd: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub exit_signal: Option<String>,
+    #[serde(default)]
+    pub timed_out: bool,
+    #[serde(default)]
+    pub cancelled: bool,
+    #[serde(default)]
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostExecStatus {
+    Succeeded,
+    Failed,
+    TimedOut,
+    Cancelled,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostExecResult {
+    pub connection_id: String,
+    pub connection_name: String,
+    pub status: HostExecStatus,
+    pub exit_code: Option<i32>,
+    pub exit_signal: Option<String>,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiExecSummary {
+    pub job_id: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub duration_ms: u64,
+    pub results: Vec<HostExecResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutomationScript {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub steps: Vec<ScriptStep>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptStep {
+    /// 供 `goto` 跳转的标签。
+    pub label: Option<String>,
+    #[serde(flatten)]
+    pub action: ScriptAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ScriptAction {
+    /// 原样写入会话输入（需要回车时自行带上 `\n`）。
+    #[serde(rename_all = "camelCase")]
+    Send { data: String },
+    /// 等待任一分支的正则命中，按分支顺序优先；超时走 `on_timeout`，未配置则脚本失败。
+    #[serde(rename_all = "camelCase")]
+    Expect {
+        branches: Vec<ExpectBranch>,
+        timeout_ms: Option<u64>,
+        on_timeout: Option<ScriptJump>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Sleep { ms: u64 },
+    #[serde(rename_all = "camelCase")]
+    Jump { to: ScriptJump },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpectBranch {
+    pub pattern: String,
+    #[serde(default)]
+    pub then: ScriptJump,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ScriptJump {
+    #[default]
+    Next,
+    Goto {
+        label: String,
+    },
+    Succeed,
+    Fail {
+        message: Option<String>,
+    },
+}
+
+/// 输出触发规则：会话输出命中 `pattern` 时推送 `session-trigger` 事件。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerRule {
+    pub id: String,
+    pub name: String,
+    pub pattern: String,
+    pub scope: TriggerScope,
+    pub action: TriggerAction,
+    pub enabled: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TriggerScope {
+    Global,
+    #[serde(rename_all = "camelCase")]
+    Group {
+        group_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Connection {
+        connection_id: String,
+    },
+}
+
+impl TriggerScope {
+    /// 本地 shell 没有连接，只匹配全局规则。
+    pub fn applies_to(&self, connection: Option<&Connection>) -> bool {
+        match (self, connection) {
+            (Self::Global, _) => true,
+            (Self::Group { group_id }, Some(conn)) => conn.group_id.as_deref() == Some(group_id),
+            (Self::Connection { connection_id }, Some(conn)) => &conn.id == connection_id,
+            _ => false,
+        }
+    }
+}
+
+/// 前端据此决定如何呈现命中：高亮、系统通知或提示自动填充。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerAction {
+    Highlight,
+    Notify,
+    Autofill,
+}
+
+/// 会话文本日志配置，保存在应用数据目录的 `transcript.json`。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TranscriptSettings {
+    pub enabled: bool,
+    /// 为空时使用应用数据目录下的 `transcripts/`。
+    pub directory: Option<String>,
+    /// 单个文件超过该大小后轮转，0 表示不轮转。
+    pub max_file_bytes: u64,
+    /// 超过该天数的日志在启动、修改配置与轮转时清理，0 表示永久保留。
+    pub retention_days: u32,
+}
+
+impl Default for TranscriptSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: None,
+            max_file_bytes: 10 * 1024 * 1024,
+            retention_days: 30,
+        }
+    }
+}
+
+/// 一级会话超时策略；字段为空表示沿用上一级，为 0 表示不限制。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionPolicy {
+    /// 既无输入也无输出超过该秒数后关闭。
+    pub idle_timeout_secs: Option<u64>,
+    /// 自建立起超过该秒数后关闭。
+    pub max_duration_secs: Option<u64>,
+}
+
+/// 会话超时策略，保存在应用数据目录的 `session_policy.json`。
+/// 逐项按 连接 > 分组 > 全局 取最具体的一级。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionPolicySettings {
+    pub global: SessionPolicy,
+    /// 以分组 id 为键。
+    pub groups: HashMap<String, SessionPolicy>,
+    /// 以连接 id 为键。
+    pub connections: HashMap<String, SessionPolicy>,
+    /// 强制关闭前提前多少秒推送 `session-policy-warning`。
+    pub warning_secs: u64,
+}
+
+impl Default for SessionPolicySettings {
+    fn default() -> Self {
+        Self {
+            global: SessionPolicy::default(),
+            groups: HashMap::new(),
+            connections: HashMap::new(),
+            warning_secs: 60,
+        }
+    }
+}
+
+/// 本地 shell 配置：新建本地标签时启动的程序及其参数、环境与目录。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellProfile {
+    pub id: String,
+    pub name: String,
+    /// 可执行文件路径，或可在 `PATH` 中找到的程序名。
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// 为空时使用应用的当前目录；支持 `~` 开头。
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// 以登录 shell 启动（Unix 下 argv[0] 前加 `-`，与 `bash -l` 效果相同）。
+    #[serde(default)]
+    pub login: bool,
+    /// 从 `/etc/shells` 检测得到、尚未保存的配置。
+    #[serde(default)]
+    pub detected: bool,
+}
+
+/// 本地 shell 配置列表，保存在应用数据目录的 `shell_profiles.json`。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ShellProfileSettings {
+    pub profiles: Vec<ShellProfile>,
+    /// 新建本地标签使用的配置，为空时启动系统默认 shell。
+    pub default_profile_id: Option<String>,
+    /// 本地会话交给后台守护进程托管，应用退出后继续运行（仅 Unix）。
+    pub persistent_sessions: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    Text,
+    Html,
+}
+
+/// 会话内嵌的文件传输协议（`rz`/`sz` 与 `trz`/`tsz`）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferProtocol {
+    Zmodem,
+    Trzsz,
+}
+
+/// 以本机为视角：`Upload` 为远端等待接收（`rz`/`trz`），`Download` 为远端发送（`sz`/`tsz`）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferState {
+    /// 已识别起始序列，等待前端选择文件或保存目录。
+    Pending,
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
 }
@@ -1,10 +1,12 @@
 use std::sync::Arc;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 
 use uuid::Uuid;
 
-use crate::domain::models::{AuthType, Connection, ConnectionHealth, Protocol};
+use crate::domain::models::{
+    is_env_name, AgentForwarding, AuthType, Connection, ConnectionHealth, Protocol, StartupActions,
+};
 
 pub trait ConnectionRepository: Send + Sync {
     fn list(&self) -> Result<Vec<Connection>>;
@@ -62,6 +64,8 @@ impl ConnectionService {
             favorite: false,
             status: ConnectionHealth::Idle,
             last_connected_at: None,
+            startup: StartupActions::default(),
+            agent_forwarding: AgentForwarding::default(),
         };
         self.repo.create(connection)
     }
@@ -70,6 +74,17 @@ impl ConnectionService {
         self.repo.get(id)
     }
 
+    /// 分组内可远程执行命令的连接；FTP 等非 SSH 协议的连接不参与批量执行。
+    pub fn list_group_connections(&self, group_id: &str) -> Result<Vec<Connection>> {
+        Ok(self
+            .repo
+            .list()?
+            .into_iter()
+            .filter(|conn| conn.group_id.as_deref() == Some(group_id))
+            .filter(|conn| matches!(conn.protocol, Protocol::Ssh | Protocol::Sftp))
+            .collect())
+    }
+
     pub fn update_connection(&self, payload: UpdateConnection) -> Result<Connection> {
         let mut existing = self
             .repo
@@ -83,6 +98,27 @@ impl ConnectionService {
         self.repo.update(existing)
     }
 
+    pub fn update_startup_actions(&self, id: &str, startup: StartupActions) -> Result<Connection> {
+        if let Some(key) = startup.env.keys().find(|key| !is_env_name(key)) {
+            bail!("无效的环境变量名: {key}");
+        }
+        let mut existing = self
+            .repo
+            .get(id)?
+            .ok_or_else(|| anyhow!("Connection not found"))?;
+        existing.startup = startup;
+        self.repo.update(existing)
+    }
+
+    pub fn update_agent_forwarding(&self, id: &str, policy: AgentForwarding) -> Result<Connection> {
+        let mut existing = self
+            .repo
+            .get(id)?
+            .ok_or_else(|| anyhow!("Connection not found"))?;
+        existing.agent_forwarding = policy;
+        self.repo.update(existing)
+    }
+
     pub fn delete_connection(&self, id: &str) -> Result<()> {
         self.repo.delete(id)
     }
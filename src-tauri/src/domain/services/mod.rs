@@ -1,3 +1,5 @@
 pub mod connection_service;
+pub mod script_service;
 pub mod session_service;
 pub mod sync_service;
+pub mod trigger_service;
@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use regex::Regex;
+use uuid::Uuid;
+
+use crate::domain::models::{AutomationScript, ScriptAction, ScriptJump, ScriptStep};
+
+pub trait ScriptRepository: Send + Sync {
+    fn list(&self) -> Result<Vec<AutomationScript>>;
+    fn create(&self, script: AutomationScript) -> Result<AutomationScript>;
+    fn get(&self, id: &str) -> Result<Option<AutomationScript>>;
+    fn update(&self, script: AutomationScript) -> Result<AutomationScript>;
+    fn delete(&self, id: &str) -> Result<()>;
+}
+
+#[derive(Clone)]
+pub struct ScriptService {
+    repo: Arc<dyn ScriptRepository>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveScript {
+    /// 为空时新建脚本。
+    pub id: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    pub steps: Vec<ScriptStep>,
+}
+
+impl ScriptService {
+    pub fn new(repo: Arc<dyn ScriptRepository>) -> Self {
+        Self { repo }
+    }
+
+    pub fn list_scripts(&self) -> Result<Vec<AutomationScript>> {
+        self.repo.list()
+    }
+
+    pub fn get_script(&self, id: &str) -> Result<Option<AutomationScript>> {
+        self.repo.get(id)
+    }
+
+    pub fn save_script(&self, payload: SaveScript) -> Result<AutomationScript> {
+        validate_steps(&payload.steps)?;
+        let now = Utc::now().to_rfc3339();
+        match payload.id {
+            Some(id) => {
+                let mut existing = self
+                    .repo
+                    .get(&id)?
+                    .ok_or_else(|| anyhow!("Script not found"))?;
+                existing.name = payload.name;
+                existing.description = payload.description;
+                existing.steps = payload.steps;
+                existing.updated_at = now;
+                self.repo.update(existing)
+            }
+            None => self.repo.create(AutomationScript {
+                id: generate_id(),
+                name: payload.name,
+                description: payload.description,
+                steps: payload.steps,
+                created_at: now.clone(),
+                updated_at: now,
+            }),
+        }
+    }
+
+    pub fn delete_script(&self, id: &str) -> Result<()> {
+        self.repo.delete(id)
+    }
+}
+
+/// 保存前检查 expect 正则与 goto 标签，避免错误到运行时才暴露。
+fn validate_steps(steps: &[ScriptStep]) -> Result<()> {
+    let mut labels = HashSet::new();
+    for label in steps.iter().filter_map(|step| step.label.as_deref()) {
+        if !labels.insert(label) {
+            bail!("脚本中的标签重复: {label}");
+        }
+    }
+    let check_jump = |jump: &ScriptJump| match jump {
+        ScriptJump::Goto { label } if !labels.contains(label.as_str()) => {
+            Err(anyhow!("脚本引用了不存在的标签: {label}"))
+        }
+        _ => Ok(()),
+    };
+    for step in steps {
+        match &step.action {
+            ScriptAction::Expect {
+                branches,
+                on_timeout,
+                ..
+            } => {
+                for branch in branches {
+                    Regex::new(&branch.pattern)
+                        .map_err(|err| anyhow!("无效的匹配规则 {}: {err}", branch.pattern))?;
+                    check_jump(&branch.then)?;
+                }
+                if let Some(jump) = on_timeout {
+                    check_jump(jump)?;
+                }
+            }
+            ScriptAction::Jump { to } => check_jump(to)?,
+            ScriptAction::Send { .. } | ScriptAction::Sleep { .. } => {}
+        }
+    }
+    Ok(())
+}
+
+fn generate_id() -> String {
+    format!("script-{}", Uuid::new_v4().simple())
+}
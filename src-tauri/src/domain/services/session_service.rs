@@ -2,23 +2,69 @@ use std::sync::Arc;
 
 use anyhow::Result;
 
-use crate::domain::models::SessionSummary;
+use crate::domain::models::{SessionRecord, SessionSummary, ShellCommand};
 
 pub trait SessionRepository: Send + Sync {
     fn list(&self) -> Result<Vec<SessionSummary>>;
 }
 
+/// 已结束会话的持久化记录。
+pub trait SessionHistoryRepository: Send + Sync {
+    fn save(&self, record: &SessionRecord) -> Result<()>;
+    /// 最近结束的会话，新的在前。
+    fn recent(&self, limit: usize) -> Result<Vec<SessionRecord>>;
+    fn recent_for_connection(
+        &self,
+        connection_id: &str,
+        limit: usize,
+    ) -> Result<Vec<SessionRecord>>;
+}
+
+/// shell 集成识别出的命令历史，按连接保存。
+pub trait CommandHistoryRepository: Send + Sync {
+    fn save(&self, command: &ShellCommand) -> Result<()>;
+    /// 该连接最近执行的命令，新的在前。
+    fn recent_for_connection(&self, connection_id: &str, limit: usize)
+        -> Result<Vec<ShellCommand>>;
+}
+
 #[derive(Clone)]
 pub struct SessionService {
     repo: Arc<dyn SessionRepository>,
+    history: Arc<dyn SessionHistoryRepository>,
+    commands: Arc<dyn CommandHistoryRepository>,
 }
 
 impl SessionService {
-    pub fn new(repo: Arc<dyn SessionRepository>) -> Self {
-        Self { repo }
+    pub fn new(
+        repo: Arc<dyn SessionRepository>,
+        history: Arc<dyn SessionHistoryRepository>,
+        commands: Arc<dyn CommandHistoryRepository>,
+    ) -> Self {
+        Self {
+            repo,
+            history,
+            commands,
+        }
     }
 
     pub fn list_sessions(&self) -> Result<Vec<SessionSummary>> {
         self.repo.list()
     }
+
+    /// `connection_id` 为空时返回全部连接的记录。
+    pub fn recent_sessions(
+        &self,
+        connection_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<SessionRecord>> {
+        match connection_id {
+            Some(id) => self.history.recent_for_connection(id, limit),
+            None => self.history.recent(limit),
+        }
+    }
+
+    pub fn recent_commands(&self, connection_id: &str, limit: usize) -> Result<Vec<ShellCommand>> {
+        self.commands.recent_for_connection(connection_id, limit)
+    }
 }
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use regex::Regex;
+use uuid::Uuid;
+
+use crate::domain::models::{TriggerAction, TriggerRule, TriggerScope};
+
+pub trait TriggerRepository: Send + Sync {
+    fn list(&self) -> Result<Vec<TriggerRule>>;
+    fn create(&self, rule: TriggerRule) -> Result<TriggerRule>;
+    fn get(&self, id: &str) -> Result<Option<TriggerRule>>;
+    fn update(&self, rule: TriggerRule) -> Result<TriggerRule>;
+    fn delete(&self, id: &str) -> Result<()>;
+}
+
+#[derive(Clone)]
+pub struct TriggerService {
+    repo: Arc<dyn TriggerRepository>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveTrigger {
+    /// 为空时新建规则。
+    pub id: Option<String>,
+    pub name: String,
+    pub pattern: String,
+    pub scope: TriggerScope,
+    pub action: TriggerAction,
+    pub enabled: bool,
+}
+
+impl TriggerService {
+    pub fn new(repo: Arc<dyn TriggerRepository>) -> Self {
+        Self { repo }
+    }
+
+    pub fn list_rules(&self) -> Result<Vec<TriggerRule>> {
+        self.repo.list()
+    }
+
+    pub fn save_rule(&self, payload: SaveTrigger) -> Result<TriggerRule> {
+        Regex::new(&payload.pattern).map_err(|err| anyhow!("无效的匹配规则: {err}"))?;
+        let now = Utc::now().to_rfc3339();
+        match payload.id {
+            Some(id) => {
+                let mut existing = self
+                    .repo
+                    .get(&id)?
+                    .ok_or_else(|| anyhow!("Trigger not found"))?;
+                existing.name = payload.name;
+                existing.pattern = payload.pattern;
+                existing.scope = payload.scope;
+                existing.action = payload.action;
+                existing.enabled = payload.enabled;
+                existing.updated_at = now;
+                self.repo.update(existing)
+            }
+            None => self.repo.create(TriggerRule {
+                id: generate_id(),
+                name: payload.name,
+                pattern: payload.pattern,
+                scope: payload.scope,
+                action: payload.action,
+                enabled: payload.enabled,
+                created_at: now.clone(),
+                updated_at: now,
+            }),
+        }
+    }
+
+    pub fn delete_rule(&self, id: &str) -> Result<()> {
+        self.repo.delete(id)
+    }
+}
+
+fn generate_id() -> String {
+    format!("trigger-{}", Uuid::new_v4().simple())
+}
@@ -2,9 +2,10 @@ use anyhow::{anyhow, Result};
 use once_cell::sync::Lazy;
 use std::sync::Mutex;
 
-use crate::domain::models::{AuthType, Connection, ConnectionHealth, Protocol, SessionSummary};
+use crate::domain::models::{
+    AgentForwarding, AuthType, Connection, ConnectionHealth, Protocol, StartupActions,
+};
 use crate::domain::services::connection_service::ConnectionRepository;
-use crate::domain::services::session_service::SessionRepository;
 
 static CONNECTIONS: Lazy<Mutex<Vec<Connection>>> = Lazy::new(|| Mutex::new(default_connections()));
 
@@ -68,6 +69,8 @@ fn default_connections() -> Vec<Connection> {
             favorite: true,
             status: ConnectionHealth::Healthy,
             last_connected_at: Some("2025-11-10T08:12:33Z".into()),
+            startup: StartupActions::default(),
+            agent_forwarding: AgentForwarding::default(),
         },
         Connection {
             id: "conn-payments-edge".into(),
@@ -83,6 +86,8 @@ fn default_connections() -> Vec<Connection> {
             favorite: false,
             status: ConnectionHealth::Deploying,
             last_connected_at: None,
+            startup: StartupActions::default(),
+            agent_forwarding: AgentForwarding::default(),
         },
         Connection {
             id: "conn-analytics".into(),
@@ -98,6 +103,8 @@ fn default_connections() -> Vec<Connection> {
             favorite: false,
             status: ConnectionHealth::Idle,
             last_connected_at: Some("2025-11-14T22:31:09Z".into()),
+            startup: StartupActions::default(),
+            agent_forwarding: AgentForwarding::default(),
         },
         Connection {
             id: "conn-qa-gateway".into(),
@@ -113,40 +120,8 @@ fn default_connections() -> Vec<Connection> {
             favorite: false,
             status: ConnectionHealth::Connected,
             last_connected_at: Some("2025-11-15T07:05:44Z".into()),
+            startup: StartupActions::default(),
+            agent_forwarding: AgentForwarding::default(),
         },
     ]
 }
-
-#[derive(Default)]
-pub struct InMemorySessionRepository;
-
-impl SessionRepository for InMemorySessionRepository {
-    fn list(&self) -> Result<Vec<SessionSummary>> {
-        Ok(vec![
-            SessionSummary {
-                id: "session-prod-api".into(),
-                connection_id: "conn-prod-api".into(),
-                title: "prod-api-01".into(),
-                latency_ms: 42,
-                status_label: "Live".into(),
-                updated_at: "2025-11-15T13:35:00Z".into(),
-            },
-            SessionSummary {
-                id: "session-payments-edge".into(),
-                connection_id: "conn-payments-edge".into(),
-                title: "payments-edge".into(),
-                latency_ms: 51,
-                status_label: "Deploying".into(),
-                updated_at: "2025-11-15T13:31:00Z".into(),
-            },
-            SessionSummary {
-                id: "session-analytics".into(),
-                connection_id: "conn-analytics".into(),
-                title: "analytics-pipeline".into(),
-                latency_ms: 68,
-                status_label: "Idle".into(),
-                updated_at: "2025-11-15T13:15:00Z".into(),
-            },
-        ])
-    }
-}
@@ -2,9 +2,19 @@ use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use rusqlite::{params, Connection};
+use tracing::warn;
 
-use crate::domain::models::{AuthType, Connection as DomainConnection, ConnectionHealth, Protocol};
+use crate::domain::models::{
+    AgentForwarding, AuthType, AutomationScript, Connection as DomainConnection, ConnectionHealth,
+    Protocol, SessionRecord, SessionStateReason, ShellCommand, StartupActions, TriggerAction,
+    TriggerRule,
+};
 use crate::domain::services::connection_service::ConnectionRepository;
+use crate::domain::services::script_service::ScriptRepository;
+use crate::domain::services::session_service::{
+    CommandHistoryRepository, SessionHistoryRepository,
+};
+use crate::domain::services::trigger_service::TriggerRepository;
 
 pub struct SqliteConnectionRepository {
     path: PathBuf,
@@ -29,22 +39,44 @@ impl SqliteConnectionRepository {
                 tags TEXT DEFAULT '[]',
                 favorite INTEGER DEFAULT 0,
                 status TEXT DEFAULT 'idle',
-                last_connected_at TEXT
+                last_connected_at TEXT,
+                startup TEXT DEFAULT '{}',
+                agent_forwarding TEXT DEFAULT 'disabled'
             );",
         )?;
+        repo.migrate()?;
         Ok(repo)
     }
 
     fn connection(&self) -> Result<Connection> {
         Ok(Connection::open(&self.path)?)
     }
+
+    /// 为旧版本数据库补齐后续新增的列。
+    fn migrate(&self) -> Result<()> {
+        let conn = self.connection()?;
+        for (column, definition) in [
+            ("startup", "TEXT DEFAULT '{}'"),
+            ("agent_forwarding", "TEXT DEFAULT 'disabled'"),
+        ] {
+            let exists = conn
+                .prepare("SELECT 1 FROM pragma_table_info('connections') WHERE name = ?1")?
+                .exists(params![column])?;
+            if !exists {
+                conn.execute_batch(&format!(
+                    "ALTER TABLE connections ADD COLUMN {column} {definition};"
+                ))?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl ConnectionRepository for SqliteConnectionRepository {
     fn list(&self) -> Result<Vec<DomainConnection>> {
         let conn = self.connection()?;
         let mut stmt = conn.prepare(
-            "SELECT id, name, protocol, host, port, username, auth_type, group_id, group_name, tags, favorite, status, last_connected_at FROM connections ORDER BY rowid DESC",
+            "SELECT id, name, protocol, host, port, username, auth_type, group_id, group_name, tags, favorite, status, last_connected_at, startup, agent_forwarding FROM connections ORDER BY rowid DESC",
         )?;
         let rows = stmt.query_map([], |row| {
             let tags: String = row.get(9)?;
@@ -62,6 +94,8 @@ impl ConnectionRepository for SqliteConnectionRepository {
                 favorite: row.get::<_, i64>(10)? == 1,
                 status: parse_status(row.get::<_, String>(11)?.as_str()),
                 last_connected_at: row.get(12)?,
+                startup: parse_startup(row.get(13)?),
+                agent_forwarding: parse_agent_forwarding(row.get(14)?),
             })
         })?;
         Ok(rows.filter_map(Result::ok).collect())
@@ -70,8 +104,8 @@ impl ConnectionRepository for SqliteConnectionRepository {
     fn create(&self, connection: DomainConnection) -> Result<DomainConnection> {
         let conn = self.connection()?;
         conn.execute(
-            "INSERT INTO connections (id, name, protocol, host, port, username, auth_type, group_id, group_name, tags, favorite, status, last_connected_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            "INSERT INTO connections (id, name, protocol, host, port, username, auth_type, group_id, group_name, tags, favorite, status, last_connected_at, startup, agent_forwarding)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
             params![
                 connection.id,
                 connection.name,
@@ -86,6 +120,8 @@ impl ConnectionRepository for SqliteConnectionRepository {
                 if connection.favorite { 1 } else { 0 },
                 format_status(&connection.status),
                 connection.last_connected_at,
+                serde_json::to_string(&connection.startup)?,
+                format_agent_forwarding(connection.agent_forwarding),
             ],
         )?;
         Ok(connection)
@@ -94,7 +130,7 @@ impl ConnectionRepository for SqliteConnectionRepository {
     fn get(&self, id: &str) -> Result<Option<DomainConnection>> {
         let conn = self.connection()?;
         let mut stmt = conn.prepare(
-            "SELECT id, name, protocol, host, port, username, auth_type, group_id, group_name, tags, favorite, status, last_connected_at FROM connections WHERE id = ?1",
+            "SELECT id, name, protocol, host, port, username, auth_type, group_id, group_name, tags, favorite, status, last_connected_at, startup, agent_forwarding FROM connections WHERE id = ?1",
         )?;
         let mut rows = stmt.query(params![id])?;
         if let Some(row) = rows.next()? {
@@ -113,6 +149,8 @@ impl ConnectionRepository for SqliteConnectionRepository {
                 favorite: row.get::<_, i64>(10)? == 1,
                 status: parse_status(row.get::<_, String>(11)?.as_str()),
                 last_connected_at: row.get(12)?,
+                startup: parse_startup(row.get(13)?),
+                agent_forwarding: parse_agent_forwarding(row.get(14)?),
             }));
         }
         Ok(None)
@@ -121,13 +159,15 @@ impl ConnectionRepository for SqliteConnectionRepository {
     fn update(&self, connection: DomainConnection) -> Result<DomainConnection> {
         let conn = self.connection()?;
         conn.execute(
-            "UPDATE connections SET name = ?1, protocol = ?2, host = ?3, port = ?4, username = ?5 WHERE id = ?6",
+            "UPDATE connections SET name = ?1, protocol = ?2, host = ?3, port = ?4, username = ?5, startup = ?6, agent_forwarding = ?7 WHERE id = ?8",
             params![
                 connection.name,
                 format_protocol(&connection.protocol),
                 connection.host,
                 connection.port,
                 connection.username,
+                serde_json::to_string(&connection.startup)?,
+                format_agent_forwarding(connection.agent_forwarding),
                 connection.id,
             ],
         )?;
@@ -141,6 +181,466 @@ impl ConnectionRepository for SqliteConnectionRepository {
     }
 }
 
+/// 自动化脚本与连接共用同一个数据库文件，步骤以 JSON 保存。
+pub struct SqliteScriptRepository {
+    path: PathBuf,
+}
+
+impl SqliteScriptRepository {
+    pub fn new(path: &Path) -> Result<Self> {
+        let repo = Self {
+            path: path.to_path_buf(),
+        };
+        repo.connection()?.execute_batch(
+            "CREATE TABLE IF NOT EXISTS automation_scripts (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                description TEXT,
+                steps TEXT DEFAULT '[]',
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+        )?;
+        Ok(repo)
+    }
+
+    fn connection(&self) -> Result<Connection> {
+        Ok(Connection::open(&self.path)?)
+    }
+}
+
+impl ScriptRepository for SqliteScriptRepository {
+    fn list(&self) -> Result<Vec<AutomationScript>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description, steps, created_at, updated_at FROM automation_scripts ORDER BY updated_at DESC",
+        )?;
+        let rows = stmt.query_map([], read_script)?;
+        // 步骤无法解析的脚本不加载，避免以空步骤“成功”运行
+        Ok(rows
+            .filter_map(|row| {
+                row.map_err(|err| warn!(target: "db", %err, "跳过无法解析的自动化脚本"))
+                    .ok()
+            })
+            .collect())
+    }
+
+    fn create(&self, script: AutomationScript) -> Result<AutomationScript> {
+        let conn = self.connection()?;
+        conn.execute(
+            "INSERT INTO automation_scripts (id, name, description, steps, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                script.id,
+                script.name,
+                script.description,
+                serde_json::to_string(&script.steps)?,
+                script.created_at,
+                script.updated_at,
+            ],
+        )?;
+        Ok(script)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<AutomationScript>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description, steps, created_at, updated_at FROM automation_scripts WHERE id = ?1",
+        )?;
+        let mut rows = stmt.query(params![id])?;
+        if let Some(row) = rows.next()? {
+            return Ok(Some(read_script(row)?));
+        }
+        Ok(None)
+    }
+
+    fn update(&self, script: AutomationScript) -> Result<AutomationScript> {
+        let conn = self.connection()?;
+        conn.execute(
+            "UPDATE automation_scripts SET name = ?1, description = ?2, steps = ?3, updated_at = ?4 WHERE id = ?5",
+            params![
+                script.name,
+                script.description,
+                serde_json::to_string(&script.steps)?,
+                script.updated_at,
+                script.id,
+            ],
+        )?;
+        Ok(script)
+    }
+
+    fn delete(&self, id: &str) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute("DELETE FROM automation_scripts WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+}
+
+fn read_script(row: &rusqlite::Row<'_>) -> rusqlite::Result<AutomationScript> {
+    let steps: Option<String> = row.get(3)?;
+    Ok(AutomationScript {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        steps: match steps {
+            Some(_) => json_column(row, 3)?,
+            None => Vec::new(),
+        },
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+pub struct SqliteTriggerRepository {
+    path: PathBuf,
+}
+
+impl SqliteTriggerRepository {
+    pub fn new(path: &Path) -> Result<Self> {
+        let repo = Self {
+            path: path.to_path_buf(),
+        };
+        repo.connection()?.execute_batch(
+            "CREATE TABLE IF NOT EXISTS trigger_rules (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                pattern TEXT NOT NULL,
+                scope TEXT NOT NULL,
+                action TEXT NOT NULL,
+                enabled INTEGER DEFAULT 1,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+        )?;
+        Ok(repo)
+    }
+
+    fn connection(&self) -> Result<Connection> {
+        Ok(Connection::open(&self.path)?)
+    }
+}
+
+impl TriggerRepository for SqliteTriggerRepository {
+    fn list(&self) -> Result<Vec<TriggerRule>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, pattern, scope, action, enabled, created_at, updated_at FROM trigger_rules ORDER BY created_at",
+        )?;
+        let rows = stmt.query_map([], read_trigger)?;
+        // 无法解析的规则不加载，避免范围丢失后退化为对所有会话生效
+        Ok(rows
+            .filter_map(|row| {
+                row.map_err(|err| warn!(target: "db", %err, "跳过无法解析的触发规则"))
+                    .ok()
+            })
+            .collect())
+    }
+
+    fn create(&self, rule: TriggerRule) -> Result<TriggerRule> {
+        let conn = self.connection()?;
+        conn.execute(
+            "INSERT INTO trigger_rules (id, name, pattern, scope, action, enabled, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                rule.id,
+                rule.name,
+                rule.pattern,
+                serde_json::to_string(&rule.scope)?,
+                format_trigger_action(rule.action),
+                if rule.enabled { 1 } else { 0 },
+                rule.created_at,
+                rule.updated_at,
+            ],
+        )?;
+        Ok(rule)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<TriggerRule>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, pattern, scope, action, enabled, created_at, updated_at FROM trigger_rules WHERE id = ?1",
+        )?;
+        let mut rows = stmt.query(params![id])?;
+        if let Some(row) = rows.next()? {
+            return Ok(Some(read_trigger(row)?));
+        }
+        Ok(None)
+    }
+
+    fn update(&self, rule: TriggerRule) -> Result<TriggerRule> {
+        let conn = self.connection()?;
+        conn.execute(
+            "UPDATE trigger_rules SET name = ?1, pattern = ?2, scope = ?3, action = ?4, enabled = ?5, updated_at = ?6 WHERE id = ?7",
+            params![
+                rule.name,
+                rule.pattern,
+                serde_json::to_string(&rule.scope)?,
+                format_trigger_action(rule.action),
+                if rule.enabled { 1 } else { 0 },
+                rule.updated_at,
+                rule.id,
+            ],
+        )?;
+        Ok(rule)
+    }
+
+    fn delete(&self, id: &str) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute("DELETE FROM trigger_rules WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+}
+
+fn read_trigger(row: &rusqlite::Row<'_>) -> rusqlite::Result<TriggerRule> {
+    Ok(TriggerRule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        pattern: row.get(2)?,
+        scope: json_column(row, 3)?,
+        action: parse_trigger_action(row.get::<_, String>(4)?.as_str()),
+        enabled: row.get::<_, i64>(5)? == 1,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+/// 读取 JSON 文本列；格式错误作为该行的转换错误返回，而不是退回默认值。
+fn json_column<T: serde::de::DeserializeOwned>(
+    row: &rusqlite::Row<'_>,
+    index: usize,
+) -> rusqlite::Result<T> {
+    let raw: String = row.get(index)?;
+    serde_json::from_str(&raw).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(err))
+    })
+}
+
+fn format_trigger_action(action: TriggerAction) -> &'static str {
+    match action {
+        TriggerAction::Highlight => "highlight",
+        TriggerAction::Notify => "notify",
+        TriggerAction::Autofill => "autofill",
+    }
+}
+
+fn parse_trigger_action(value: &str) -> TriggerAction {
+    match value {
+        "notify" => TriggerAction::Notify,
+        "autofill" => TriggerAction::Autofill,
+        _ => TriggerAction::Highlight,
+    }
+}
+
+pub struct SqliteSessionHistoryRepository {
+    path: PathBuf,
+}
+
+impl SqliteSessionHistoryRepository {
+    pub fn new(path: &Path) -> Result<Self> {
+        let repo = Self {
+            path: path.to_path_buf(),
+        };
+        repo.connection()?.execute_batch(
+            "CREATE TABLE IF NOT EXISTS session_history (
+                session_id TEXT PRIMARY KEY,
+                connection_id TEXT,
+                title TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                ended_at TEXT,
+                duration_ms INTEGER,
+                exit_code INTEGER,
+                exit_signal TEXT,
+                close_reason TEXT,
+                message TEXT,
+                bytes_in INTEGER DEFAULT 0,
+                bytes_out INTEGER DEFAULT 0
+            );
+            CREATE INDEX IF NOT EXISTS idx_session_history_connection
+                ON session_history (connection_id, ended_at);",
+        )?;
+        Ok(repo)
+    }
+
+    fn connection(&self) -> Result<Connection> {
+        Ok(Connection::open(&self.path)?)
+    }
+}
+
+const SESSION_HISTORY_COLUMNS: &str = "session_id, connection_id, title, started_at, ended_at, duration_ms, exit_code, exit_signal, close_reason, message, bytes_in, bytes_out";
+
+impl SessionHistoryRepository for SqliteSessionHistoryRepository {
+    fn save(&self, record: &SessionRecord) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO session_history ({SESSION_HISTORY_COLUMNS})
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"
+            ),
+            params![
+                record.session_id,
+                record.connection_id,
+                record.title,
+                record.started_at,
+                record.ended_at,
+                record.duration_ms.map(|ms| ms as i64),
+                record.exit_code,
+                record.exit_signal,
+                record.close_reason.map(format_close_reason),
+                record.message,
+                record.bytes_in as i64,
+                record.bytes_out as i64,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn recent(&self, limit: usize) -> Result<Vec<SessionRecord>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_HISTORY_COLUMNS} FROM session_history ORDER BY ended_at DESC LIMIT ?1"
+        ))?;
+        let rows = stmt.query_map(params![limit as i64], read_session_record)?;
+        Ok(rows.filter_map(Result::ok).collect())
+    }
+
+    fn recent_for_connection(
+        &self,
+        connection_id: &str,
+        limit: usize,
+    ) -> Result<Vec<SessionRecord>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_HISTORY_COLUMNS} FROM session_history WHERE connection_id = ?1 ORDER BY ended_at DESC LIMIT ?2"
+        ))?;
+        let rows = stmt.query_map(params![connection_id, limit as i64], read_session_record)?;
+        Ok(rows.filter_map(Result::ok).collect())
+    }
+}
+
+fn read_session_record(row: &rusqlite::Row<'_>) -> rusqlite::Result<SessionRecord> {
+    Ok(SessionRecord {
+        session_id: row.get(0)?,
+        connection_id: row.get(1)?,
+        title: row.get(2)?,
+        started_at: row.get(3)?,
+        ended_at: row.get(4)?,
+        duration_ms: row.get::<_, Option<i64>>(5)?.map(|ms| ms as u64),
+        exit_code: row.get(6)?,
+        exit_signal: row.get(7)?,
+        close_reason: row
+            .get::<_, Option<String>>(8)?
+            .as_deref()
+            .and_then(parse_close_reason),
+        message: row.get(9)?,
+        bytes_in: row.get::<_, i64>(10)? as u64,
+        bytes_out: row.get::<_, i64>(11)? as u64,
+    })
+}
+
+pub struct SqliteCommandHistoryRepository {
+    path: PathBuf,
+}
+
+impl SqliteCommandHistoryRepository {
+    pub fn new(path: &Path) -> Result<Self> {
+        let repo = Self {
+            path: path.to_path_buf(),
+        };
+        repo.connection()?.execute_batch(
+            "CREATE TABLE IF NOT EXISTS command_history (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                connection_id TEXT NOT NULL,
+                command TEXT NOT NULL,
+                cwd TEXT,
+                started_at TEXT NOT NULL,
+                finished_at TEXT,
+                exit_code INTEGER,
+                duration_ms INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS idx_command_history_connection
+                ON command_history (connection_id, started_at);",
+        )?;
+        Ok(repo)
+    }
+
+    fn connection(&self) -> Result<Connection> {
+        Ok(Connection::open(&self.path)?)
+    }
+}
+
+impl CommandHistoryRepository for SqliteCommandHistoryRepository {
+    fn save(&self, command: &ShellCommand) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO command_history (id, session_id, connection_id, command, cwd, started_at, finished_at, exit_code, duration_ms)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                command.id,
+                command.session_id,
+                command.connection_id,
+                command.command,
+                command.cwd,
+                command.started_at,
+                command.finished_at,
+                command.exit_code,
+                command.duration_ms.map(|ms| ms as i64),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn recent_for_connection(
+        &self,
+        connection_id: &str,
+        limit: usize,
+    ) -> Result<Vec<ShellCommand>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, session_id, connection_id, command, cwd, started_at, finished_at, exit_code, duration_ms FROM command_history WHERE connection_id = ?1 ORDER BY started_at DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![connection_id, limit as i64], |row| {
+            Ok(ShellCommand {
+                id: row.get(0)?,
+                session_id: row.get(1)?,
+                connection_id: row.get(2)?,
+                command: row.get(3)?,
+                cwd: row.get(4)?,
+                started_at: row.get(5)?,
+                finished_at: row.get(6)?,
+                exit_code: row.get(7)?,
+                duration_ms: row.get::<_, Option<i64>>(8)?.map(|ms| ms as u64),
+            })
+        })?;
+        Ok(rows.filter_map(Result::ok).collect())
+    }
+}
+
+fn format_close_reason(reason: SessionStateReason) -> &'static str {
+    match reason {
+        SessionStateReason::UserClosed => "user_closed",
+        SessionStateReason::RemoteClosed => "remote_closed",
+        SessionStateReason::ChannelDisconnected => "channel_disconnected",
+        SessionStateReason::ProcessExited => "process_exited",
+        SessionStateReason::Error => "error",
+        SessionStateReason::IdleTimeout => "idle_timeout",
+        SessionStateReason::MaxDuration => "max_duration",
+    }
+}
+
+fn parse_close_reason(value: &str) -> Option<SessionStateReason> {
+    match value {
+        "user_closed" => Some(SessionStateReason::UserClosed),
+        "remote_closed" => Some(SessionStateReason::RemoteClosed),
+        "channel_disconnected" => Some(SessionStateReason::ChannelDisconnected),
+        "process_exited" => Some(SessionStateReason::ProcessExited),
+        "error" => Some(SessionStateReason::Error),
+        "idle_timeout" => Some(SessionStateReason::IdleTimeout),
+        "max_duration" => Some(SessionStateReason::MaxDuration),
+        _ => None,
+    }
+}
+
 fn format_protocol(protocol: &Protocol) -> &'static str {
     match protocol {
         Protocol::Ssh => "ssh",
@@ -182,6 +682,28 @@ fn format_status(status: &ConnectionHealth) -> &'static str {
     }
 }
 
+fn parse_startup(value: Option<String>) -> StartupActions {
+    value
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn format_agent_forwarding(policy: AgentForwarding) -> &'static str {
+    match policy {
+        AgentForwarding::Disabled => "disabled",
+        AgentForwarding::Allow => "allow",
+        AgentForwarding::Confirm => "confirm",
+    }
+}
+
+fn parse_agent_forwarding(value: Option<String>) -> AgentForwarding {
+    match value.as_deref() {
+        Some("allow") => AgentForwarding::Allow,
+        Some("confirm") => AgentForwarding::Confirm,
+        _ => AgentForwarding::Disabled,
+    }
+}
+
 fn parse_status(value: &str) -> ConnectionHealth {
     match value {
         "healthy" => ConnectionHealth::Healthy,
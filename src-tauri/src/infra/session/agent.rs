@@ -0,0 +1,593 @@
+//! SSH agent 转发（`ssh -A`）：在 shell 通道上请求 `auth-agent-req@openssh.com`，
+//! 远端打开的 `auth-agent@openssh.com` 通道逐条转发给本地 ssh-agent。
+//!
+//! ssh2 没有暴露接收这类通道的接口，这里直接注册 libssh2 的 `LIBSSH2_CALLBACK_AUTHAGENT` 回调。
+//! 回调只登记通道指针；每个池化连接只有一个中继，随连接一起释放，由请求了转发的 shell 线程在主循环里驱动，
+//! 读写时持有 ssh2 的会话锁，与 shell 通道互不干扰。
+//!
+//! 远端打开 agent 通道时不指明来自哪个会话，签名确认与提示发给最近请求转发、仍在运行的标签；
+//! 该标签关闭后，它名下的通道转交给下一个标签，等待中的签名请求按拒绝处理。
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use libssh2_sys as raw;
+use ssh2::Session as SshSession;
+use uuid::Uuid;
+
+use crate::domain::models::AgentForwarding;
+
+use super::events::Events;
+use super::output::OutputBus;
+use super::{SessionInput, SessionKind, SessionManager};
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+/// 单条 agent 消息的长度上限，与 OpenSSH 一致。
+const MAX_MESSAGE: usize = 256 * 1024;
+/// 等待用户确认签名的最长时间，超时按拒绝处理。
+const DECISION_TIMEOUT: Duration = Duration::from_secs(60);
+const LIBSSH2_CALLBACK_AUTHAGENT: c_int = 7;
+
+extern "C" {
+    // libssh2-sys 没有导出该函数；`_set2` 只在 1.11.1 之后提供，链接系统库时可能缺失
+    fn libssh2_session_callback_set(
+        session: *mut raw::LIBSSH2_SESSION,
+        cbtype: c_int,
+        callback: *mut c_void,
+    ) -> *mut c_void;
+}
+
+/// 回调登记、尚未被中继接管的通道：(会话指针, 通道指针)。
+static INCOMING: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+extern "C" fn on_agent_channel(
+    session: *mut raw::LIBSSH2_SESSION,
+    channel: *mut raw::LIBSSH2_CHANNEL,
+    _abstract: *mut *mut c_void,
+) {
+    // 不能在 FFI 边界上 panic，锁中毒时只能放弃该通道，由 libssh2 在会话结束时释放
+    if let Ok(mut incoming) = INCOMING.lock() {
+        incoming.push((session as usize, channel as usize));
+    }
+}
+
+/// 推送给前端的签名确认请求（事件 `session-agent-request`），由 `respond_agent_request` 答复。
+#[derive(serde::Serialize, Clone)]
+pub struct SessionAgentRequestPayload {
+    pub session_id: String,
+    pub request_id: String,
+    pub key_type: String,
+    /// 本地 agent 登记的密钥注释，远端未先列出密钥时为空。
+    pub comment: Option<String>,
+}
+
+pub(super) struct AgentDecision {
+    request_id: String,
+    allow: bool,
+}
+
+trait AgentStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> AgentStream for T {}
+
+struct PendingSign {
+    request_id: String,
+    message: Vec<u8>,
+    since: Instant,
+}
+
+struct ForwardedChannel {
+    raw: *mut raw::LIBSSH2_CHANNEL,
+    /// 接收签名确认与提示的标签。
+    owner: String,
+    inbound: Vec<u8>,
+    agent: Option<Box<dyn AgentStream>>,
+    pending: Option<PendingSign>,
+}
+
+/// 请求了 agent 转发的标签。
+struct Requester {
+    session_id: String,
+    events: Events,
+    output: OutputBus,
+    policy: AgentForwarding,
+}
+
+/// 一个池化连接上所有被转发的 agent 通道，由连接池持有，随连接释放一并关闭。
+pub(super) struct AgentRelay {
+    session: SshSession,
+    session_ptr: usize,
+    /// 按请求转发的先后排列，最后一个接收新通道。
+    requesters: Vec<Requester>,
+    channels: Vec<ForwardedChannel>,
+    /// 密钥 blob 到注释的映射，取自本地 agent 的密钥列表应答。
+    comments: HashMap<Vec<u8>, String>,
+    warned: bool,
+}
+
+// 通道指针只在持有 ssh2 会话锁（`session.raw()`）时使用，可以随中继在线程间移动
+unsafe impl Send for AgentRelay {}
+
+/// 标签对中继的登记：shell 线程经它驱动中继、应用确认答复，释放时注销该标签。
+pub(super) struct AgentForward {
+    relay: Arc<Mutex<AgentRelay>>,
+    session_id: String,
+}
+
+impl AgentForward {
+    /// 接管新通道并处理已到达的请求，返回本轮是否有数据往来。
+    pub(super) fn pump(&self) -> bool {
+        self.relay.lock().unwrap().pump()
+    }
+
+    /// 应用用户对签名请求的答复；只接受发给本标签的请求，已超时或通道已关闭时忽略。
+    pub(super) fn decide(&self, decision: AgentDecision) {
+        self.relay
+            .lock()
+            .unwrap()
+            .decide(&self.session_id, decision);
+    }
+}
+
+impl Drop for AgentForward {
+    fn drop(&mut self) {
+        if let Ok(mut relay) = self.relay.lock() {
+            relay.unregister(&self.session_id);
+        }
+    }
+}
+
+impl AgentRelay {
+    /// 注册通道回调；须在请求 agent 转发之前调用。
+    pub(super) fn install(session: &SshSession) -> Self {
+        let mut guard = session.raw();
+        let session_ptr = &mut *guard as *mut raw::LIBSSH2_SESSION;
+        unsafe {
+            libssh2_session_callback_set(
+                session_ptr,
+                LIBSSH2_CALLBACK_AUTHAGENT,
+                on_agent_channel as *mut c_void,
+            );
+        }
+        drop(guard);
+        Self {
+            session: session.clone(),
+            session_ptr: session_ptr as usize,
+            requesters: Vec::new(),
+            channels: Vec::new(),
+            comments: HashMap::new(),
+            warned: false,
+        }
+    }
+
+    /// 登记请求转发的标签，之后远端打开的 agent 通道由该标签确认。
+    pub(super) fn register(
+        relay: &Arc<Mutex<Self>>,
+        session_id: &str,
+        events: &Events,
+        output: &OutputBus,
+        policy: AgentForwarding,
+    ) -> AgentForward {
+        relay.lock().unwrap().requesters.push(Requester {
+            session_id: session_id.to_string(),
+            events: events.clone(),
+            output: output.clone(),
+            policy,
+        });
+        AgentForward {
+            relay: relay.clone(),
+            session_id: session_id.to_string(),
+        }
+    }
+
+    /// 注销标签：它名下的通道转交给最近登记的标签，等待中的签名请求按拒绝处理；
+    /// 没有标签时关闭所有通道。
+    fn unregister(&mut self, session_id: &str) {
+        self.requesters
+            .retain(|requester| requester.session_id != session_id);
+        let Some(heir) = self
+            .requesters
+            .last()
+            .map(|requester| requester.session_id.clone())
+        else {
+            for channel in std::mem::take(&mut self.channels) {
+                self.free(channel.raw);
+            }
+            return;
+        };
+        let mut index = 0;
+        while index < self.channels.len() {
+            if self.channels[index].owner != session_id {
+                index += 1;
+                continue;
+            }
+            self.channels[index].owner = heir.clone();
+            if self.channels[index].pending.take().is_some()
+                && self.reply(index, &[SSH_AGENT_FAILURE]).is_err()
+            {
+                let channel = self.channels.remove(index);
+                self.free(channel.raw);
+                continue;
+            }
+            index += 1;
+        }
+    }
+
+    fn requester(&self, session_id: &str) -> Option<&Requester> {
+        self.requesters
+            .iter()
+            .find(|requester| requester.session_id == session_id)
+    }
+
+    fn pump(&mut self) -> bool {
+        let adopted: Vec<usize> = match INCOMING.lock() {
+            Ok(mut incoming) => {
+                let mut adopted = Vec::new();
+                incoming.retain(|&(session, channel)| {
+                    if session != self.session_ptr {
+                        return true;
+                    }
+                    adopted.push(channel);
+                    false
+                });
+                adopted
+            }
+            Err(_) => Vec::new(),
+        };
+        for channel in adopted {
+            let raw = channel as *mut raw::LIBSSH2_CHANNEL;
+            match self.requesters.last() {
+                Some(owner) => self.channels.push(ForwardedChannel {
+                    raw,
+                    owner: owner.session_id.clone(),
+                    inbound: Vec::new(),
+                    agent: None,
+                    pending: None,
+                }),
+                None => self.free(raw),
+            }
+        }
+
+        let mut active = false;
+        let mut index = 0;
+        while index < self.channels.len() {
+            match self.service(index) {
+                Ok(worked) => {
+                    active |= worked;
+                    index += 1;
+                }
+                Err(_) => {
+                    let channel = self.channels.remove(index);
+                    self.free(channel.raw);
+                }
+            }
+        }
+        active
+    }
+    /// 处理单个通道；返回错误表示通道已结束，应当关闭。
+    fn service(&mut self, index: usize) -> Result<bool> {
+        if let Some(pending) = &self.channels[index].pending {
+            if pending.since.elapsed() < DECISION_TIMEOUT {
+                return Ok(false);
+            }
+            self.channels[index].pending = None;
+            self.notice(index, "agent 签名请求等待确认超时，已拒绝\r\n");
+            self.reply(index, &[SSH_AGENT_FAILURE])?;
+        }
+
+        let raw = self.channels[index].raw;
+        let mut active = false;
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = {
+                let _lock = self.session.raw();
+                unsafe {
+                    raw::libssh2_channel_read_ex(
+                        raw,
+                        0,
+                        buffer.as_mut_ptr() as *mut c_char,
+                        buffer.len() as _,
+                    )
+                }
+            };
+            match read {
+                size if size > 0 => {
+                    active = true;
+                    self.channels[index]
+                        .inbound
+                        .extend_from_slice(&buffer[..size as usize]);
+                }
+                0 => break,
+                code if code as c_int == raw::LIBSSH2_ERROR_EAGAIN => break,
+                code => bail!("读取 agent 通道失败: {code}"),
+            }
+        }
+
+        while self.channels[index].pending.is_none() {
+            let Some(message) = take_message(&mut self.channels[index].inbound)? else {
+                break;
+            };
+            active = true;
+            self.handle(index, message)?;
+        }
+
+        let eof = {
+            let _lock = self.session.raw();
+            unsafe { raw::libssh2_channel_eof(raw) }
+        };
+        if eof == 1 && self.channels[index].pending.is_none() {
+            bail!("远端已关闭 agent 通道");
+        }
+        Ok(active)
+    }
+
+    fn handle(&mut self, index: usize, message: Vec<u8>) -> Result<()> {
+        let Some(owner) = self.requester(&self.channels[index].owner) else {
+            return self.reply(index, &[SSH_AGENT_FAILURE]);
+        };
+        match message.first().copied() {
+            Some(SSH_AGENTC_REQUEST_IDENTITIES) => self.forward(index, &message),
+            Some(SSH_AGENTC_SIGN_REQUEST) if owner.policy == AgentForwarding::Confirm => {
+                let blob = read_string(&message, &mut 1).unwrap_or_default().to_vec();
+                let key_type = read_string(&blob, &mut 0)
+                    .map(|value| String::from_utf8_lossy(value).into_owned())
+                    .unwrap_or_default();
+                let request_id = format!("agent-{}", Uuid::new_v4().simple());
+                let payload = SessionAgentRequestPayload {
+                    session_id: owner.session_id.clone(),
+                    request_id: request_id.clone(),
+                    key_type,
+                    comment: self.comments.get(&blob).cloned(),
+                };
+                let _ = owner.events.emit("session-agent-request", payload);
+                self.channels[index].pending = Some(PendingSign {
+                    request_id,
+                    message,
+                    since: Instant::now(),
+                });
+                Ok(())
+            }
+            Some(SSH_AGENTC_SIGN_REQUEST) => self.forward(index, &message),
+            // 只放行列出密钥与签名，添加、删除、锁定等操作一律拒绝
+            _ => self.reply(index, &[SSH_AGENT_FAILURE]),
+        }
+    }
+
+    fn decide(&mut self, session_id: &str, decision: AgentDecision) {
+        let Some(index) = self.channels.iter().position(|channel| {
+            channel.owner == session_id
+                && channel
+                    .pending
+                    .as_ref()
+                    .is_some_and(|pending| pending.request_id == decision.request_id)
+        }) else {
+            return;
+        };
+        let Some(pending) = self.channels[index].pending.take() else {
+            return;
+        };
+        let result = if decision.allow {
+            self.forward(index, &pending.message)
+        } else {
+            self.reply(index, &[SSH_AGENT_FAILURE])
+        };
+        if result.is_err() {
+            let channel = self.channels.remove(index);
+            self.free(channel.raw);
+        }
+    }
+
+    /// 把请求交给本地 agent 并回写应答；本地 agent 不可用时回复失败，不中断远端程序。
+    fn forward(&mut self, index: usize, message: &[u8]) -> Result<()> {
+        match self.ask_agent(index, message) {
+            Ok(response) => {
+                if response.first() == Some(&SSH_AGENT_IDENTITIES_ANSWER) {
+                    self.remember_comments(&response);
+                }
+                self.reply(index, &response)
+            }
+            Err(err) => {
+                self.channels[index].agent = None;
+                if !self.warned {
+                    self.warned = true;
+                    self.notice(index, &format!("本地 ssh-agent 不可用: {err}\r\n"));
+                }
+                self.reply(index, &[SSH_AGENT_FAILURE])
+            }
+        }
+    }
+
+    fn ask_agent(&mut self, index: usize, message: &[u8]) -> Result<Vec<u8>> {
+        let channel = &mut self.channels[index];
+        if channel.agent.is_none() {
+            channel.agent = Some(connect_agent()?);
+        }
+        let agent = channel.agent.as_mut().expect("agent was just connected");
+        agent.write_all(&frame(message))?;
+        agent.flush()?;
+        let mut header = [0u8; 4];
+        agent
+            .read_exact(&mut header)
+            .context("读取 agent 应答失败")?;
+        let length = u32::from_be_bytes(header) as usize;
+        if length == 0 || length > MAX_MESSAGE {
+            bail!("agent 应答长度异常: {length}");
+        }
+        let mut response = vec![0u8; length];
+        agent
+            .read_exact(&mut response)
+            .context("读取 agent 应答失败")?;
+        Ok(response)
+    }
+
+    fn remember_comments(&mut self, response: &[u8]) {
+        let mut pos = 1;
+        let Some(count) = read_u32(response, &mut pos) else {
+            return;
+        };
+        for _ in 0..count {
+            let (Some(blob), Some(comment)) = (
+                read_string(response, &mut pos),
+                read_string(response, &mut pos),
+            ) else {
+                return;
+            };
+            self.comments
+                .insert(blob.to_vec(), String::from_utf8_lossy(comment).into_owned());
+        }
+    }
+
+    fn reply(&mut self, index: usize, message: &[u8]) -> Result<()> {
+        let raw = self.channels[index].raw;
+        let data = frame(message);
+        let mut remaining = data.as_slice();
+        while !remaining.is_empty() {
+            let written = {
+                let _lock = self.session.raw();
+                unsafe {
+                    raw::libssh2_channel_write_ex(
+                        raw,
+                        0,
+                        remaining.as_ptr() as *const c_char,
+                        remaining.len() as _,
+                    )
+                }
+            };
+            match written {
+                size if size > 0 => remaining = &remaining[size as usize..],
+                code if code as c_int == raw::LIBSSH2_ERROR_EAGAIN || code == 0 => {
+                    thread::sleep(Duration::from_millis(12));
+                }
+                code => bail!("写入 agent 通道失败: {code}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// 在通道所属标签的终端里提示。
+    fn notice(&self, index: usize, message: &str) {
+        let owner = &self.channels[index].owner;
+        if let Some(requester) = self.requester(owner) {
+            requester.output.publish(owner, "stderr", message);
+        }
+    }
+
+    /// 关闭并释放通道；非阻塞模式下多次重试，仍未完成时留给会话释放时回收。
+    fn free(&self, channel: *mut raw::LIBSSH2_CHANNEL) {
+        for _ in 0..50 {
+            let rc = {
+                let _lock = self.session.raw();
+                unsafe { raw::libssh2_channel_free(channel) }
+            };
+            if rc != raw::LIBSSH2_ERROR_EAGAIN {
+                return;
+            }
+            thread::sleep(Duration::from_millis(12));
+        }
+    }
+}
+
+impl Drop for AgentRelay {
+    fn drop(&mut self) {
+        let session_ptr = self.session_ptr;
+        if let Ok(mut incoming) = INCOMING.lock() {
+            incoming.retain(|&(session, _)| session != session_ptr);
+        }
+        for channel in std::mem::take(&mut self.channels) {
+            self.free(channel.raw);
+        }
+    }
+}
+
+impl SessionManager {
+    /// 答复 `session-agent-request`：`allow` 为 false 时远端收到签名失败。
+    pub async fn respond_agent_request(
+        &self,
+        session_id: &str,
+        request_id: &str,
+        allow: bool,
+    ) -> Result<()> {
+        let sessions = self.sessions.lock().await;
+        let handle = sessions
+            .get(session_id)
+            .ok_or_else(|| anyhow!("session not found"))?;
+        match &handle.kind {
+            SessionKind::Ssh2 { tx } => tx
+                .send(SessionInput::Agent(AgentDecision {
+                    request_id: request_id.to_string(),
+                    allow,
+                }))
+                .map_err(|err| anyhow!("发送 agent 确认失败: {err}")),
+            SessionKind::Local { .. } => bail!("本地 shell 没有 agent 转发"),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn connect_agent() -> Result<Box<dyn AgentStream>> {
+    /// 本地 agent 的应答超时。
+    const AGENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    let path = std::env::var_os("SSH_AUTH_SOCK").ok_or_else(|| anyhow!("未设置 SSH_AUTH_SOCK"))?;
+    let stream = std::os::unix::net::UnixStream::connect(&path)
+        .with_context(|| format!("无法连接 {}", path.to_string_lossy()))?;
+    stream.set_read_timeout(Some(AGENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(AGENT_TIMEOUT))?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(windows)]
+fn connect_agent() -> Result<Box<dyn AgentStream>> {
+    const PIPE: &str = r"\\.\pipe\openssh-ssh-agent";
+    let pipe = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(PIPE)
+        .with_context(|| format!("无法连接 {PIPE}"))?;
+    Ok(Box::new(pipe))
+}
+
+/// 从缓冲区取出一条完整消息（去掉长度前缀），数据不足时返回 None。
+fn take_message(inbound: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+    let Some(header) = inbound.get(..4) else {
+        return Ok(None);
+    };
+    let length = u32::from_be_bytes(header.try_into().expect("four bytes")) as usize;
+    if length == 0 || length > MAX_MESSAGE {
+        bail!("agent 请求长度异常: {length}");
+    }
+    if inbound.len() < 4 + length {
+        return Ok(None);
+    }
+    let message = inbound[4..4 + length].to_vec();
+    inbound.drain(..4 + length);
+    Ok(Some(message))
+}
+
+fn frame(message: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(4 + message.len());
+    data.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    data.extend_from_slice(message);
+    data
+}
+
+fn read_u32(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let bytes = data.get(*pos..*pos + 4)?;
+    *pos += 4;
+    Some(u32::from_be_bytes(bytes.try_into().ok()?))
+}
+
+fn read_string<'d>(data: &'d [u8], pos: &mut usize) -> Option<&'d [u8]> {
+    let length = read_u32(data, pos)? as usize;
+    let value = data.get(*pos..pos.checked_add(length)?)?;
+    *pos += length;
+    Some(value)
+}
@@ -0,0 +1,278 @@
+//! 终端输出中的 ANSI 转义序列处理：去除控制序列、保留 SGR 颜色，以及把带颜色的文本渲染成 HTML。
+
+use std::fmt::Write;
+
+/// OSC 内容的长度上限，超出部分被丢弃，避免异常输出占用内存。
+const MAX_OSC_BYTES: usize = 4096;
+
+/// 过滤后的输出片段。
+pub enum AnsiPiece<'a> {
+    Text(char),
+    /// SGR 序列（`ESC [ ... m`），内容为参数部分。
+    Sgr(&'a str),
+    /// OSC 序列（`ESC ] ... BEL` 或 `ESC ] ... ESC \\`），内容为终止符之前的部分。
+    Osc(&'a str),
+    /// 换行与制表符以外的控制字符（退格、回车等），不属于可见文本。
+    Control(char),
+}
+
+#[derive(Default)]
+enum FilterState {
+    #[default]
+    Text,
+    Escape,
+    /// `ESC` 之后的中间字节（0x20–0x2F，如字符集切换 `ESC ( B`），等待结束字节。
+    EscapeIntermediate,
+    Csi,
+    Osc,
+    OscEscape,
+}
+
+/// 跨分块保持状态的转义序列过滤器：SGR、OSC 与控制字符分别以 [`AnsiPiece::Sgr`]、[`AnsiPiece::Osc`]、[`AnsiPiece::Control`] 交给调用方，其余序列被丢弃。
+#[derive(Default)]
+pub struct AnsiFilter {
+    state: FilterState,
+    params: String,
+}
+
+impl AnsiFilter {
+    pub fn feed(&mut self, data: &str, mut emit: impl FnMut(AnsiPiece<'_>)) {
+        for ch in data.chars() {
+            match self.state {
+                FilterState::Text => match ch {
+                    '\x1b' => self.state = FilterState::Escape,
+                    '\n' | '\t' => emit(AnsiPiece::Text(ch)),
+                    c if c.is_control() => emit(AnsiPiece::Control(c)),
+                    c => emit(AnsiPiece::Text(c)),
+                },
+                FilterState::Escape => {
+                    self.state = match ch {
+                        '[' => {
+                            self.params.clear();
+                            FilterState::Csi
+                        }
+                        ']' => {
+                            self.params.clear();
+                            FilterState::Osc
+                        }
+                        '\x20'..='\x2f' => FilterState::EscapeIntermediate,
+                        _ => FilterState::Text,
+                    }
+                }
+                FilterState::EscapeIntermediate => {
+                    if !('\x20'..='\x2f').contains(&ch) {
+                        self.state = FilterState::Text;
+                    }
+                }
+                FilterState::Csi => {
+                    if ('\x40'..='\x7e').contains(&ch) {
+                        if ch == 'm' {
+                            emit(AnsiPiece::Sgr(&self.params));
+                        }
+                        self.state = FilterState::Text;
+                    } else {
+                        self.params.push(ch);
+                    }
+                }
+                FilterState::Osc => match ch {
+                    '\x07' => {
+                        emit(AnsiPiece::Osc(&self.params));
+                        self.state = FilterState::Text;
+                    }
+                    '\x1b' => self.state = FilterState::OscEscape,
+                    _ if self.params.len() < MAX_OSC_BYTES => self.params.push(ch),
+                    _ => {}
+                },
+                FilterState::OscEscape => {
+                    if ch == '\\' {
+                        emit(AnsiPiece::Osc(&self.params));
+                        self.state = FilterState::Text;
+                    } else {
+                        self.params.push(ch);
+                        self.state = FilterState::Osc;
+                    }
+                }
+            }
+        }
+    }
+}
+
+const PALETTE: [&str; 16] = [
+    "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5",
+    "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+];
+
+#[derive(Clone, Default, PartialEq)]
+struct Style {
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Style {
+    fn is_plain(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn css(&self) -> String {
+        let mut css = String::new();
+        if let Some(fg) = &self.fg {
+            let _ = write!(css, "color:{fg};");
+        }
+        if let Some(bg) = &self.bg {
+            let _ = write!(css, "background-color:{bg};");
+        }
+        if self.bold {
+            css.push_str("font-weight:bold;");
+        }
+        if self.italic {
+            css.push_str("font-style:italic;");
+        }
+        if self.underline {
+            css.push_str("text-decoration:underline;");
+        }
+        css
+    }
+
+    /// 参数以 `;` 分隔，单个参数内可带 `:` 子参数（`38:2::r:g:b`、`4:3` 等）。
+    fn apply(&mut self, params: &str) {
+        if params.is_empty() {
+            *self = Self::default();
+            return;
+        }
+        let mut groups = params.split(';');
+        while let Some(group) = groups.next() {
+            let mut fields = group.split(':');
+            let code: u16 = fields
+                .next()
+                .and_then(|code| code.parse().ok())
+                .unwrap_or(0);
+            let subs: Vec<u16> = fields.map(|sub| sub.parse().unwrap_or(0)).collect();
+            match code {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                3 => self.italic = true,
+                // `4:0` 关闭下划线，`4:1`–`4:5` 为各种样式的下划线
+                4 => self.underline = subs.first() != Some(&0),
+                22 => self.bold = false,
+                23 => self.italic = false,
+                24 => self.underline = false,
+                30..=37 => self.fg = Some(PALETTE[(code - 30) as usize].to_string()),
+                90..=97 => self.fg = Some(PALETTE[(code - 90 + 8) as usize].to_string()),
+                40..=47 => self.bg = Some(PALETTE[(code - 40) as usize].to_string()),
+                100..=107 => self.bg = Some(PALETTE[(code - 100 + 8) as usize].to_string()),
+                38 | 48 => {
+                    let color = if subs.is_empty() {
+                        extended_color(&mut groups.by_ref().map(|v| v.parse().unwrap_or(0)))
+                    } else {
+                        colon_color(subs)
+                    };
+                    if code == 38 {
+                        self.fg = color;
+                    } else {
+                        self.bg = color;
+                    }
+                }
+                39 => self.fg = None,
+                49 => self.bg = None,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// 解析 `38;5;n` / `38;2;r;g;b` 形式的扩展颜色。
+fn extended_color(iter: &mut impl Iterator<Item = u16>) -> Option<String> {
+    match iter.next()? {
+        5 => Some(xterm_256(iter.next()?)),
+        2 => {
+            let (r, g, b) = (iter.next()?, iter.next()?, iter.next()?);
+            Some(format!(
+                "#{:02x}{:02x}{:02x}",
+                r.min(255),
+                g.min(255),
+                b.min(255)
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// 冒号子参数形式的扩展颜色：`38:5:n`、`38:2:r:g:b`，以及带色彩空间字段的 `38:2::r:g:b`。
+fn colon_color(mut subs: Vec<u16>) -> Option<String> {
+    if subs.first() == Some(&2) && subs.len() >= 5 {
+        subs.remove(1);
+    }
+    extended_color(&mut subs.into_iter())
+}
+
+fn xterm_256(index: u16) -> String {
+    match index {
+        0..=15 => PALETTE[index as usize].to_string(),
+        16..=231 => {
+            let level = |value: u16| if value == 0 { 0 } else { value * 40 + 55 };
+            let cube = index - 16;
+            format!(
+                "#{:02x}{:02x}{:02x}",
+                level(cube / 36),
+                level((cube / 6) % 6),
+                level(cube % 6)
+            )
+        }
+        _ => {
+            let gray = (index.min(255) - 232) * 10 + 8;
+            format!("#{gray:02x}{gray:02x}{gray:02x}")
+        }
+    }
+}
+
+/// 把保留 SGR 的文本渲染为独立 HTML 文档，颜色以内联样式保留。
+pub fn to_html(title: &str, data: &str) -> String {
+    let mut body = String::with_capacity(data.len() * 2);
+    let mut style = Style::default();
+    let mut span_open = false;
+    AnsiFilter::default().feed(data, |piece| match piece {
+        AnsiPiece::Text(ch) => {
+            if !span_open && !style.is_plain() {
+                let _ = write!(body, "<span style=\"{}\">", style.css());
+                span_open = true;
+            }
+            push_escaped(&mut body, ch);
+        }
+        AnsiPiece::Sgr(params) => {
+            let mut next = style.clone();
+            next.apply(params);
+            if next != style {
+                if span_open {
+                    body.push_str("</span>");
+                    span_open = false;
+                }
+                style = next;
+            }
+        }
+        AnsiPiece::Osc(_) | AnsiPiece::Control(_) => {}
+    });
+    if span_open {
+        body.push_str("</span>");
+    }
+
+    let mut escaped_title = String::new();
+    title
+        .chars()
+        .for_each(|ch| push_escaped(&mut escaped_title, ch));
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{escaped_title}</title>\n</head>\n<body style=\"background:#1e1e1e;color:#e5e5e5\">\n<pre style=\"font-family:monospace;white-space:pre-wrap\">{body}</pre>\n</body>\n</html>\n"
+    )
+}
+
+fn push_escaped(out: &mut String, ch: char) {
+    match ch {
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '&' => out.push_str("&amp;"),
+        '"' => out.push_str("&quot;"),
+        c => out.push(c),
+    }
+}
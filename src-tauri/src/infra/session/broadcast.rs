@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::SessionManager;
+
+/// 输入广播组：组 id 与普通会话 id 共用 `send_session_input`，写入会扇出到全部成员。
+#[derive(Clone, Default)]
+pub struct BroadcastGroups {
+    groups: Arc<Mutex<HashMap<String, Vec<String>>>>,
+}
+
+impl BroadcastGroups {
+    pub async fn members(&self, group_id: &str) -> Option<Vec<String>> {
+        self.groups.lock().await.get(group_id).cloned()
+    }
+
+    /// 会话关闭后从所有组中移除。
+    pub async fn forget_session(&self, session_id: &str) {
+        for members in self.groups.lock().await.values_mut() {
+            members.retain(|member| member != session_id);
+        }
+    }
+}
+
+impl SessionManager {
+    pub async fn create_broadcast_group(&self, session_ids: Vec<String>) -> Result<String> {
+        let group_id = format!("broadcast-{}", Uuid::new_v4().simple());
+        let mut members = Vec::new();
+        for session_id in session_ids {
+            self.ensure_session(&session_id).await?;
+            if !members.contains(&session_id) {
+                members.push(session_id);
+            }
+        }
+        self.broadcast
+            .groups
+            .lock()
+            .await
+            .insert(group_id.clone(), members);
+        Ok(group_id)
+    }
+
+    pub async fn add_broadcast_member(&self, group_id: &str, session_id: &str) -> Result<()> {
+        self.ensure_session(session_id).await?;
+        let mut groups = self.broadcast.groups.lock().await;
+        let members = groups
+            .get_mut(group_id)
+            .ok_or_else(|| anyhow!("广播组不存在"))?;
+        if !members.iter().any(|member| member == session_id) {
+            members.push(session_id.to_string());
+        }
+        Ok(())
+    }
+
+    pub async fn remove_broadcast_member(&self, group_id: &str, session_id: &str) -> Result<()> {
+        let mut groups = self.broadcast.groups.lock().await;
+        let members = groups
+            .get_mut(group_id)
+            .ok_or_else(|| anyhow!("广播组不存在"))?;
+        members.retain(|member| member != session_id);
+        Ok(())
+    }
+
+    pub async fn dissolve_broadcast_group(&self, group_id: &str) -> Result<()> {
+        self.broadcast
+            .groups
+            .lock()
+            .await
+            .remove(group_id)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("广播组不存在"))
+    }
+
+    pub async fn broadcast_members(&self, group_id: &str) -> Result<Vec<String>> {
+        self.broadcast
+            .members(group_id)
+            .await
+            .ok_or_else(|| anyhow!("广播组不存在"))
+    }
+
+    async fn ensure_session(&self, session_id: &str) -> Result<()> {
+        if self.sessions.lock().await.contains_key(session_id) {
+            Ok(())
+        } else {
+            Err(anyhow!("session not found: {session_id}"))
+        }
+    }
+}
@@ -0,0 +1,646 @@
+//! 本地会话守护进程：开启“保持本地会话”后，本地 shell 交由独立的后台进程托管，
+//! 应用关闭、崩溃或升级后继续运行；重新启动的应用经 Unix 套接字列出并重新附加，连同回滚缓冲一起还原。
+//!
+//! 守护进程即应用本身以 `--session-daemon <socket>` 启动，脱离应用的进程组。协议为按行分隔的 JSON：
+//! `spawn` / `list` 为一问一答的短连接；`attach` 之后连接保持，守护进程持续推送输出与退出事件，
+//! 客户端在同一连接上发送输入与关闭请求。断开连接只是分离，会话继续运行；最后一个会话结束后守护进程退出。
+
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::process::ChildStdin;
+use tokio::sync::{broadcast, Notify};
+use uuid::Uuid;
+
+use crate::domain::models::{SessionErrorCode, SessionState, SessionStateReason, ShellProfile};
+
+use super::events::Events;
+use super::history::SessionExit;
+use super::state::StateChange;
+use super::{describe_exit, profile, LocalStdin, SessionHandle, SessionKind, SessionManager};
+
+/// 以守护进程方式启动应用的命令行参数，其后跟套接字路径。
+pub const DAEMON_ARG: &str = "--session-daemon";
+/// 应用数据目录下的套接字文件名。
+pub const SOCKET_NAME: &str = "sessiond.sock";
+/// 每个会话保留的回滚缓冲上限，重新附加时整体回放。
+const SCROLLBACK_LIMIT: usize = 256 * 1024;
+/// 启动守护进程后等待其开始监听的时长。
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    Spawn {
+        profile: ShellProfile,
+        cwd: Option<String>,
+    },
+    List,
+    Attach {
+        id: String,
+    },
+    Input {
+        data: String,
+    },
+    Close,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply {
+    Spawned {
+        id: String,
+    },
+    Sessions {
+        sessions: Vec<HostedSession>,
+    },
+    Attached {
+        name: String,
+        scrollback: String,
+    },
+    Output {
+        stream: String,
+        data: String,
+    },
+    Exited {
+        exit_code: Option<i32>,
+        exit_signal: Option<String>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct HostedSession {
+    id: String,
+    name: String,
+    started_at: String,
+}
+
+/// 守护进程侧：托管的 shell 与其回滚缓冲。
+struct Hosted {
+    info: HostedSession,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    scrollback: String,
+    events: broadcast::Sender<Reply>,
+    kill: Arc<Notify>,
+    /// 没有客户端附加时退出的 shell 保留退出事件，等下一次附加取走后再移除。
+    exited: Option<Reply>,
+}
+
+#[derive(Clone, Default)]
+struct Registry {
+    sessions: Arc<Mutex<HashMap<String, Hosted>>>,
+    /// 会话结束时通知监听循环检查是否还有会话。
+    emptied: Arc<Notify>,
+}
+
+/// 在 `socket` 上运行守护进程，直到最后一个会话结束；已有守护进程在监听时直接返回。
+pub async fn run_daemon(socket: &Path) -> Result<()> {
+    if UnixStream::connect(socket).await.is_ok() {
+        return Ok(());
+    }
+    let _ = fs::remove_file(socket);
+    let listener =
+        UnixListener::bind(socket).with_context(|| format!("无法监听 {}", socket.display()))?;
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+
+    let registry = Registry::default();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                let registry = registry.clone();
+                tokio::spawn(async move {
+                    let _ = serve(stream, registry).await;
+                });
+            }
+            _ = registry.emptied.notified() => {
+                if registry.sessions.lock().unwrap().is_empty() {
+                    break;
+                }
+            }
+        }
+    }
+    let _ = fs::remove_file(socket);
+    Ok(())
+}
+
+/// `--session-daemon` 入口：脱离应用独立运行，不启动界面。
+pub fn run_daemon_blocking(socket: PathBuf) -> Result<()> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run_daemon(&socket))
+}
+
+async fn serve(stream: UnixStream, registry: Registry) -> Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let Some(line) = lines.next_line().await? else {
+        return Ok(());
+    };
+    let reply = match serde_json::from_str(&line)? {
+        Request::Spawn { profile, cwd } => match registry.spawn(&profile, cwd.as_deref()) {
+            Ok(id) => Reply::Spawned { id },
+            Err(err) => Reply::Error {
+                message: err.to_string(),
+            },
+        },
+        Request::List => {
+            let mut sessions: Vec<HostedSession> = registry
+                .sessions
+                .lock()
+                .unwrap()
+                .values()
+                .map(|hosted| hosted.info.clone())
+                .collect();
+            sessions.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+            Reply::Sessions { sessions }
+        }
+        Request::Attach { id } => return registry.attach(&id, lines, write).await,
+        Request::Input { .. } | Request::Close => Reply::Error {
+            message: "尚未附加会话".to_string(),
+        },
+    };
+    send_line(&mut write, &reply).await
+}
+
+impl Registry {
+    fn spawn(&self, profile: &ShellProfile, cwd: Option<&str>) -> Result<String> {
+        let mut cmd = profile::shell_command(profile, cwd);
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("无法启动 {}", profile.program))?;
+        let stdin = child.stdin.take().ok_or_else(|| anyhow!("缺少 stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("缺少 stdout"))?;
+        let stderr = child.stderr.take().ok_or_else(|| anyhow!("缺少 stderr"))?;
+
+        let id = format!("session-{}", Uuid::new_v4().simple());
+        let kill = Arc::new(Notify::new());
+        self.sessions.lock().unwrap().insert(
+            id.clone(),
+            Hosted {
+                info: HostedSession {
+                    id: id.clone(),
+                    name: profile.name.clone(),
+                    started_at: Utc::now().to_rfc3339(),
+                },
+                stdin: Arc::new(tokio::sync::Mutex::new(stdin)),
+                scrollback: String::new(),
+                events: broadcast::channel(1024).0,
+                kill: kill.clone(),
+                exited: None,
+            },
+        );
+
+        let stdout_task = self.record(&id, stdout, "stdout");
+        self.record(&id, stderr, "stderr");
+        let registry = self.clone();
+        let session_id = id.clone();
+        tokio::spawn(async move {
+            let waited = tokio::select! {
+                status = child.wait() => Some(status),
+                _ = kill.notified() => None,
+            };
+            let killed = waited.is_none();
+            let status = match waited {
+                Some(status) => status,
+                None => {
+                    let _ = child.start_kill();
+                    child.wait().await
+                }
+            };
+            let _ = stdout_task.await;
+            let exit = match status {
+                Ok(status) => SessionExit {
+                    exit_code: status.code(),
+                    exit_signal: super::local_exit_signal(&status),
+                },
+                Err(_) => SessionExit::default(),
+            };
+            let exited = Reply::Exited {
+                exit_code: exit.exit_code,
+                exit_signal: exit.exit_signal,
+            };
+            let mut sessions = registry.sessions.lock().unwrap();
+            let Some(hosted) = sessions.get_mut(&session_id) else {
+                return;
+            };
+            // 刚启动就退出、或应用不在时退出的 shell 先留着，附加方仍能看到最后的输出与退出码
+            if !killed && hosted.events.receiver_count() == 0 {
+                hosted.exited = Some(exited);
+                return;
+            }
+            if let Some(hosted) = sessions.remove(&session_id) {
+                let _ = hosted.events.send(exited);
+            }
+            drop(sessions);
+            registry.emptied.notify_one();
+        });
+        Ok(id)
+    }
+
+    /// 输出先写入回滚缓冲再推送，两者在同一把锁内完成，附加时的回放与后续推送不重不漏。
+    fn record(
+        &self,
+        id: &str,
+        stream_handle: impl AsyncRead + Unpin + Send + 'static,
+        stream: &'static str,
+    ) -> tokio::task::JoinHandle<()> {
+        let registry = self.clone();
+        let id = id.to_string();
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(stream_handle);
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match reader.read_line(&mut line).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        let mut sessions = registry.sessions.lock().unwrap();
+                        let Some(hosted) = sessions.get_mut(&id) else {
+                            break;
+                        };
+                        hosted.scrollback.push_str(&line);
+                        trim_scrollback(&mut hosted.scrollback);
+                        let _ = hosted.events.send(Reply::Output {
+                            stream: stream.to_string(),
+                            data: line.clone(),
+                        });
+                    }
+                }
+            }
+        })
+    }
+
+    async fn attach(
+        &self,
+        id: &str,
+        mut lines: Lines<BufReader<OwnedReadHalf>>,
+        mut write: OwnedWriteHalf,
+    ) -> Result<()> {
+        let attached = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let attached = sessions.get(id).map(|hosted| {
+                (
+                    Reply::Attached {
+                        name: hosted.info.name.clone(),
+                        scrollback: hosted.scrollback.clone(),
+                    },
+                    hosted.events.subscribe(),
+                    hosted.stdin.clone(),
+                    hosted.kill.clone(),
+                    hosted.exited.clone(),
+                )
+            });
+            // 已退出的会话交给这次附加后即移除
+            if matches!(&attached, Some((.., Some(_)))) {
+                sessions.remove(id);
+            }
+            attached
+        };
+        let Some((reply, mut events, stdin, kill, exited)) = attached else {
+            return send_line(
+                &mut write,
+                &Reply::Error {
+                    message: "会话不存在".to_string(),
+                },
+            )
+            .await;
+        };
+        send_line(&mut write, &reply).await?;
+        if let Some(exited) = exited {
+            self.emptied.notify_one();
+            return send_line(&mut write, &exited).await;
+        }
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(reply) => {
+                        let exited = matches!(reply, Reply::Exited { .. });
+                        send_line(&mut write, &reply).await?;
+                        if exited {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                line = lines.next_line() => match line {
+                    Ok(Some(line)) => match serde_json::from_str(&line) {
+                        Ok(Request::Input { data }) => {
+                            let mut stdin = stdin.lock().await;
+                            stdin.write_all(data.as_bytes()).await?;
+                            stdin.flush().await?;
+                        }
+                        Ok(Request::Close) => kill.notify_one(),
+                        _ => {}
+                    },
+                    // 客户端断开只是分离，会话继续运行
+                    _ => break,
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+fn trim_scrollback(scrollback: &mut String) {
+    if scrollback.len() <= SCROLLBACK_LIMIT {
+        return;
+    }
+    let mut cut = scrollback.len() - SCROLLBACK_LIMIT;
+    while !scrollback.is_char_boundary(cut) {
+        cut += 1;
+    }
+    scrollback.drain(..cut);
+}
+
+async fn send_line(writer: &mut (impl AsyncWrite + Unpin), message: &impl Serialize) -> Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// 应用侧的守护进程配置：套接字位置与启动守护进程所用的可执行文件。
+#[derive(Clone, Default)]
+pub struct LocalDaemon {
+    inner: Arc<Mutex<Option<DaemonTarget>>>,
+    /// 启动并附加新会话与重新附加互斥，避免同一个守护进程会话在本应用中被附加两次。
+    attaching: Arc<tokio::sync::Mutex<()>>,
+}
+
+#[derive(Clone)]
+pub(super) struct DaemonTarget {
+    socket: PathBuf,
+    /// 为空时不自动启动，只连接已在运行的守护进程。
+    launcher: Option<PathBuf>,
+}
+
+impl DaemonTarget {
+    /// 连接守护进程，未运行时用 `launcher` 启动并等待其开始监听。
+    async fn connect(&self, launch: bool) -> Result<UnixStream> {
+        if let Ok(stream) = UnixStream::connect(&self.socket).await {
+            return Ok(stream);
+        }
+        let Some(launcher) = self.launcher.as_ref().filter(|_| launch) else {
+            bail!("本地会话守护进程未运行");
+        };
+        let mut child = std::process::Command::new(launcher)
+            .arg(DAEMON_ARG)
+            .arg(&self.socket)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()
+            .context("无法启动本地会话守护进程")?;
+        // 守护进程退出时回收，避免在应用运行期间留下僵尸进程
+        thread::spawn(move || child.wait());
+        let deadline = tokio::time::Instant::now() + LAUNCH_TIMEOUT;
+        loop {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            match UnixStream::connect(&self.socket).await {
+                Ok(stream) => return Ok(stream),
+                Err(err) if tokio::time::Instant::now() >= deadline => {
+                    return Err(anyhow!("本地会话守护进程未能启动: {err}"))
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    /// 一问一答的短连接请求。
+    async fn request(&self, request: &Request, launch: bool) -> Result<Reply> {
+        let stream = self.connect(launch).await?;
+        let (read, mut write) = stream.into_split();
+        send_line(&mut write, request).await?;
+        read_reply(&mut BufReader::new(read).lines()).await
+    }
+}
+
+async fn read_reply(lines: &mut Lines<BufReader<OwnedReadHalf>>) -> Result<Reply> {
+    let line = lines
+        .next_line()
+        .await?
+        .ok_or_else(|| anyhow!("本地会话守护进程断开了连接"))?;
+    match serde_json::from_str(&line)? {
+        Reply::Error { message } => Err(anyhow!(message)),
+        reply => Ok(reply),
+    }
+}
+
+impl SessionManager {
+    /// 设置守护进程的套接字与启动程序（通常为应用自身）；是否启用由 `persistentSessions` 设置决定。
+    pub fn set_local_daemon(&self, socket: PathBuf, launcher: Option<PathBuf>) {
+        *self.daemon.inner.lock().unwrap() = Some(DaemonTarget { socket, launcher });
+    }
+
+    /// 已启用“保持本地会话”时返回守护进程配置。
+    pub(super) fn daemon_target(&self) -> Option<DaemonTarget> {
+        if !self.shell_profiles().persistent_sessions {
+            return None;
+        }
+        self.daemon.inner.lock().unwrap().clone()
+    }
+
+    pub(super) async fn spawn_daemon_shell(
+        &self,
+        target: &DaemonTarget,
+        events: Events,
+        profile: &ShellProfile,
+        cwd: Option<&str>,
+    ) -> Result<String> {
+        let _attaching = self.daemon.attaching.lock().await;
+        let request = Request::Spawn {
+            profile: profile.clone(),
+            cwd: cwd.map(str::to_string),
+        };
+        let Reply::Spawned { id } = target.request(&request, true).await? else {
+            bail!("本地会话守护进程返回了意外的响应");
+        };
+        self.attach_daemon_session(target, events, &id).await?;
+        Ok(id)
+    }
+
+    /// 重新附加守护进程中仍在运行、本应用尚未打开的本地会话，回滚缓冲作为首段输出回放。
+    pub async fn reattach_local_sessions(&self, events: Events) -> Result<Vec<String>> {
+        let Some(target) = self.daemon_target() else {
+            return Ok(Vec::new());
+        };
+        let _attaching = self.daemon.attaching.lock().await;
+        let Ok(Reply::Sessions { sessions }) = target.request(&Request::List, false).await else {
+            return Ok(Vec::new());
+        };
+        let mut attached = Vec::new();
+        for hosted in sessions {
+            if self.sessions.lock().await.contains_key(&hosted.id) {
+                continue;
+            }
+            // 重新附加期间为 reconnecting，附加成功后迁移为 connected
+            self.states.transition(
+                &events,
+                &hosted.id,
+                SessionState::Reconnecting,
+                StateChange::default(),
+            );
+            if let Err(err) = self
+                .attach_daemon_session(&target, events.clone(), &hosted.id)
+                .await
+            {
+                self.states.transition(
+                    &events,
+                    &hosted.id,
+                    SessionState::Failed,
+                    StateChange::failure(SessionErrorCode::IoError, err.to_string()),
+                );
+                return Err(err);
+            }
+            attached.push(hosted.id);
+        }
+        Ok(attached)
+    }
+
+    async fn attach_daemon_session(
+        &self,
+        target: &DaemonTarget,
+        events: Events,
+        session_id: &str,
+    ) -> Result<()> {
+        let stream = target.connect(false).await?;
+        let (read, mut write) = stream.into_split();
+        send_line(
+            &mut write,
+            &Request::Attach {
+                id: session_id.to_string(),
+            },
+        )
+        .await?;
+        let mut lines = BufReader::new(read).lines();
+        let Reply::Attached { name, scrollback } = read_reply(&mut lines).await? else {
+            bail!("本地会话守护进程返回了意外的响应");
+        };
+
+        self.open_output(&events, session_id, None, &name, true);
+        self.history.start(session_id, None, name);
+        self.states.transition(
+            &events,
+            session_id,
+            SessionState::Connected,
+            StateChange::default(),
+        );
+        if scrollback.is_empty() {
+            self.publish_output(
+                session_id,
+                "stdout",
+                "本地 shell 已启动（由守护进程托管）\r\n",
+            );
+        } else {
+            self.publish_output(session_id, "stdout", &scrollback);
+            self.publish_output(session_id, "stdout", "已重新附加本地会话\r\n");
+        }
+
+        self.sessions.lock().await.insert(
+            session_id.to_string(),
+            SessionHandle {
+                kind: SessionKind::Local {
+                    stdin: LocalStdin::Daemon(Arc::new(tokio::sync::Mutex::new(write))),
+                },
+                events: events.clone(),
+            },
+        );
+
+        let manager = self.clone();
+        let exit_session_id = session_id.to_string();
+        tokio::spawn(async move {
+            let (state, change, exit) = manager
+                .relay_daemon_output(&exit_session_id, &mut lines)
+                .await;
+            manager
+                .finish_session(&events, &exit_session_id, state, change, exit)
+                .await;
+        });
+        Ok(())
+    }
+
+    async fn relay_daemon_output(
+        &self,
+        session_id: &str,
+        lines: &mut Lines<BufReader<OwnedReadHalf>>,
+    ) -> (SessionState, StateChange, SessionExit) {
+        loop {
+            let reply = match lines.next_line().await {
+                Ok(Some(line)) => serde_json::from_str(&line).ok(),
+                _ => break,
+            };
+            match reply {
+                Some(Reply::Output { stream, data }) => {
+                    self.history.add_bytes_in(session_id, data.len());
+                    let stream = if stream == "stderr" {
+                        "stderr"
+                    } else {
+                        "stdout"
+                    };
+                    self.publish_output(session_id, stream, &data);
+                }
+                Some(Reply::Exited {
+                    exit_code,
+                    exit_signal,
+                }) => {
+                    let exit = SessionExit {
+                        exit_code,
+                        exit_signal,
+                    };
+                    let message = format!("本地 shell 已退出{}", describe_exit(&exit));
+                    return (
+                        SessionState::Closed,
+                        StateChange::reason(SessionStateReason::ProcessExited, message),
+                        exit,
+                    );
+                }
+                _ => {}
+            }
+        }
+        (
+            SessionState::Failed,
+            StateChange::failure(SessionErrorCode::IoError, "与本地会话守护进程的连接已断开"),
+            SessionExit::default(),
+        )
+    }
+}
+
+/// 向守护进程托管的会话写入输入。
+pub(super) async fn write_daemon_input(writer: &mut OwnedWriteHalf, data: &str) -> Result<()> {
+    send_line(
+        writer,
+        &Request::Input {
+            data: data.to_string(),
+        },
+    )
+    .await
+}
+
+/// 结束守护进程托管的会话（终止 shell 进程）。
+pub(super) async fn close_daemon_session(writer: &mut OwnedWriteHalf) -> Result<()> {
+    send_line(writer, &Request::Close).await
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+/// 会话层推送事件的出口。生产环境由 `AppHandle` 实现，测试可换成内存收集器，无需启动应用。
+pub trait EventSink: Send + Sync {
+    fn emit_value(&self, event: &str, payload: serde_json::Value);
+
+    /// 只推送给标签为 `target` 的窗口；默认退化为广播，测试收集器无需区分窗口。
+    fn emit_value_to(&self, target: &str, event: &str, payload: serde_json::Value) {
+        let _ = target;
+        self.emit_value(event, payload);
+    }
+}
+
+impl EventSink for AppHandle {
+    fn emit_value(&self, event: &str, payload: serde_json::Value) {
+        let _ = Emitter::emit(self, event, payload);
+    }
+
+    fn emit_value_to(&self, target: &str, event: &str, payload: serde_json::Value) {
+        let _ = Emitter::emit_to(self, target, event, payload);
+    }
+}
+
+/// 把全部事件定向到一个窗口的出口。
+struct TargetedSink {
+    sink: Arc<dyn EventSink>,
+    target: String,
+}
+
+impl EventSink for TargetedSink {
+    fn emit_value(&self, event: &str, payload: serde_json::Value) {
+        self.sink.emit_value_to(&self.target, event, payload);
+    }
+}
+
+/// 可廉价克隆的事件句柄，供会话线程与后台任务持有。
+#[derive(Clone)]
+pub struct Events {
+    sink: Arc<dyn EventSink>,
+    target: Option<String>,
+}
+
+impl Events {
+    pub fn new(sink: impl EventSink + 'static) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            target: None,
+        }
+    }
+
+    /// 只推送给标签为 `target` 的窗口的句柄，供观察者窗口各自持有。
+    pub fn for_target(&self, target: &str) -> Self {
+        Self {
+            target: Some(target.to_string()),
+            ..Self::new(TargetedSink {
+                sink: self.sink.clone(),
+                target: target.to_string(),
+            })
+        }
+    }
+
+    /// 定向推送的窗口标签；会话的事件句柄据此确定所属窗口。
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    pub fn emit<T: Serialize>(&self, event: &str, payload: T) -> serde_json::Result<()> {
+        self.sink.emit_value(event, serde_json::to_value(payload)?);
+        Ok(())
+    }
+}
+
+impl From<AppHandle> for Events {
+    fn from(app: AppHandle) -> Self {
+        Self::new(app)
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use tokio::sync::Notify;
+
+use crate::domain::models::{is_env_name, CommandOutput, Connection, Protocol};
+
+use super::history::SessionExit;
+use super::pool::ConnectionPool;
+use super::{
+    close_channel, is_session_would_block, is_would_block, read_exit_status, shell_quote,
+    wait_for_ssh, SessionManager, SessionSecret,
+};
+
+/// 一次非交互式命令：本地走 `sh -c`，远程走 ssh2 exec 通道（不申请 PTY）。
+#[derive(Clone, Debug, Default)]
+pub struct ExecRequest {
+    pub command: String,
+    pub stdin: Option<String>,
+    pub env: HashMap<String, String>,
+    pub cwd: Option<String>,
+    pub timeout: Option<Duration>,
+    /// 取消后尽快终止命令，结果标记为 cancelled。
+    pub cancel: Option<CancelToken>,
+}
+
+/// 可在线程与异步任务之间共享的取消标记。
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// 正在执行的命令任务，按 job id 登记以便 `cancel_command` 取消。
+#[derive(Clone, Default)]
+pub struct ExecJobs {
+    jobs: Arc<Mutex<HashMap<String, CancelToken>>>,
+}
+
+impl ExecJobs {
+    pub fn register(&self, job_id: &str) -> CancelToken {
+        let token = CancelToken::default();
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(job_id.to_string(), token.clone());
+        token
+    }
+
+    pub fn finish(&self, job_id: &str) {
+        self.jobs.lock().unwrap().remove(job_id);
+    }
+
+    pub fn cancel(&self, job_id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(job_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecStream {
+    Stdout,
+    Stderr,
+}
+
+impl ExecStream {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
+impl SessionManager {
+    pub async fn exec_remote(
+        &self,
+        connection: Connection,
+        secret: Option<SessionSecret>,
+        request: ExecRequest,
+    ) -> Result<CommandOutput> {
+        self.exec_remote_streaming(connection, secret, request, |_, _| {})
+            .await
+    }
+
+    /// 与 [`exec_remote`](Self::exec_remote) 相同，但每读到一段输出就回调 `on_output`。
+    pub async fn exec_remote_streaming(
+        &self,
+        connection: Connection,
+        secret: Option<SessionSecret>,
+        request: ExecRequest,
+        mut on_output: impl FnMut(ExecStream, &[u8]) + Send + 'static,
+    ) -> Result<CommandOutput> {
+        if !matches!(connection.protocol, Protocol::Ssh | Protocol::Sftp) {
+            bail!("{} 不是 SSH 连接，无法执行远程命令", connection.name);
+        }
+        if let Some(key) = request.env.keys().find(|key| !is_env_name(key)) {
+            bail!("无效的环境变量名: {key}");
+        }
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            run_exec(
+                &pool,
+                &connection,
+                secret.as_ref(),
+                &request,
+                &mut on_output,
+            )
+        })
+        .await
+        .map_err(|err| anyhow!("远程命令线程异常退出: {err}"))?
+    }
+}
+
+fn run_exec(
+    pool: &ConnectionPool,
+    connection: &Connection,
+    secret: Option<&SessionSecret>,
+    request: &ExecRequest,
+    on_output: &mut dyn FnMut(ExecStream, &[u8]),
+) -> Result<CommandOutput> {
+    let (lease, mut channel) = pool.open_channel(connection, secret, || {})?;
+    let requests = lease.lock_requests();
+
+    // 多数 sshd 默认只接受 LANG/LC_* (AcceptEnv)，被拒绝的变量改为在命令前 export
+    let mut rejected = Vec::new();
+    for (key, value) in &request.env {
+        if wait_for_ssh("setenv", || channel.setenv(key, value)).is_err() {
+            rejected.push((key.as_str(), value.as_str()));
+        }
+    }
+    let mut command = request.command.clone();
+    if let Some(cwd) = &request.cwd {
+        command = format!("cd {} && {command}", shell_quote(cwd));
+    }
+    if !rejected.is_empty() {
+        let exports: Vec<String> = rejected
+            .iter()
+            .map(|(key, value)| format!("export {key}={};", shell_quote(value)))
+            .collect();
+        command = format!("{} {command}", exports.join(" "));
+    }
+    wait_for_ssh("exec", || channel.exec(&command))?;
+    drop(requests);
+
+    // 输入在读取循环中分段写入：远端不读输入时写入会反复 WouldBlock，超时与取消照常生效，
+    // 输出也不会因输入未写完而无人读取
+    let started = Instant::now();
+    let mut stdin = request.stdin.as_deref().unwrap_or_default().as_bytes();
+    let mut eof_sent = false;
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let mut timed_out = false;
+    let mut cancelled = false;
+    loop {
+        let mut read_something = false;
+        if !stdin.is_empty() {
+            match channel.write(stdin) {
+                Ok(written) => {
+                    read_something = written > 0;
+                    stdin = &stdin[written..];
+                }
+                Err(err) if is_would_block(&err) => {}
+                Err(err) => return Err(anyhow!("写入远程 stdin 失败: {err}")),
+            }
+        } else if !eof_sent {
+            match channel.send_eof() {
+                Ok(()) => eof_sent = true,
+                Err(err) if is_session_would_block(&err) => {}
+                Err(err) => return Err(anyhow!("send_eof: {err}")),
+            }
+        }
+        match channel.read(&mut buffer) {
+            Ok(0) => {}
+            Ok(size) => {
+                read_something = true;
+                on_output(ExecStream::Stdout, &buffer[..size]);
+                stdout.extend_from_slice(&buffer[..size]);
+            }
+            Err(err) if is_would_block(&err) => {}
+            Err(err) => return Err(anyhow!("读取远程 stdout 失败: {err}")),
+        }
+        match channel.stderr().read(&mut buffer) {
+            Ok(0) => {}
+            Ok(size) => {
+                read_something = true;
+                on_output(ExecStream::Stderr, &buffer[..size]);
+                stderr.extend_from_slice(&buffer[..size]);
+            }
+            Err(err) if is_would_block(&err) => {}
+            Err(err) => return Err(anyhow!("读取远程 stderr 失败: {err}")),
+        }
+
+        if channel.eof() && !read_something {
+            break;
+        }
+        if request
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.is_cancelled())
+        {
+            cancelled = true;
+            break;
+        }
+        if let Some(timeout) = request.timeout {
+            if started.elapsed() >= timeout {
+                timed_out = true;
+                break;
+            }
+        }
+        if !read_something {
+            thread::sleep(Duration::from_millis(12));
+        }
+    }
+
+    let exit = if timed_out || cancelled {
+        // 命令可能仍在运行：只发出关闭，不等待远端确认，超时与取消不再被远端拖住
+        wait_for_ssh("channel.close", || channel.close())?;
+        SessionExit::default()
+    } else {
+        close_channel(&mut channel)?;
+        read_exit_status(&channel)
+    };
+    Ok(CommandOutput {
+        stdout: String::from_utf8_lossy(&stdout).to_string(),
+        stderr: String::from_utf8_lossy(&stderr).to_string(),
+        exit_code: exit.exit_code,
+        exit_signal: exit.exit_signal,
+        timed_out,
+        cancelled,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
@@ -0,0 +1,128 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use crate::domain::models::{SessionRecord, SessionStateReason};
+use crate::domain::services::session_service::SessionHistoryRepository;
+
+const HISTORY_CAPACITY: usize = 200;
+
+/// 会话历史：运行中的会话登记在 `live`，结束后带上退出信息移入 `completed`。
+///
+/// 设置了持久化仓储时，结束的会话同时写入仓储。
+#[derive(Clone, Default)]
+pub struct SessionHistory {
+    inner: Arc<Mutex<HistoryInner>>,
+}
+
+#[derive(Default)]
+struct HistoryInner {
+    live: HashMap<String, SessionRecord>,
+    completed: VecDeque<SessionRecord>,
+    store: Option<Arc<dyn SessionHistoryRepository>>,
+}
+
+/// 会话结束时采集到的退出信息。
+#[derive(Clone, Debug, Default)]
+pub struct SessionExit {
+    pub exit_code: Option<i32>,
+    pub exit_signal: Option<String>,
+}
+
+impl SessionHistory {
+    pub fn start(&self, session_id: &str, connection_id: Option<String>, title: String) {
+        let record = SessionRecord {
+            session_id: session_id.to_string(),
+            connection_id,
+            title,
+            started_at: Utc::now().to_rfc3339(),
+            ended_at: None,
+            exit_code: None,
+            exit_signal: None,
+            close_reason: None,
+            message: None,
+            duration_ms: None,
+            bytes_in: 0,
+            bytes_out: 0,
+        };
+        self.inner
+            .lock()
+            .unwrap()
+            .live
+            .insert(session_id.to_string(), record);
+    }
+
+    pub fn finish(
+        &self,
+        session_id: &str,
+        exit: &SessionExit,
+        close_reason: Option<SessionStateReason>,
+        message: Option<String>,
+    ) -> Option<SessionRecord> {
+        let (mut record, store) = {
+            let mut inner = self.inner.lock().unwrap();
+            (inner.live.remove(session_id)?, inner.store.clone())
+        };
+        let ended_at = Utc::now();
+        record.duration_ms = DateTime::parse_from_rfc3339(&record.started_at)
+            .ok()
+            .and_then(|started| (ended_at - started.with_timezone(&Utc)).to_std().ok())
+            .map(|duration| duration.as_millis() as u64);
+        record.ended_at = Some(ended_at.to_rfc3339());
+        record.exit_code = exit.exit_code;
+        record.exit_signal = exit.exit_signal.clone();
+        record.close_reason = close_reason;
+        record.message = message;
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.completed.len() >= HISTORY_CAPACITY {
+                inner.completed.pop_back();
+            }
+            inner.completed.push_front(record.clone());
+        }
+        // 写库在锁外进行，慢速磁盘不会阻塞输出计数与会话列表
+        if let Some(store) = store {
+            if let Err(err) = store.save(&record) {
+                warn!(target: "session", session_id, %err, "写入会话历史失败");
+            }
+        }
+        Some(record)
+    }
+
+    pub fn set_store(&self, store: Arc<dyn SessionHistoryRepository>) {
+        self.inner.lock().unwrap().store = Some(store);
+    }
+
+    pub fn add_bytes_in(&self, session_id: &str, bytes: usize) {
+        if let Some(record) = self.inner.lock().unwrap().live.get_mut(session_id) {
+            record.bytes_in += bytes as u64;
+        }
+    }
+
+    pub fn add_bytes_out(&self, session_id: &str, bytes: usize) {
+        if let Some(record) = self.inner.lock().unwrap().live.get_mut(session_id) {
+            record.bytes_out += bytes as u64;
+        }
+    }
+
+    /// 运行中的会话，先开始的在前。
+    pub fn live(&self) -> Vec<SessionRecord> {
+        let mut records: Vec<SessionRecord> =
+            self.inner.lock().unwrap().live.values().cloned().collect();
+        records.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        records
+    }
+
+    /// 最近结束的会话，新的在前。
+    pub fn recent(&self) -> Vec<SessionRecord> {
+        self.inner
+            .lock()
+            .unwrap()
+            .completed
+            .iter()
+            .cloned()
+            .collect()
+    }
+}
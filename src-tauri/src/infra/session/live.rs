@@ -0,0 +1,64 @@
+use anyhow::Result;
+
+use crate::domain::models::{SessionState, SessionSummary};
+use crate::domain::services::session_service::SessionRepository;
+
+use super::SessionManager;
+
+/// 直接从 [`SessionManager`] 读取运行中会话的仓储实现。
+pub struct LiveSessionRepository {
+    manager: SessionManager,
+}
+
+impl LiveSessionRepository {
+    pub fn new(manager: SessionManager) -> Self {
+        Self { manager }
+    }
+}
+
+impl SessionRepository for LiveSessionRepository {
+    fn list(&self) -> Result<Vec<SessionSummary>> {
+        Ok(self.manager.live_sessions())
+    }
+}
+
+impl SessionManager {
+    /// 运行中会话的摘要，先开始的在前。
+    pub fn live_sessions(&self) -> Vec<SessionSummary> {
+        self.history
+            .live()
+            .into_iter()
+            .map(|record| {
+                let state = self.states.get(&record.session_id);
+                let latency = self.latency_stats(&record.session_id);
+                let current = state
+                    .as_ref()
+                    .map(|payload| payload.state)
+                    .unwrap_or(SessionState::Connecting);
+                SessionSummary {
+                    status_label: status_label(current).into(),
+                    updated_at: state
+                        .map(|payload| payload.updated_at)
+                        .unwrap_or_else(|| record.started_at.clone()),
+                    id: record.session_id,
+                    connection_id: record.connection_id,
+                    title: record.title,
+                    state: current,
+                    started_at: record.started_at,
+                    latency,
+                }
+            })
+            .collect()
+    }
+}
+
+fn status_label(state: SessionState) -> &'static str {
+    match state {
+        SessionState::Connecting => "连接中",
+        SessionState::Authenticating => "认证中",
+        SessionState::Connected => "已连接",
+        SessionState::Reconnecting => "重连中",
+        SessionState::Closed => "已关闭",
+        SessionState::Failed => "失败",
+    }
+}
@@ -0,0 +1,197 @@
+use std::process::Stdio;
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, Command};
+
+use crate::domain::models::{CommandOutput, Connection};
+
+use super::events::Events;
+use super::exec::{ExecRequest, ExecStream};
+use super::{local_exit_signal, SessionManager, SessionSecret};
+
+#[derive(serde::Serialize, Clone)]
+pub struct CommandOutputPayload {
+    pub job_id: String,
+    pub stream: String,
+    pub data: String,
+}
+
+impl SessionManager {
+    /// 以 job id 登记并执行一条命令；`connection` 为空时在本地执行。
+    ///
+    /// `stream` 为 true 时每段输出都会以 `command-output` 事件推送。
+    pub async fn run_command_job(
+        &self,
+        events: Events,
+        job_id: String,
+        connection: Option<Connection>,
+        secret: Option<SessionSecret>,
+        mut request: ExecRequest,
+        stream: bool,
+    ) -> Result<CommandOutput> {
+        request.cancel = Some(self.jobs.register(&job_id));
+        let event_job_id = job_id.clone();
+        let on_output = move |kind: ExecStream, data: &[u8]| {
+            if stream {
+                let _ = events.emit(
+                    "command-output",
+                    CommandOutputPayload {
+                        job_id: event_job_id.clone(),
+                        stream: kind.as_str().to_string(),
+                        data: String::from_utf8_lossy(data).to_string(),
+                    },
+                );
+            }
+        };
+        let result = match connection {
+            Some(connection) => {
+                self.exec_remote_streaming(connection, secret, request, on_output)
+                    .await
+            }
+            None => exec_local(&request, on_output).await,
+        };
+        self.jobs.finish(&job_id);
+        result
+    }
+
+    /// 取消正在执行的命令任务，返回该任务是否存在。
+    pub fn cancel_command(&self, job_id: &str) -> bool {
+        self.jobs.cancel(job_id)
+    }
+}
+
+enum Interrupt {
+    Cancelled,
+    TimedOut,
+}
+
+async fn exec_local(
+    request: &ExecRequest,
+    mut on_output: impl FnMut(ExecStream, &[u8]),
+) -> Result<CommandOutput> {
+    #[cfg(target_os = "windows")]
+    let mut cmd = Command::new("cmd");
+    #[cfg(target_os = "windows")]
+    cmd.arg("/C").arg(&request.command);
+
+    #[cfg(not(target_os = "windows"))]
+    let mut cmd = Command::new("sh");
+    #[cfg(not(target_os = "windows"))]
+    cmd.arg("-c").arg(&request.command);
+    // 独立进程组，取消或超时时连同命令派生的后台进程一并终止
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    if let Some(cwd) = &request.cwd {
+        cmd.current_dir(cwd);
+    }
+    cmd.envs(&request.env)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let started = Instant::now();
+    let mut child = cmd.spawn()?;
+    // 输入在单独任务中写入，写完即关闭管道：输入超过管道容量时不会与输出读取互相等待，
+    // 取消与超时也不会被阻塞的写入拖住；进程被终止后写入失败，任务随之结束
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), request.stdin.clone()) {
+        tokio::spawn(async move {
+            let _ = stdin.write_all(input.as_bytes()).await;
+        });
+    }
+    let mut stdout = child.stdout.take().ok_or_else(|| anyhow!("缺少 stdout"))?;
+    let mut stderr = child.stderr.take().ok_or_else(|| anyhow!("缺少 stderr"))?;
+
+    let cancel = request.cancel.clone().unwrap_or_default();
+    let deadline = request
+        .timeout
+        .map(|timeout| tokio::time::Instant::now() + timeout);
+    let mut out = Vec::new();
+    let mut err = Vec::new();
+    let mut out_buf = [0u8; 4096];
+    let mut err_buf = [0u8; 4096];
+    let mut out_open = true;
+    let mut err_open = true;
+    let mut interrupt = None;
+
+    while out_open || err_open {
+        tokio::select! {
+            read = stdout.read(&mut out_buf), if out_open => match read {
+                Ok(0) | Err(_) => out_open = false,
+                Ok(size) => {
+                    on_output(ExecStream::Stdout, &out_buf[..size]);
+                    out.extend_from_slice(&out_buf[..size]);
+                }
+            },
+            read = stderr.read(&mut err_buf), if err_open => match read {
+                Ok(0) | Err(_) => err_open = false,
+                Ok(size) => {
+                    on_output(ExecStream::Stderr, &err_buf[..size]);
+                    err.extend_from_slice(&err_buf[..size]);
+                }
+            },
+            _ = cancel.cancelled() => {
+                interrupt = Some(Interrupt::Cancelled);
+                break;
+            }
+            _ = sleep_until(deadline) => {
+                interrupt = Some(Interrupt::TimedOut);
+                break;
+            }
+        }
+    }
+
+    // 输出流关闭后进程仍可能在运行，等待退出时同样响应取消与超时
+    let status = match interrupt {
+        Some(_) => None,
+        None => tokio::select! {
+            status = child.wait() => Some(status?),
+            _ = cancel.cancelled() => {
+                interrupt = Some(Interrupt::Cancelled);
+                None
+            }
+            _ = sleep_until(deadline) => {
+                interrupt = Some(Interrupt::TimedOut);
+                None
+            }
+        },
+    };
+    if status.is_none() {
+        kill_process_tree(&mut child);
+        let _ = child.wait().await;
+    }
+
+    Ok(CommandOutput {
+        stdout: String::from_utf8_lossy(&out).to_string(),
+        stderr: String::from_utf8_lossy(&err).to_string(),
+        exit_code: status.and_then(|status| status.code()),
+        exit_signal: status.as_ref().and_then(local_exit_signal),
+        timed_out: matches!(interrupt, Some(Interrupt::TimedOut)),
+        cancelled: matches!(interrupt, Some(Interrupt::Cancelled)),
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// 终止命令进程；Unix 上向其所在进程组发送 SIGKILL。
+///
+/// 须在回收子进程之前调用：未被 wait 的子进程保留着 pid，进程组号不会被复用。
+fn kill_process_tree(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: killpg 只读取参数，组号即 process_group(0) 启动的子进程 pid
+        unsafe {
+            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+    let _ = child.start_kill();
+}
+
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::domain::models::LatencyStats;
+
+use super::events::Events;
+use super::SessionManager;
+
+/// 两次采样之间的最小间隔，连续输入时不会把窗口挤满同一时刻的样本。
+const PROBE_INTERVAL: Duration = Duration::from_secs(10);
+/// 输入后超过这个时间才有输出（如远端关闭了回显），不计为样本。
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// 通道至少安静这么久才开始计时；输出仍在持续时读到的下一段数据未必是回显。
+const QUIET_PERIOD: Duration = Duration::from_millis(500);
+/// 统计窗口保留的样本数。
+const WINDOW_SIZE: usize = 30;
+
+#[derive(serde::Serialize, Clone)]
+pub struct SessionMetricsPayload {
+    pub session_id: String,
+    pub last_ms: f64,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+    pub samples: u32,
+}
+
+/// 每个 SSH 会话最近若干次往返时延的滚动窗口。
+#[derive(Clone, Default)]
+pub struct SessionMetrics {
+    windows: Arc<Mutex<HashMap<String, VecDeque<Duration>>>>,
+}
+
+impl SessionMetrics {
+    fn record(&self, session_id: &str, rtt: Duration) -> LatencyStats {
+        let mut windows = self.windows.lock().unwrap();
+        let window = windows.entry(session_id.to_string()).or_default();
+        if window.len() == WINDOW_SIZE {
+            window.pop_front();
+        }
+        window.push_back(rtt);
+        summarize(window)
+    }
+
+    pub(super) fn forget(&self, session_id: &str) {
+        self.windows.lock().unwrap().remove(session_id);
+    }
+}
+
+/// 以输入回显计时：PTY 开启回显时，写入输入后通道上第一次读到的数据就是远端的回显，
+/// 两者之间是一次完整的往返。只借用交互本身的往返，不额外开通道或发请求。
+/// 仅在通道安静时计时，避免把持续输出（如 `tail -f`）中恰好到达的一段当成回显。
+#[derive(Default)]
+pub(super) struct EchoProbe {
+    sent: Option<Instant>,
+    last_sample: Option<Instant>,
+    last_output: Option<Instant>,
+}
+
+impl EchoProbe {
+    /// 输入已写入通道；距上次采样不足 `PROBE_INTERVAL`、已有待回显的输入
+    /// 或通道安静不足 `QUIET_PERIOD` 时忽略。
+    pub(super) fn on_input(&mut self) {
+        if self.sent.is_none()
+            && self
+                .last_sample
+                .is_none_or(|at| at.elapsed() >= PROBE_INTERVAL)
+            && self
+                .last_output
+                .is_none_or(|at| at.elapsed() >= QUIET_PERIOD)
+        {
+            self.sent = Some(Instant::now());
+        }
+    }
+
+    /// 从通道读到输出，返回本次往返时延。
+    pub(super) fn on_output(&mut self) -> Option<Duration> {
+        self.last_output = Some(Instant::now());
+        let rtt = self.sent.take()?.elapsed();
+        if rtt > PROBE_TIMEOUT {
+            return None;
+        }
+        self.last_sample = Some(Instant::now());
+        Some(rtt)
+    }
+}
+
+impl SessionManager {
+    /// 会话尚未产生样本或已结束时返回 None。
+    pub fn latency_stats(&self, session_id: &str) -> Option<LatencyStats> {
+        self.metrics
+            .windows
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .filter(|window| !window.is_empty())
+            .map(summarize)
+    }
+
+    /// 记入一次往返时延并推送 `session-metrics`。
+    pub(super) fn record_latency(&self, events: &Events, session_id: &str, rtt: Duration) {
+        let stats = self.metrics.record(session_id, rtt);
+        let _ = events.emit(
+            "session-metrics",
+            SessionMetricsPayload {
+                session_id: session_id.to_string(),
+                last_ms: stats.last_ms,
+                min_ms: stats.min_ms,
+                avg_ms: stats.avg_ms,
+                p95_ms: stats.p95_ms,
+                samples: stats.samples,
+            },
+        );
+    }
+}
+
+fn summarize(window: &VecDeque<Duration>) -> LatencyStats {
+    let mut sorted: Vec<f64> = window.iter().map(|rtt| as_ms(*rtt)).collect();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    // 最近秩法：取不小于 95% 样本的那个值
+    let rank = (sorted.len() * 95).div_ceil(100).max(1);
+    LatencyStats {
+        last_ms: window.back().map(|rtt| as_ms(*rtt)).unwrap_or_default(),
+        min_ms: sorted[0],
+        avg_ms: round_ms(sorted.iter().sum::<f64>() / sorted.len() as f64),
+        p95_ms: sorted[rank - 1],
+        samples: sorted.len() as u32,
+    }
+}
+
+fn as_ms(rtt: Duration) -> f64 {
+    round_ms(rtt.as_secs_f64() * 1000.0)
+}
+
+/// 保留一位小数，局域网内的亚毫秒时延也能看出差别。
+fn round_ms(ms: f64) -> f64 {
+    (ms * 10.0).round() / 10.0
+}
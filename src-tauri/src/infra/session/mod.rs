@@ -8,19 +8,87 @@ use std::time::Duration;
 use anyhow::{anyhow, Context, Result};
 use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
 use ssh2::{
-    Channel as SshChannel, Error as SshError, ErrorCode, ExtendedData, Session as SshSession,
+    Channel as SshChannel, Error as SshError, ErrorCode, ExtendedData, KeyboardInteractivePrompt,
+    Prompt, Session as SshSession,
 };
-use tauri::{AppHandle, Emitter};
 use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
-use tokio::process::{ChildStdin, Command};
+use tokio::process::{Child, ChildStdin};
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
-use crate::domain::models::{Connection, Protocol};
+use crate::domain::models::{
+    is_env_name, AgentForwarding, Connection, Protocol, SessionErrorCode, SessionRecord,
+    SessionState, SessionStateReason, ShellProfile,
+};
+use crate::domain::services::session_service::SessionHistoryRepository;
+
+pub mod agent;
+pub mod ansi;
+pub mod broadcast;
+#[cfg(unix)]
+pub mod daemon;
+pub mod events;
+pub mod exec;
+pub mod history;
+pub mod live;
+pub mod local_exec;
+pub mod metrics;
+pub mod multi_exec;
+pub mod observer;
+pub mod output;
+pub mod policy;
+pub mod pool;
+pub mod profile;
+pub mod screen;
+pub mod script;
+pub mod search;
+pub mod shell_integration;
+mod startup;
+pub mod state;
+pub mod transcript;
+pub mod transfer;
+pub mod trigger;
+pub mod trzsz;
+pub mod zmodem;
+
+use agent::{AgentDecision, AgentRelay};
+use broadcast::BroadcastGroups;
+use events::Events;
+use exec::ExecJobs;
+use history::{SessionExit, SessionHistory};
+use metrics::{EchoProbe, SessionMetrics};
+use observer::Observers;
+use output::OutputBus;
+use policy::SessionPolicies;
+use pool::{ConnectionPool, SshLease};
+use profile::ShellProfiles;
+use screen::ScreenModels;
+use shell_integration::ShellIntegration;
+use state::{SessionFailure, SessionStatePayload, SessionStateStore, StateChange};
+use transcript::TranscriptLogger;
+use transfer::{ActiveTransfers, TransferAction, TransferControl, TransferDetector};
+use trigger::TriggerRules;
 
 #[derive(Clone)]
 pub struct SessionManager {
     sessions: Arc<Mutex<HashMap<String, SessionHandle>>>,
+    states: SessionStateStore,
+    history: SessionHistory,
+    broadcast: BroadcastGroups,
+    jobs: ExecJobs,
+    output: OutputBus,
+    triggers: TriggerRules,
+    transcripts: TranscriptLogger,
+    metrics: SessionMetrics,
+    shell: ShellIntegration,
+    screens: ScreenModels,
+    transfers: ActiveTransfers,
+    pool: ConnectionPool,
+    observers: Observers,
+    policies: SessionPolicies,
+    profiles: ShellProfiles,
+    #[cfg(unix)]
+    daemon: daemon::LocalDaemon,
 }
 
 #[derive(Clone)]
@@ -30,15 +98,28 @@ pub struct SessionSecret {
 
 struct SessionHandle {
     kind: SessionKind,
+    events: Events,
 }
 
+/// 输入端可复制：写入前从会话表中取出，写入期间不占用会话表的锁。
+#[derive(Clone)]
 enum SessionKind {
-    Local { stdin: ChildStdin },
+    Local { stdin: LocalStdin },
     Ssh2 { tx: Sender<SessionInput> },
 }
 
+/// 本地 shell 的输入端：应用直接启动的子进程，或由本地会话守护进程托管。
+#[derive(Clone)]
+enum LocalStdin {
+    Pipe(Arc<Mutex<ChildStdin>>),
+    #[cfg(unix)]
+    Daemon(Arc<Mutex<tokio::net::unix::OwnedWriteHalf>>),
+}
+
 enum SessionInput {
     Data(String),
+    Transfer(TransferAction),
+    Agent(AgentDecision),
     Close,
 }
 
@@ -49,192 +130,477 @@ pub struct SessionEventPayload {
     pub data: String,
 }
 
+#[derive(serde::Serialize, Clone)]
+pub struct SessionClosedPayload {
+    pub session_id: String,
+    pub exit_code: Option<i32>,
+    pub exit_signal: Option<String>,
+    pub reason: Option<SessionStateReason>,
+    pub message: Option<String>,
+}
+
+impl Default for SessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SessionManager {
     pub fn new() -> Self {
         Self {
             sessions: Arc::new(Mutex::new(HashMap::new())),
+            states: SessionStateStore::default(),
+            history: SessionHistory::default(),
+            broadcast: BroadcastGroups::default(),
+            jobs: ExecJobs::default(),
+            output: OutputBus::default(),
+            triggers: TriggerRules::default(),
+            transcripts: TranscriptLogger::default(),
+            metrics: SessionMetrics::default(),
+            shell: ShellIntegration::default(),
+            screens: ScreenModels::default(),
+            transfers: ActiveTransfers::default(),
+            pool: ConnectionPool::default(),
+            observers: Observers::default(),
+            policies: SessionPolicies::default(),
+            profiles: ShellProfiles::default(),
+            #[cfg(unix)]
+            daemon: daemon::LocalDaemon::default(),
         }
     }
 
+    /// 加载会话日志配置，`data_dir` 为应用数据目录。
+    pub fn init_transcripts(&self, data_dir: &std::path::Path) -> Result<()> {
+        self.transcripts.init(
+            data_dir.join("transcript.json"),
+            data_dir.join("transcripts"),
+        )
+    }
+
+    /// 加载会话超时策略，`data_dir` 为应用数据目录。
+    pub fn init_session_policy(&self, data_dir: &std::path::Path) -> Result<()> {
+        self.policies.init(data_dir.join("session_policy.json"))
+    }
+
+    /// 加载本地 shell 配置，`data_dir` 为应用数据目录。
+    pub fn init_shell_profiles(&self, data_dir: &std::path::Path) -> Result<()> {
+        self.profiles.init(data_dir.join("shell_profiles.json"))
+    }
+
+    /// 之后结束的会话同时写入 `store`。
+    pub fn set_history_store(&self, store: Arc<dyn SessionHistoryRepository>) {
+        self.history.set_store(store);
+    }
+
+    /// 连接池中最后一个租约释放后保留连接的时长。
+    pub fn set_pool_idle_timeout(&self, timeout: Duration) {
+        self.pool.set_idle_timeout(timeout);
+    }
+
+    /// 连接池当前保持着的 SSH 连接数。
+    pub fn pooled_connections(&self) -> usize {
+        self.pool.open_connections()
+    }
+
+    /// 从连接池租用 SSH 连接，供 SFTP 与端口转发在其上开启通道；释放租约即归还。
+    pub async fn lease_connection(
+        &self,
+        connection: Connection,
+        secret: Option<SessionSecret>,
+    ) -> Result<SshLease> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || pool.acquire(&connection, secret.as_ref(), || {}))
+            .await
+            .map_err(|err| anyhow!("连接线程异常退出: {err}"))?
+    }
+
+    pub fn session_state(&self, session_id: &str) -> Option<SessionStatePayload> {
+        self.states.get(session_id)
+    }
+
+    pub fn session_history(&self) -> Vec<SessionRecord> {
+        self.history.recent()
+    }
+
     pub async fn create_shell_session(
         &self,
-        app_handle: AppHandle,
+        events: Events,
         connection: Option<Connection>,
         secret: Option<SessionSecret>,
     ) -> Result<String> {
         match connection {
             Some(conn) if matches!(conn.protocol, Protocol::Ssh | Protocol::Sftp) => {
-                self.spawn_ssh_session(app_handle, conn, secret).await
+                self.spawn_ssh_session(events, conn, secret).await
             }
-            _ => self.spawn_local_shell(app_handle).await,
+            _ => self.create_local_shell(events, None).await,
         }
     }
 
+    /// 在指定目录启动本地 shell，用于“在同一目录打开新标签”。
+    pub async fn create_local_shell_in(&self, events: Events, cwd: &str) -> Result<String> {
+        let profile = self.default_shell_profile();
+        self.spawn_local_shell(events, &profile, Some(cwd)).await
+    }
+
+    /// `session_id` 也可以是广播组 id，此时写入组内每个成员会话。
+    ///
+    /// 输入端先从会话表中取出再写入：某个成员的写入卡住时，其他会话的输入与关闭不受影响。
     pub async fn send_input(&self, session_id: &str, data: &str) -> Result<()> {
-        let mut sessions = self.sessions.lock().await;
-        let handle = sessions
-            .get_mut(session_id)
-            .ok_or_else(|| anyhow!("session not found"))?;
-        match &mut handle.kind {
-            SessionKind::Local { stdin } => {
-                stdin.write_all(data.as_bytes()).await?;
-                stdin.flush().await?;
-                Ok(())
+        let Some(members) = self.broadcast.members(session_id).await else {
+            let kind = self
+                .sessions
+                .lock()
+                .await
+                .get(session_id)
+                .map(|handle| handle.kind.clone())
+                .ok_or_else(|| anyhow!("session not found"))?;
+            write_input(&kind, data).await?;
+            self.history.add_bytes_out(session_id, data.len());
+            self.policies.touch_input(session_id);
+            return Ok(());
+        };
+
+        let writers: Vec<(String, SessionKind)> = {
+            let sessions = self.sessions.lock().await;
+            members
+                .into_iter()
+                .filter_map(|member| {
+                    let kind = sessions.get(&member)?.kind.clone();
+                    Some((member, kind))
+                })
+                .collect()
+        };
+        let mut failed = Vec::new();
+        for (member, kind) in &writers {
+            match write_input(kind, data).await {
+                Ok(()) => {
+                    self.history.add_bytes_out(member, data.len());
+                    self.policies.touch_input(member);
+                }
+                Err(err) => failed.push(format!("{member}: {err}")),
             }
-            SessionKind::Ssh2 { tx } => tx
-                .send(SessionInput::Data(data.to_string()))
-                .map_err(|err| anyhow!("发送 SSH 输入失败: {err}")),
+        }
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("广播输入部分失败: {}", failed.join("; ")))
         }
     }
 
     pub async fn close_session(&self, session_id: &str) -> Result<()> {
+        self.broadcast.forget_session(session_id).await;
         if let Some(handle) = self.sessions.lock().await.remove(session_id) {
-            if let SessionKind::Ssh2 { tx } = handle.kind {
-                let _ = tx.send(SessionInput::Close);
+            match handle.kind {
+                SessionKind::Ssh2 { tx } => {
+                    // SSH 线程会在关闭通道后自行上报 closed
+                    let _ = tx.send(SessionInput::Close);
+                }
+                SessionKind::Local { stdin } => {
+                    #[cfg(unix)]
+                    if let LocalStdin::Daemon(writer) = stdin {
+                        let _ = daemon::close_daemon_session(&mut *writer.lock().await).await;
+                    }
+                    #[cfg(not(unix))]
+                    drop(stdin);
+                    self.states.transition(
+                        &handle.events,
+                        session_id,
+                        SessionState::Closed,
+                        StateChange::reason(SessionStateReason::UserClosed, "用户主动关闭"),
+                    );
+                }
             }
         }
         Ok(())
     }
 
-    async fn spawn_local_shell(&self, app_handle: AppHandle) -> Result<String> {
-        #[cfg(target_os = "windows")]
-        let mut cmd = Command::new("cmd");
-        #[cfg(target_os = "windows")]
-        cmd.arg("/K");
-
-        #[cfg(not(target_os = "windows"))]
-        let mut cmd = Command::new("/bin/sh");
-        #[cfg(not(target_os = "windows"))]
-        cmd.arg("-i");
+    async fn spawn_local_shell(
+        &self,
+        events: Events,
+        profile: &ShellProfile,
+        cwd: Option<&str>,
+    ) -> Result<String> {
+        #[cfg(unix)]
+        if let Some(target) = self.daemon_target() {
+            return self.spawn_daemon_shell(&target, events, profile, cwd).await;
+        }
 
+        let mut cmd = profile::shell_command(profile, cwd);
         cmd.stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped());
 
-        let mut child = cmd.spawn()?;
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("无法启动 {}", profile.program))?;
         let stdin = child.stdin.take().ok_or_else(|| anyhow!("缺少 stdin"))?;
         let stdout = child.stdout.take().ok_or_else(|| anyhow!("缺少 stdout"))?;
         let stderr = child.stderr.take().ok_or_else(|| anyhow!("缺少 stderr"))?;
         let session_id = format!("session-{}", Uuid::new_v4().simple());
+        self.open_output(&events, &session_id, None, &profile.name, true);
 
-        Self::spawn_async_reader(stdout, session_id.clone(), "stdout", app_handle.clone());
-        Self::spawn_async_reader(stderr, session_id.clone(), "stderr", app_handle.clone());
-
-        let header = SessionEventPayload {
-            session_id: session_id.clone(),
-            stream: "stdout".into(),
-            data: "本地 shell 已启动\r\n".into(),
-        };
-        let _ = app_handle.emit("session-data", header);
+        let stdout_task = self.spawn_async_reader(stdout, session_id.clone(), "stdout");
+        self.spawn_async_reader(stderr, session_id.clone(), "stderr");
+        self.history.start(&session_id, None, profile.name.clone());
+        self.states.transition(
+            &events,
+            &session_id,
+            SessionState::Connected,
+            StateChange::default(),
+        );
 
+        // 先登记再等待退出：进程立即退出时，结束处理也能把会话移出会话表
         self.sessions.lock().await.insert(
             session_id.clone(),
             SessionHandle {
-                kind: SessionKind::Local { stdin },
+                kind: SessionKind::Local {
+                    stdin: LocalStdin::Pipe(Arc::new(Mutex::new(stdin))),
+                },
+                events: events.clone(),
             },
         );
+
+        let manager = self.clone();
+        let exit_session_id = session_id.clone();
+        tokio::spawn(async move {
+            let (state, change, exit) = wait_local_exit(child).await;
+            let _ = stdout_task.await;
+            manager
+                .finish_session(&events, &exit_session_id, state, change, exit)
+                .await;
+        });
+
+        self.publish_output(&session_id, "stdout", "本地 shell 已启动\r\n");
         Ok(session_id)
     }
 
     async fn spawn_ssh_session(
         &self,
-        app_handle: AppHandle,
+        events: Events,
         connection: Connection,
         secret: Option<SessionSecret>,
     ) -> Result<String> {
         let session_id = format!("session-{}", Uuid::new_v4().simple());
+        self.open_output(
+            &events,
+            &session_id,
+            Some(&connection),
+            &connection.name,
+            false,
+        );
         let (tx, rx) = unbounded();
+        self.states.transition(
+            &events,
+            &session_id,
+            SessionState::Connecting,
+            StateChange::default(),
+        );
+        self.history.start(
+            &session_id,
+            Some(connection.id.clone()),
+            connection.name.clone(),
+        );
+        // 先登记再启动 SSH 线程：连接立即失败时，结束处理也能把会话移出会话表
+        self.sessions.lock().await.insert(
+            session_id.clone(),
+            SessionHandle {
+                kind: SessionKind::Ssh2 { tx },
+                events: events.clone(),
+            },
+        );
+
         let connection_clone = connection.clone();
-        let event_handle = app_handle.clone();
         let thread_session_id = session_id.clone();
+        let manager = self.clone();
+        let runtime = tokio::runtime::Handle::current();
         thread::spawn(move || {
-            let result = Self::run_ssh_session(
+            let result = manager.run_ssh_session(
                 connection_clone,
                 thread_session_id.clone(),
-                event_handle.clone(),
+                events.clone(),
                 secret,
                 rx,
             );
-            if let Err(err) = result {
-                let _ = event_handle.emit(
-                    "session-data",
-                    SessionEventPayload {
-                        session_id: thread_session_id.clone(),
-                        stream: "stderr".into(),
-                        data: format!("SSH 会话错误: {err}"),
-                    },
-                );
+            match result {
+                Ok((change, exit)) => {
+                    runtime.block_on(manager.finish_session(
+                        &events,
+                        &thread_session_id,
+                        SessionState::Closed,
+                        change,
+                        exit,
+                    ));
+                }
+                Err(err) => {
+                    manager.publish_output(
+                        &thread_session_id,
+                        "stderr",
+                        &format!("SSH 会话错误: {err}"),
+                    );
+                    runtime.block_on(manager.finish_session(
+                        &events,
+                        &thread_session_id,
+                        SessionState::Failed,
+                        StateChange::failure(SessionFailure::code_of(&err), err.to_string()),
+                        SessionExit::default(),
+                    ));
+                }
             }
-            let _ = event_handle.emit(
-                "session-closed",
-                serde_json::json!({ "session_id": thread_session_id }),
-            );
         });
 
-        let header = SessionEventPayload {
-            session_id: session_id.clone(),
-            stream: "stdout".into(),
-            data: format!(
+        self.publish_output(
+            &session_id,
+            "stdout",
+            &format!(
                 "正在连接 {}@{}:{}\r\n",
                 connection.name, connection.host, connection.port
             ),
-        };
-        let _ = app_handle.emit("session-data", header);
-
-        self.sessions.lock().await.insert(
-            session_id.clone(),
-            SessionHandle {
-                kind: SessionKind::Ssh2 { tx },
-            },
         );
-
         Ok(session_id)
     }
 
+    /// 记录终态、写入会话历史，并经输出总线推送 `session-closed`（排在剩余输出之后）。
+    ///
+    /// 若会话此前已被用户关闭，则沿用已记录的关闭原因，只补充退出码与信号。
+    /// 结束的会话同时移出会话表与所有广播组。
+    async fn finish_session(
+        &self,
+        events: &Events,
+        session_id: &str,
+        state: SessionState,
+        change: StateChange,
+        exit: SessionExit,
+    ) {
+        self.states
+            .transition(events, session_id, state, change.clone());
+        let (reason, message) = match self.states.get(session_id) {
+            Some(current) => (current.reason, current.message),
+            None => (change.reason, change.message),
+        };
+        self.history
+            .finish(session_id, &exit, reason, message.clone());
+        self.metrics.forget(session_id);
+        self.shell.forget(session_id);
+        self.screens.forget(session_id);
+        self.transfers.forget(session_id);
+        self.observers.forget(session_id);
+        self.policies.forget(session_id);
+        self.sessions.lock().await.remove(session_id);
+        self.broadcast.forget_session(session_id).await;
+        let payload = SessionClosedPayload {
+            session_id: session_id.to_string(),
+            exit_code: exit.exit_code,
+            exit_signal: exit.exit_signal,
+            reason,
+            message,
+        };
+        self.output.close(session_id, payload);
+    }
+
     fn run_ssh_session(
+        &self,
         connection: Connection,
         session_id: String,
-        app_handle: AppHandle,
+        events: Events,
         secret: Option<SessionSecret>,
         input_rx: Receiver<SessionInput>,
-    ) -> Result<()> {
-        let addr = format!("{}:{}", connection.host, connection.port);
-        let tcp = TcpStream::connect(&addr).with_context(|| format!("连接 {addr} 失败"))?;
-        tcp.set_nodelay(true).ok();
-        let tcp_control = tcp
-            .try_clone()
-            .context("克隆 TCP 流失败：无法配置非阻塞模式")?;
-
-        let mut session = SshSession::new().context("创建 SSH Session 失败")?;
-        session.set_blocking(true);
-        session.set_tcp_stream(tcp);
-        wait_for_ssh("handshake", || session.handshake())?;
-        Self::emit_stream(&app_handle, &session_id, "stdout", "SSH 握手完成\r\n");
-        authenticate(&mut session, &connection, secret.as_ref()).context("auth")?;
-        Self::emit_stream(&app_handle, &session_id, "stdout", "SSH 认证成功\r\n");
-
-        let mut channel = wait_for_ssh("channel_session", || session.channel_session())?;
-        wait_for_ssh("handle_extended_data", || {
-            channel.handle_extended_data(ExtendedData::Merge)
-        })?;
-        wait_for_ssh("request_pty", || {
-            channel.request_pty("xterm-256color", None, Some((80, 24, 0, 0)))
+    ) -> Result<(StateChange, SessionExit)> {
+        let (lease, channel) = self.pool.open_channel(&connection, secret.as_ref(), || {
+            self.publish_output(&session_id, "stdout", "SSH 握手完成\r\n");
+            self.states.transition(
+                &events,
+                &session_id,
+                SessionState::Authenticating,
+                StateChange::default(),
+            );
         })?;
-        wait_for_ssh("shell", || channel.shell())?;
-        Self::emit_stream(
-            &app_handle,
+        if lease.reused() {
+            self.publish_output(&session_id, "stdout", "复用已建立的 SSH 连接\r\n");
+        } else {
+            self.publish_output(&session_id, "stdout", "SSH 认证成功\r\n");
+        }
+
+        let mut agent = (connection.agent_forwarding != AgentForwarding::Disabled)
+            .then(|| lease.agent_relay())
+            .flatten()
+            .map(|relay| {
+                AgentRelay::register(
+                    &relay,
+                    &session_id,
+                    &events,
+                    &self.output,
+                    connection.agent_forwarding,
+                )
+            });
+        let forward_agent = agent.is_some();
+        let requests = lease.lock_requests();
+        let ShellChannel {
+            mut channel,
+            rejected_env,
+            agent_forwarded,
+        } = start_shell(channel, &connection.startup.env, forward_agent)
+            .map_err(|err| SessionFailure::new(SessionErrorCode::ChannelFailed, err.to_string()))?;
+        drop(requests);
+        self.states.transition(
+            &events,
             &session_id,
-            "stdout",
-            "PTY 与 shell 已建立\r\n",
+            SessionState::Connected,
+            StateChange::default(),
         );
-        // 建立会话后切回非阻塞，方便轮询读写
-        tcp_control
-            .set_nonblocking(true)
-            .context("设置 SSH 套接字为非阻塞失败")?;
-        session.set_blocking(false);
+        self.publish_output(&session_id, "stdout", "PTY 与 shell 已建立\r\n");
+        if forward_agent && !agent_forwarded {
+            agent = None;
+            self.publish_output(&session_id, "stderr", "远端拒绝了 agent 转发请求\r\n");
+        }
+        let mut startup_inputs = startup::StartupInputs {
+            input_rx: &input_rx,
+            deferred: Vec::new(),
+        };
+        if !connection.startup.is_empty() || !rejected_env.is_empty() {
+            let mut emit = |chunk: &str| {
+                self.history.add_bytes_in(&session_id, chunk.len());
+                self.publish_output(&session_id, "stdout", chunk);
+            };
+            if let Err(err) = startup::run_startup_actions(
+                &mut channel,
+                &connection.startup,
+                &rejected_env,
+                &mut startup_inputs,
+                &mut emit,
+            ) {
+                if err.is::<startup::StartupClosed>() {
+                    let _ = close_channel(&mut channel);
+                    return Ok((
+                        StateChange::reason(SessionStateReason::UserClosed, "用户主动关闭"),
+                        SessionExit::default(),
+                    ));
+                }
+                let message = format!("启动动作未完成: {err}\r\n");
+                self.publish_output(&session_id, "stderr", &message);
+            }
+        }
 
-        let mut closed_reason: Option<String> = None;
+        let mut closed_reason: Option<StateChange> = None;
         let mut buffer = [0u8; 4096];
+        let mut probe = EchoProbe::default();
+        let mut detector = TransferDetector::default();
+        // 启动动作期间暂存的输入按到达顺序补发
+        for input in startup_inputs.deferred {
+            match input {
+                SessionInput::Data(data) => {
+                    write_channel(&mut channel, &data).map_err(|err| {
+                        SessionFailure::new(SessionErrorCode::IoError, err.to_string())
+                    })?;
+                    probe.on_input();
+                }
+                SessionInput::Agent(decision) => {
+                    if let Some(agent) = &agent {
+                        agent.decide(decision);
+                    }
+                }
+                SessionInput::Transfer(_) | SessionInput::Close => {}
+            }
+        }
 
         loop {
             let mut read_something = false;
@@ -242,39 +608,87 @@ impl SessionManager {
                 Ok(0) => break,
                 Ok(size) => {
                     read_something = true;
-                    let chunk = String::from_utf8_lossy(&buffer[..size]).to_string();
-                    Self::emit_stream(&app_handle, &session_id, "stdout", &chunk);
+                    if let Some(rtt) = probe.on_output() {
+                        self.record_latency(&events, &session_id, rtt);
+                    }
+                    self.history.add_bytes_in(&session_id, size);
+                    let (text, detected) = detector.scan(&buffer[..size]);
+                    self.publish_bytes(&session_id, &text);
+                    if let Some(detected) = detected {
+                        let mut control = TransferControl::new(&input_rx);
+                        self.run_transfer(
+                            &events,
+                            &session_id,
+                            &mut channel,
+                            &mut control,
+                            detected,
+                        );
+                        if control.close_requested {
+                            let _ = close_channel(&mut channel);
+                            return Ok((
+                                StateChange::reason(SessionStateReason::UserClosed, "用户主动关闭"),
+                                SessionExit::default(),
+                            ));
+                        }
+                    }
                 }
                 Err(err) => {
                     if is_would_block(&err) {
                         // allow write handling below even when没有可读数据
+                        self.publish_bytes(&session_id, &detector.flush());
                     } else {
-                        closed_reason = Some(format!("read error: {err}"));
+                        // 同一连接上的其他通道也无法继续，不再分配给新的会话
+                        lease.invalidate();
+                        closed_reason = Some(StateChange::failure(
+                            SessionErrorCode::IoError,
+                            format!("read error: {err}"),
+                        ));
                         break;
                     }
                 }
             }
 
+            if let Some(agent) = &agent {
+                read_something |= agent.pump();
+            }
+
             match input_rx.try_recv() {
                 Ok(SessionInput::Data(data)) => {
-                    write_channel(&mut channel, &data)?;
+                    write_channel(&mut channel, &data).map_err(|err| {
+                        SessionFailure::new(SessionErrorCode::IoError, err.to_string())
+                    })?;
+                    probe.on_input();
                 }
-                Ok(SessionInput::Close) => {
-                    if let Err(err) = close_channel(&mut channel) {
-                        closed_reason.get_or_insert_with(|| format!("channel close error: {err}"));
+                // 没有进行中的传输，迟到的确认或取消直接忽略
+                Ok(SessionInput::Transfer(_)) => {}
+                Ok(SessionInput::Agent(decision)) => {
+                    if let Some(agent) = &agent {
+                        agent.decide(decision);
                     }
-                    closed_reason.get_or_insert_with(|| "用户主动关闭".into());
-                    return Ok(());
+                }
+                Ok(SessionInput::Close) => {
+                    let _ = close_channel(&mut channel);
+                    return Ok((
+                        StateChange::reason(SessionStateReason::UserClosed, "用户主动关闭"),
+                        SessionExit::default(),
+                    ));
                 }
                 Err(TryRecvError::Disconnected) => {
-                    closed_reason.get_or_insert_with(|| "会话通道已断开".into());
+                    closed_reason.get_or_insert_with(|| {
+                        StateChange::reason(
+                            SessionStateReason::ChannelDisconnected,
+                            "会话通道已断开",
+                        )
+                    });
                     break;
                 }
                 Err(TryRecvError::Empty) => {}
             }
 
             if channel.eof() {
-                closed_reason.get_or_insert_with(|| "远端已关闭连接".into());
+                closed_reason.get_or_insert_with(|| {
+                    StateChange::reason(SessionStateReason::RemoteClosed, "远端已关闭连接")
+                });
                 break;
             }
             if !read_something {
@@ -283,34 +697,70 @@ impl SessionManager {
         }
 
         if let Err(err) = close_channel(&mut channel) {
-            closed_reason.get_or_insert_with(|| format!("channel close error: {err}"));
+            closed_reason.get_or_insert_with(|| {
+                StateChange::failure(
+                    SessionErrorCode::ChannelFailed,
+                    format!("channel close error: {err}"),
+                )
+            });
         }
-        if let Some(reason) = closed_reason {
-            let message = format!("SSH 会话结束: {reason}\r\n");
-            Self::emit_stream(&app_handle, &session_id, "stderr", &message);
+        let exit = read_exit_status(&channel);
+        let change = closed_reason.unwrap_or_else(|| {
+            StateChange::reason(SessionStateReason::RemoteClosed, "远端已关闭连接")
+        });
+        if let Some(reason) = &change.message {
+            let message = format!("SSH 会话结束: {reason}{}\r\n", describe_exit(&exit));
+            self.publish_output(&session_id, "stderr", &message);
         }
-        Ok(())
+        Ok((change, exit))
     }
 
-    fn emit_stream(app_handle: &AppHandle, session_id: &str, stream: &str, data: &str) {
-        if data.is_empty() {
-            return;
-        }
-        let payload = SessionEventPayload {
-            session_id: session_id.to_string(),
-            stream: stream.to_string(),
-            data: data.to_string(),
+    /// 为新会话建立输出总线并挂上默认订阅者：前端事件、触发规则、会话日志与超时策略。
+    fn open_output(
+        &self,
+        events: &Events,
+        session_id: &str,
+        connection: Option<&Connection>,
+        title: &str,
+        line_mode: bool,
+    ) {
+        self.output.open(session_id);
+        let subscribe = || {
+            self.output
+                .subscribe(session_id)
+                .expect("output bus was just opened")
         };
-        let _ = app_handle.emit("session-data", payload);
+        output::spawn_event_emitter(
+            events.clone(),
+            session_id.to_string(),
+            subscribe(),
+            line_mode,
+        );
+        self.watch_triggers(events, session_id, connection, subscribe());
+        self.watch_shell_integration(events, session_id, connection, subscribe());
+        self.transcripts.record(session_id, title, subscribe());
+        self.screens.watch(session_id, subscribe(), line_mode);
+        self.watch_policy(events, session_id, connection, subscribe());
+    }
+
+    fn publish_output(&self, session_id: &str, stream: &'static str, data: &str) {
+        self.output.publish(session_id, stream, data);
+    }
+
+    fn publish_bytes(&self, session_id: &str, data: &[u8]) {
+        if !data.is_empty() {
+            self.publish_output(session_id, "stdout", &String::from_utf8_lossy(data));
+        }
     }
 
     fn spawn_async_reader(
+        &self,
         stream_handle: impl AsyncRead + Unpin + Send + 'static,
         session_id: String,
-        stream: &str,
-        app_handle: AppHandle,
-    ) {
-        let stream_name = stream.to_string();
+        stream: &'static str,
+    ) -> tokio::task::JoinHandle<()> {
+        let output = self.output.clone();
+        let history = self.history.clone();
         tokio::spawn(async move {
             let mut reader = BufReader::new(stream_handle);
             let mut line = String::new();
@@ -318,22 +768,34 @@ impl SessionManager {
                 line.clear();
                 match reader.read_line(&mut line).await {
                     Ok(0) => break,
-                    Ok(_) => {
-                        let payload = SessionEventPayload {
-                            session_id: session_id.clone(),
-                            stream: stream_name.clone(),
-                            data: line.trim_end_matches(['\r', '\n']).to_string(),
-                        };
-                        let _ = app_handle.emit("session-data", payload);
+                    Ok(size) => {
+                        history.add_bytes_in(&session_id, size);
+                        output.publish(&session_id, stream, &line);
                     }
                     Err(_) => break,
                 }
             }
-            let _ = app_handle.emit(
-                "session-closed",
-                serde_json::json!({ "session_id": session_id }),
-            );
-        });
+        })
+    }
+}
+
+async fn write_input(kind: &SessionKind, data: &str) -> Result<()> {
+    match kind {
+        SessionKind::Local {
+            stdin: LocalStdin::Pipe(stdin),
+        } => {
+            let mut stdin = stdin.lock().await;
+            stdin.write_all(data.as_bytes()).await?;
+            stdin.flush().await?;
+            Ok(())
+        }
+        #[cfg(unix)]
+        SessionKind::Local {
+            stdin: LocalStdin::Daemon(writer),
+        } => daemon::write_daemon_input(&mut *writer.lock().await, data).await,
+        SessionKind::Ssh2 { tx } => tx
+            .send(SessionInput::Data(data.to_string()))
+            .map_err(|err| anyhow!("发送 SSH 输入失败: {err}")),
     }
 }
 
@@ -394,25 +856,207 @@ fn is_session_would_block(err: &SshError) -> bool {
     matches!(err.code(), ErrorCode::Session(-37))
 }
 
+/// 读取远端 `exit-status` / `exit-signal`。被信号终止时远端不会发送退出码。
+fn read_exit_status(channel: &SshChannel) -> SessionExit {
+    let exit_signal = channel
+        .exit_signal()
+        .ok()
+        .and_then(|signal| signal.exit_signal);
+    let exit_code = if exit_signal.is_some() {
+        None
+    } else {
+        channel.exit_status().ok()
+    };
+    SessionExit {
+        exit_code,
+        exit_signal,
+    }
+}
+
+fn describe_exit(exit: &SessionExit) -> String {
+    match (&exit.exit_signal, exit.exit_code) {
+        (Some(signal), _) => format!("（信号 {signal}）"),
+        (None, Some(code)) => format!("（退出码 {code}）"),
+        (None, None) => String::new(),
+    }
+}
+
+async fn wait_local_exit(mut child: Child) -> (SessionState, StateChange, SessionExit) {
+    match child.wait().await {
+        Ok(status) => {
+            let exit = SessionExit {
+                exit_code: status.code(),
+                exit_signal: local_exit_signal(&status),
+            };
+            let message = format!("本地 shell 已退出{}", describe_exit(&exit));
+            (
+                SessionState::Closed,
+                StateChange::reason(SessionStateReason::ProcessExited, message),
+                exit,
+            )
+        }
+        Err(err) => (
+            SessionState::Failed,
+            StateChange::failure(
+                SessionErrorCode::IoError,
+                format!("等待本地 shell 退出失败: {err}"),
+            ),
+            SessionExit::default(),
+        ),
+    }
+}
+
+#[cfg(unix)]
+fn local_exit_signal(status: &std::process::ExitStatus) -> Option<String> {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.signal().map(|signal| {
+        let name = match signal {
+            1 => "HUP",
+            2 => "INT",
+            3 => "QUIT",
+            6 => "ABRT",
+            9 => "KILL",
+            11 => "SEGV",
+            13 => "PIPE",
+            15 => "TERM",
+            _ => return signal.to_string(),
+        };
+        name.to_string()
+    })
+}
+
+#[cfg(not(unix))]
+fn local_exit_signal(_status: &std::process::ExitStatus) -> Option<String> {
+    None
+}
+
+struct ShellChannel {
+    channel: SshChannel,
+    /// 被 sshd 拒绝的环境变量，需由调用方改用 export。
+    rejected_env: Vec<(String, String)>,
+    /// 请求了 agent 转发且远端已接受。
+    agent_forwarded: bool,
+}
+
+/// 在新开的 session 通道上申请 PTY 并启动 shell；`forward_agent` 为 true 时在启动 shell 前请求 agent 转发。
+fn start_shell(
+    mut channel: SshChannel,
+    env: &HashMap<String, String>,
+    forward_agent: bool,
+) -> Result<ShellChannel> {
+    let mut rejected = Vec::new();
+    for (key, value) in env {
+        // 不合法的变量名不发送，交给启动动作统一报错
+        if !is_env_name(key) || wait_for_ssh("setenv", || channel.setenv(key, value)).is_err() {
+            rejected.push((key.clone(), value.clone()));
+        }
+    }
+    wait_for_ssh("handle_extended_data", || {
+        channel.handle_extended_data(ExtendedData::Merge)
+    })?;
+    wait_for_ssh("request_pty", || {
+        channel.request_pty(
+            "xterm-256color",
+            None,
+            Some((
+                u32::from(screen::SCREEN_COLS),
+                u32::from(screen::SCREEN_ROWS),
+                0,
+                0,
+            )),
+        )
+    })?;
+    // 远端据此在 shell 中设置 SSH_AUTH_SOCK
+    let agent_forwarded = forward_agent
+        && wait_for_ssh("request_auth_agent_forwarding", || {
+            channel.request_auth_agent_forwarding()
+        })
+        .is_ok();
+    wait_for_ssh("shell", || channel.shell())?;
+    Ok(ShellChannel {
+        channel,
+        rejected_env: rejected,
+        agent_forwarded,
+    })
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
 fn close_channel(channel: &mut SshChannel) -> Result<()> {
     wait_for_ssh("channel.close", || channel.close())?;
     wait_for_ssh("channel.wait_close", || channel.wait_close())?;
     Ok(())
 }
 
+/// 建立 TCP 连接、完成握手与认证，返回阻塞模式的 Session 及用于切换非阻塞的 TCP 句柄。
+///
+/// 会话与命令应经连接池租用，而不是直接调用这里。
+///
+/// `on_handshake` 在握手完成、开始认证之前调用。
+fn connect_ssh(
+    connection: &Connection,
+    secret: Option<&SessionSecret>,
+    on_handshake: impl FnOnce(),
+) -> Result<(SshSession, TcpStream)> {
+    let addr = format!("{}:{}", connection.host, connection.port);
+    let tcp = TcpStream::connect(&addr).map_err(|err| {
+        SessionFailure::new(
+            SessionErrorCode::ConnectFailed,
+            format!("连接 {addr} 失败: {err}"),
+        )
+    })?;
+    tcp.set_nodelay(true).ok();
+    let tcp_control = tcp
+        .try_clone()
+        .context("克隆 TCP 流失败：无法配置非阻塞模式")?;
+
+    let mut session = SshSession::new().context("创建 SSH Session 失败")?;
+    session.set_blocking(true);
+    session.set_tcp_stream(tcp);
+    wait_for_ssh("handshake", || session.handshake())
+        .map_err(|err| SessionFailure::new(SessionErrorCode::HandshakeFailed, err.to_string()))?;
+    on_handshake();
+    authenticate(&mut session, connection, secret)
+        .map_err(|err| SessionFailure::new(SessionErrorCode::AuthFailed, err.to_string()))?;
+    Ok((session, tcp_control))
+}
+
+struct PasswordPrompt<'a>(&'a str);
+
+impl KeyboardInteractivePrompt for PasswordPrompt<'_> {
+    fn prompt<'b>(
+        &mut self,
+        _username: &str,
+        _instructions: &str,
+        prompts: &[Prompt<'b>],
+    ) -> Vec<String> {
+        prompts.iter().map(|_| self.0.to_string()).collect()
+    }
+}
+
 fn authenticate(
     session: &mut SshSession,
     connection: &Connection,
     secret: Option<&SessionSecret>,
 ) -> Result<()> {
-    if let Some(secret) = secret {
-        if let Some(password) = &secret.password {
+    if let Some(password) = secret.and_then(|secret| secret.password.as_deref()) {
+        let methods = session
+            .auth_methods(&connection.username)
+            .unwrap_or_default()
+            .to_string();
+        // 部分服务器（常见于网络设备）只开放 keyboard-interactive，此时用同一密码回答全部提示
+        let result = if !methods.contains("password") && methods.contains("keyboard-interactive") {
             session
-                .userauth_password(&connection.username, password)
-                .map_err(|err| anyhow!("密码认证失败: {err}"))?;
-            if session.authenticated() {
-                return Ok(());
-            }
+                .userauth_keyboard_interactive(&connection.username, &mut PasswordPrompt(password))
+        } else {
+            session.userauth_password(&connection.username, password)
+        };
+        result.map_err(|err| anyhow!("密码认证失败: {err}"))?;
+        if session.authenticated() {
+            return Ok(());
         }
     }
 
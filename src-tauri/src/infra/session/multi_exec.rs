@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use tokio::sync::Semaphore;
+
+use crate::domain::models::{Connection, HostExecResult, HostExecStatus, MultiExecSummary};
+
+use super::events::Events;
+use super::exec::ExecRequest;
+use super::{SessionManager, SessionSecret};
+
+const DEFAULT_CONCURRENCY: usize = 8;
+
+#[derive(Clone, Debug, Default)]
+pub struct MultiExecOptions {
+    /// 同时执行的主机数，0 表示使用默认值。
+    pub concurrency: usize,
+    /// 任一主机失败后不再启动新主机，并取消仍在执行的主机。
+    pub stop_on_failure: bool,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct ExecOutputPayload {
+    pub job_id: String,
+    pub connection_id: String,
+    pub stream: String,
+    pub data: String,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct ExecHostResultPayload {
+    pub job_id: String,
+    pub result: HostExecResult,
+}
+
+impl SessionManager {
+    /// 在多台主机上并发执行同一条命令，输出以 `exec-output` 事件按主机推送，
+    /// 每台主机结束时推送 `exec-host-result`，全部结束后推送 `exec-summary`。
+    pub async fn exec_many(
+        &self,
+        events: Events,
+        job_id: String,
+        targets: Vec<Connection>,
+        mut secrets: HashMap<String, SessionSecret>,
+        request: ExecRequest,
+        options: MultiExecOptions,
+    ) -> MultiExecSummary {
+        let started = Instant::now();
+        let concurrency = if options.concurrency == 0 {
+            DEFAULT_CONCURRENCY
+        } else {
+            options.concurrency
+        };
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+        // 整个任务共用一个取消标记：stop_on_failure 与 cancel_command 都通过它终止其余主机
+        let abort = self.jobs.register(&job_id);
+
+        let mut tasks = Vec::with_capacity(targets.len());
+        for connection in targets {
+            let manager = self.clone();
+            let events = events.clone();
+            let job_id = job_id.clone();
+            let semaphore = semaphore.clone();
+            let abort = abort.clone();
+            let secret = secrets.remove(&connection.id);
+            let mut request = request.clone();
+            request.cancel = Some(abort.clone());
+            let stop_on_failure = options.stop_on_failure;
+            tasks.push(tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await.ok();
+                let result = if abort.is_cancelled() {
+                    skipped(&connection)
+                } else {
+                    run_host(&manager, &events, &job_id, connection, secret, request).await
+                };
+                if stop_on_failure && result.status != HostExecStatus::Succeeded {
+                    abort.cancel();
+                }
+                let _ = events.emit(
+                    "exec-host-result",
+                    ExecHostResultPayload {
+                        job_id,
+                        result: result.clone(),
+                    },
+                );
+                result
+            }));
+        }
+
+        let mut results = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            if let Ok(result) = task.await {
+                results.push(result);
+            }
+        }
+
+        let count = |status: HostExecStatus| results.iter().filter(|r| r.status == status).count();
+        let succeeded = count(HostExecStatus::Succeeded);
+        let skipped = count(HostExecStatus::Skipped);
+        let summary = MultiExecSummary {
+            job_id,
+            succeeded,
+            failed: results.len() - succeeded - skipped,
+            skipped,
+            duration_ms: started.elapsed().as_millis() as u64,
+            results,
+        };
+        self.jobs.finish(&summary.job_id);
+        let _ = events.emit("exec-summary", summary.clone());
+        summary
+    }
+}
+
+async fn run_host(
+    manager: &SessionManager,
+    events: &Events,
+    job_id: &str,
+    connection: Connection,
+    secret: Option<SessionSecret>,
+    request: ExecRequest,
+) -> HostExecResult {
+    let started = Instant::now();
+    let connection_id = connection.id.clone();
+    let connection_name = connection.name.clone();
+    let output_handle = events.clone();
+    let output_job_id = job_id.to_string();
+    let output_connection_id = connection_id.clone();
+    let outcome = manager
+        .exec_remote_streaming(connection, secret, request, move |stream, data| {
+            let _ = output_handle.emit(
+                "exec-output",
+                ExecOutputPayload {
+                    job_id: output_job_id.clone(),
+                    connection_id: output_connection_id.clone(),
+                    stream: stream.as_str().to_string(),
+                    data: String::from_utf8_lossy(data).to_string(),
+                },
+            );
+        })
+        .await;
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    match outcome {
+        Ok(output) => {
+            let status = if output.cancelled {
+                HostExecStatus::Cancelled
+            } else if output.timed_out {
+                HostExecStatus::TimedOut
+            } else if output.exit_code == Some(0) {
+                HostExecStatus::Succeeded
+            } else {
+                HostExecStatus::Failed
+            };
+            HostExecResult {
+                connection_id,
+                connection_name,
+                status,
+                exit_code: output.exit_code,
+                exit_signal: output.exit_signal,
+                duration_ms,
+                error: None,
+            }
+        }
+        Err(err) => HostExecResult {
+            connection_id,
+            connection_name,
+            status: HostExecStatus::Failed,
+            exit_code: None,
+            exit_signal: None,
+            duration_ms,
+            error: Some(err.to_string()),
+        },
+    }
+}
+
+fn skipped(connection: &Connection) -> HostExecResult {
+    HostExecResult {
+        connection_id: connection.id.clone(),
+        connection_name: connection.name.clone(),
+        status: HostExecStatus::Skipped,
+        exit_code: None,
+        exit_signal: None,
+        duration_ms: 0,
+        error: None,
+    }
+}
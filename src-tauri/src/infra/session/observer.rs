@@ -0,0 +1,191 @@
+//! 只读观察者：其他窗口附加到运行中的会话，经各自窗口定向的事件收到同样的输出，但不能输入。
+//!
+//! 观察者以窗口标签区分；从观察窗口发来的输入、关闭等操作在进入会话之前即被拒绝。
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::domain::models::{AutomationScript, ObserverAttachment};
+
+use super::events::Events;
+use super::{output, SessionKind, SessionManager};
+
+/// 观察者数量变化时推送给会话所属窗口（事件 `session-observers`），便于提示“有人正在查看”。
+/// 与 `ObserverAttachment` 一样使用驼峰字段。
+#[derive(serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionObserversPayload {
+    pub session_id: String,
+    pub windows: Vec<String>,
+}
+
+struct Attachment {
+    info: ObserverAttachment,
+    emitter: JoinHandle<()>,
+}
+
+#[derive(Clone, Default)]
+pub struct Observers {
+    attachments: Arc<Mutex<HashMap<String, Attachment>>>,
+}
+
+impl Observers {
+    fn is_observing(&self, window: &str, session_id: &str) -> bool {
+        self.attachments.lock().unwrap().values().any(|attachment| {
+            attachment.info.window == window && attachment.info.session_id == session_id
+        })
+    }
+
+    fn windows_of(&self, session_id: &str) -> Vec<String> {
+        let mut windows: Vec<String> = self
+            .attachments
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|attachment| attachment.info.session_id == session_id)
+            .map(|attachment| attachment.info.window.clone())
+            .collect();
+        windows.sort();
+        windows.dedup();
+        windows
+    }
+
+    /// 会话结束时清理；输出推送任务会在收到 `session-closed` 后自行退出。
+    pub(super) fn forget(&self, session_id: &str) {
+        self.attachments
+            .lock()
+            .unwrap()
+            .retain(|_, attachment| attachment.info.session_id != session_id);
+    }
+}
+
+impl SessionManager {
+    /// 以只读方式把 `window` 附加到会话：`events` 应只推送给该窗口（见 [`Events::for_target`]），
+    /// 之后的 `session-data` / `session-closed` 经它送达，返回值附带当前屏幕快照。
+    pub async fn attach_observer(
+        &self,
+        session_id: &str,
+        window: &str,
+        events: Events,
+    ) -> Result<ObserverAttachment> {
+        let sessions = self.sessions.lock().await;
+        let handle = sessions
+            .get(session_id)
+            .ok_or_else(|| anyhow!("session not found"))?;
+        let line_mode = matches!(handle.kind, SessionKind::Local { .. });
+        // 先订阅再取快照：快照之后的输出不会遗漏
+        let output = self
+            .output
+            .subscribe(session_id)
+            .ok_or_else(|| anyhow!("会话已结束"))?;
+        let emitter =
+            output::spawn_event_emitter(events, session_id.to_string(), output, line_mode);
+        let info = ObserverAttachment {
+            attachment_id: format!("observer-{}", Uuid::new_v4().simple()),
+            session_id: session_id.to_string(),
+            window: window.to_string(),
+            attached_at: Utc::now().to_rfc3339(),
+            snapshot: None,
+        };
+        self.observers.attachments.lock().unwrap().insert(
+            info.attachment_id.clone(),
+            Attachment {
+                info: info.clone(),
+                emitter,
+            },
+        );
+        self.emit_observers(&handle.events, session_id);
+        Ok(ObserverAttachment {
+            snapshot: self.screen_snapshot(session_id),
+            ..info
+        })
+    }
+
+    /// 只有附加所在的窗口或会话所属窗口可以解除附加。
+    pub async fn detach_observer(&self, window: &str, attachment_id: &str) -> Result<()> {
+        let sessions = self.sessions.lock().await;
+        let attachment = {
+            let mut attachments = self.observers.attachments.lock().unwrap();
+            let info = &attachments
+                .get(attachment_id)
+                .ok_or_else(|| anyhow!("观察者不存在"))?
+                .info;
+            let owner = sessions
+                .get(&info.session_id)
+                .and_then(|handle| handle.events.target());
+            if info.window != window && owner != Some(window) {
+                bail!("只能解除本窗口或本窗口会话上的观察者");
+            }
+            attachments.remove(attachment_id).unwrap()
+        };
+        attachment.emitter.abort();
+        let session_id = attachment.info.session_id;
+        if let Some(handle) = sessions.get(&session_id) {
+            self.emit_observers(&handle.events, &session_id);
+        }
+        Ok(())
+    }
+
+    pub fn list_observers(&self, session_id: &str) -> Vec<ObserverAttachment> {
+        let mut observers: Vec<ObserverAttachment> = self
+            .observers
+            .attachments
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|attachment| attachment.info.session_id == session_id)
+            .map(|attachment| attachment.info.clone())
+            .collect();
+        observers.sort_by(|a, b| a.attached_at.cmp(&b.attached_at));
+        observers
+    }
+
+    /// 来自 `window` 的写操作是否允许；`session_id` 为广播组 id 时检查组内每个成员。
+    pub async fn ensure_writable(&self, window: &str, session_id: &str) -> Result<()> {
+        let targets = self
+            .broadcast
+            .members(session_id)
+            .await
+            .unwrap_or_else(|| vec![session_id.to_string()]);
+        if targets
+            .iter()
+            .any(|target| self.observers.is_observing(window, target))
+        {
+            bail!("观察者为只读模式，不能操作该会话");
+        }
+        Ok(())
+    }
+
+    /// 带来源窗口的输入：观察窗口发来的输入一律拒绝。
+    pub async fn send_input_from(&self, window: &str, session_id: &str, data: &str) -> Result<()> {
+        self.ensure_writable(window, session_id).await?;
+        self.send_input(session_id, data).await
+    }
+
+    /// 带来源窗口的脚本运行：脚本会向会话写入输入，观察窗口同样不能发起。
+    pub async fn run_script_from(
+        &self,
+        window: &str,
+        events: Events,
+        session_id: &str,
+        script: AutomationScript,
+    ) -> Result<String> {
+        self.ensure_writable(window, session_id).await?;
+        self.run_script(events, session_id, script).await
+    }
+
+    fn emit_observers(&self, events: &Events, session_id: &str) {
+        let _ = events.emit(
+            "session-observers",
+            SessionObserversPayload {
+                session_id: session_id.to_string(),
+                windows: self.observers.windows_of(session_id),
+            },
+        );
+    }
+}
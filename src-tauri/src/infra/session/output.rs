@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+use super::events::Events;
+use super::{SessionClosedPayload, SessionEventPayload};
+
+/// 每个会话缓冲的输出事件数，消费者落后超过该数量时会丢失最早的事件。
+const BUS_CAPACITY: usize = 1024;
+/// 每个会话保留的最近输出字节数，供晚于会话启动的订阅方补上此前的输出。
+const BACKLOG_BYTES: usize = 16 * 1024;
+
+#[derive(Clone)]
+pub enum OutputEvent {
+    Data {
+        stream: &'static str,
+        data: Arc<str>,
+    },
+    /// 会话已结束，这是通道中的最后一个事件。
+    Closed(SessionClosedPayload),
+}
+
+/// 按会话划分的输出总线：会话读取线程只负责发布，前端推送、触发规则、日志、脚本等各自订阅。
+#[derive(Clone, Default)]
+pub struct OutputBus {
+    channels: Arc<Mutex<HashMap<String, Channel>>>,
+}
+
+struct Channel {
+    tx: broadcast::Sender<OutputEvent>,
+    /// 最近 [`BACKLOG_BYTES`] 字节的输出。
+    backlog: String,
+}
+
+impl OutputBus {
+    /// 须在会话产生输出之前调用，之后订阅的消费者只能收到订阅之后的事件
+    /// （需要此前输出的消费者使用 [`Self::subscribe_with_backlog`]）。
+    pub fn open(&self, session_id: &str) {
+        let (tx, _) = broadcast::channel(BUS_CAPACITY);
+        self.channels.lock().unwrap().insert(
+            session_id.to_string(),
+            Channel {
+                tx,
+                backlog: String::new(),
+            },
+        );
+    }
+
+    /// 会话不存在或已结束时返回 None。
+    pub fn subscribe(&self, session_id: &str) -> Option<broadcast::Receiver<OutputEvent>> {
+        self.channels
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .map(|channel| channel.tx.subscribe())
+    }
+
+    /// 订阅并取出此前的最近输出；两者在同一把锁下完成，补回的内容与之后收到的事件首尾相接。
+    pub fn subscribe_with_backlog(
+        &self,
+        session_id: &str,
+    ) -> Option<(String, broadcast::Receiver<OutputEvent>)> {
+        self.channels
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .map(|channel| (channel.backlog.clone(), channel.tx.subscribe()))
+    }
+
+    pub fn publish(&self, session_id: &str, stream: &'static str, data: &str) {
+        if data.is_empty() {
+            return;
+        }
+        if let Some(channel) = self.channels.lock().unwrap().get_mut(session_id) {
+            channel.backlog.push_str(data);
+            if channel.backlog.len() > BACKLOG_BYTES {
+                let mut cut = channel.backlog.len() - BACKLOG_BYTES;
+                while !channel.backlog.is_char_boundary(cut) {
+                    cut += 1;
+                }
+                channel.backlog.drain(..cut);
+            }
+            let _ = channel.tx.send(OutputEvent::Data {
+                stream,
+                data: Arc::from(data),
+            });
+        }
+    }
+
+    /// 发布结束事件并关闭通道，订阅方处理完剩余事件后 `recv` 返回 `RecvError::Closed`。
+    pub fn close(&self, session_id: &str, payload: SessionClosedPayload) {
+        if let Some(channel) = self.channels.lock().unwrap().remove(session_id) {
+            let _ = channel.tx.send(OutputEvent::Closed(payload));
+        }
+    }
+}
+
+/// 把总线事件转成 `session-data` / `session-closed` 推给前端。
+///
+/// 本地 shell 按行读取，`line_mode` 为 true 时去掉行尾换行，与前端逐行写入的约定一致。
+pub fn spawn_event_emitter(
+    events: Events,
+    session_id: String,
+    mut output: broadcast::Receiver<OutputEvent>,
+    line_mode: bool,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match output.recv().await {
+                Ok(OutputEvent::Data { stream, data }) => {
+                    let data = if line_mode {
+                        data.trim_end_matches(['\r', '\n'])
+                    } else {
+                        &data
+                    };
+                    let payload = SessionEventPayload {
+                        session_id: session_id.clone(),
+                        stream: stream.to_string(),
+                        data: data.to_string(),
+                    };
+                    let _ = events.emit("session-data", payload);
+                }
+                Ok(OutputEvent::Closed(payload)) => {
+                    let _ = events.emit("session-closed", payload);
+                    break;
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(target: "session", %session_id, skipped, "前端输出推送落后，已丢弃部分输出");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
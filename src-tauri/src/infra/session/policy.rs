@@ -0,0 +1,260 @@
+//! 会话超时策略：空闲超时与最长存活时长，按 连接 > 分组 > 全局 逐项解析。
+//!
+//! 每个会话一个监视任务：订阅输出总线记录最近一次输出，输入时间由 `send_input` 记录。
+//! 距离关闭不足 `warning_secs` 时推送 `session-policy-warning`，到期后以
+//! `IdleTimeout` / `MaxDuration` 为原因关闭会话。策略在每次检查时重新解析，修改配置对运行中的会话同样生效。
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::domain::models::{
+    Connection, SessionPolicy, SessionPolicySettings, SessionState, SessionStateReason,
+};
+
+use super::events::Events;
+use super::output::OutputEvent;
+use super::state::StateChange;
+use super::SessionManager;
+
+/// 检查间隔，决定了超时关闭的精度。
+const POLICY_TICK: Duration = Duration::from_millis(250);
+
+/// 即将按策略关闭会话时推送（事件 `session-policy-warning`）；有新的输入或输出后空闲计时重新开始。
+#[derive(serde::Serialize, Clone)]
+pub struct SessionPolicyWarningPayload {
+    pub session_id: String,
+    /// `idle_timeout` 或 `max_duration`。
+    pub reason: SessionStateReason,
+    pub seconds_left: u64,
+}
+
+#[derive(Clone, Default)]
+pub struct SessionPolicies {
+    inner: Arc<Mutex<PolicyInner>>,
+}
+
+#[derive(Default)]
+struct PolicyInner {
+    settings: SessionPolicySettings,
+    settings_path: Option<PathBuf>,
+    last_input: HashMap<String, Instant>,
+}
+
+/// 解析后对单个会话生效的限制，`None` 表示不限制。
+struct EffectivePolicy {
+    idle_timeout: Option<Duration>,
+    max_duration: Option<Duration>,
+    warning: Duration,
+}
+
+impl EffectivePolicy {
+    /// 最先到期的限制及其到期时刻。
+    fn next_deadline(
+        &self,
+        started: Instant,
+        last_activity: Instant,
+    ) -> Option<(SessionStateReason, Instant, Duration)> {
+        let idle = self.idle_timeout.map(|limit| {
+            (
+                SessionStateReason::IdleTimeout,
+                last_activity + limit,
+                limit,
+            )
+        });
+        let max = self
+            .max_duration
+            .map(|limit| (SessionStateReason::MaxDuration, started + limit, limit));
+        match (idle, max) {
+            (Some(idle), Some(max)) => Some(if max.1 <= idle.1 { max } else { idle }),
+            (idle, max) => idle.or(max),
+        }
+    }
+}
+
+impl SessionPolicies {
+    /// 读取 `settings_path` 中的配置，不存在时使用默认值（不限制）。
+    ///
+    /// 文件无法读取或格式错误时返回错误，不会用默认值静默覆盖用户的配置。
+    pub fn init(&self, settings_path: PathBuf) -> Result<()> {
+        let settings = match fs::read_to_string(&settings_path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .with_context(|| format!("会话策略配置格式错误: {}", settings_path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                SessionPolicySettings::default()
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("无法读取会话策略配置: {}", settings_path.display()))
+            }
+        };
+        let mut inner = self.inner.lock().unwrap();
+        inner.settings = settings;
+        inner.settings_path = Some(settings_path);
+        Ok(())
+    }
+
+    pub(super) fn touch_input(&self, session_id: &str) {
+        self.inner
+            .lock()
+            .unwrap()
+            .last_input
+            .insert(session_id.to_string(), Instant::now());
+    }
+
+    pub(super) fn forget(&self, session_id: &str) {
+        self.inner.lock().unwrap().last_input.remove(session_id);
+    }
+
+    fn last_input(&self, session_id: &str) -> Option<Instant> {
+        self.inner
+            .lock()
+            .unwrap()
+            .last_input
+            .get(session_id)
+            .copied()
+    }
+
+    fn resolve(&self, connection_id: Option<&str>, group_id: Option<&str>) -> EffectivePolicy {
+        let inner = self.inner.lock().unwrap();
+        let settings = &inner.settings;
+        let pick = |field: fn(&SessionPolicy) -> Option<u64>| {
+            connection_id
+                .and_then(|id| settings.connections.get(id))
+                .and_then(field)
+                .or_else(|| {
+                    group_id
+                        .and_then(|id| settings.groups.get(id))
+                        .and_then(field)
+                })
+                .or_else(|| field(&settings.global))
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs)
+        };
+        EffectivePolicy {
+            idle_timeout: pick(|policy| policy.idle_timeout_secs),
+            max_duration: pick(|policy| policy.max_duration_secs),
+            warning: Duration::from_secs(settings.warning_secs),
+        }
+    }
+}
+
+impl SessionManager {
+    pub fn session_policy_settings(&self) -> SessionPolicySettings {
+        self.policies.inner.lock().unwrap().settings.clone()
+    }
+
+    pub fn update_session_policy_settings(&self, settings: SessionPolicySettings) -> Result<()> {
+        let mut inner = self.policies.inner.lock().unwrap();
+        if let Some(path) = &inner.settings_path {
+            fs::write(path, serde_json::to_string_pretty(&settings)?)?;
+        }
+        inner.settings = settings;
+        Ok(())
+    }
+
+    /// 监视会话的输入输出时间，到期前预警、到期后按策略关闭。
+    pub(super) fn watch_policy(
+        &self,
+        events: &Events,
+        session_id: &str,
+        connection: Option<&Connection>,
+        mut output: broadcast::Receiver<OutputEvent>,
+    ) {
+        let manager = self.clone();
+        let events = events.clone();
+        let session_id = session_id.to_string();
+        let connection_id = connection.map(|connection| connection.id.clone());
+        let group_id = connection.and_then(|connection| connection.group_id.clone());
+        let started = Instant::now();
+        tokio::spawn(async move {
+            let mut last_output = started;
+            let mut warned = None;
+            let mut tick = tokio::time::interval(POLICY_TICK);
+            loop {
+                tokio::select! {
+                    event = output.recv() => match event {
+                        Ok(OutputEvent::Data { .. }) | Err(RecvError::Lagged(_)) => {
+                            last_output = Instant::now();
+                        }
+                        Ok(OutputEvent::Closed(_)) | Err(RecvError::Closed) => break,
+                    },
+                    _ = tick.tick() => {
+                        let policy = manager
+                            .policies
+                            .resolve(connection_id.as_deref(), group_id.as_deref());
+                        let last_activity = manager
+                            .policies
+                            .last_input(&session_id)
+                            .map_or(last_output, |input| input.max(last_output));
+                        let Some((reason, deadline, limit)) =
+                            policy.next_deadline(started, last_activity)
+                        else {
+                            warned = None;
+                            continue;
+                        };
+                        let now = Instant::now();
+                        if now >= deadline {
+                            manager.enforce_policy(&events, &session_id, reason, limit).await;
+                            break;
+                        }
+                        let left = deadline - now;
+                        if left > policy.warning {
+                            warned = None;
+                        } else if warned != Some(reason) {
+                            warned = Some(reason);
+                            let _ = events.emit(
+                                "session-policy-warning",
+                                SessionPolicyWarningPayload {
+                                    session_id: session_id.clone(),
+                                    reason,
+                                    seconds_left: left.as_secs_f64().ceil() as u64,
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// 先记下关闭原因再关闭：之后 SSH 线程或本地进程上报的 `UserClosed` 不会覆盖终态。
+    async fn enforce_policy(
+        &self,
+        events: &Events,
+        session_id: &str,
+        reason: SessionStateReason,
+        limit: Duration,
+    ) {
+        let message = match reason {
+            SessionStateReason::MaxDuration => {
+                format!("会话存活超过 {}，已按会话策略关闭", describe(limit))
+            }
+            _ => format!("空闲超过 {}，已按会话策略关闭", describe(limit)),
+        };
+        self.publish_output(session_id, "stderr", &format!("\r\n{message}\r\n"));
+        self.states.transition(
+            events,
+            session_id,
+            SessionState::Closed,
+            StateChange::reason(reason, message),
+        );
+        let _ = self.close_session(session_id).await;
+    }
+}
+
+fn describe(limit: Duration) -> String {
+    let secs = limit.as_secs();
+    if secs.is_multiple_of(3600) {
+        format!("{} 小时", secs / 3600)
+    } else if secs.is_multiple_of(60) {
+        format!("{} 分钟", secs / 60)
+    } else {
+        format!("{secs} 秒")
+    }
+}
@@ -0,0 +1,332 @@
+//! SSH 连接池：同一连接只保持一个已认证的 ssh2 `Session`，shell、exec、SFTP 与端口转发通道都在其上开启，
+//! 避免每开一个标签就重新建立 TCP 连接和认证。
+//!
+//! 连接按租约计数，最后一个租约释放后空闲超过 `idle_timeout` 才断开；传输层出错的连接标记为失效，
+//! 下一次租用时重新建立。池中的 Session 始终处于非阻塞模式，各线程经 `wait_for_ssh` 重试 `EAGAIN`，互不阻塞。
+//!
+//! libssh2 每个 Session 只记录一个进行中的通道打开（`open_state`，SFTP 初始化同样经过它），
+//! 另一线程重试时会接手前一个打开并拿走其通道；等待通道请求的回复时读到其他通道的同类回复也会直接报错。
+//! 因此开通道、初始化 SFTP 与新通道上的 setenv/pty/shell/exec 请求在整个重试过程中都持有 `opening`。
+
+use std::collections::HashMap;
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use libssh2_sys as raw;
+use ssh2::{Channel as SshChannel, Error as SshError, ErrorCode, Session as SshSession, Sftp};
+
+use crate::domain::models::{Connection, SessionErrorCode};
+
+use super::agent::AgentRelay;
+use super::state::SessionFailure;
+use super::{connect_ssh, is_session_would_block, wait_for_ssh, SessionSecret};
+
+/// 最后一个租约释放后保留连接的时长。
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+pub struct ConnectionPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    entries: Mutex<HashMap<String, Arc<PoolEntry>>>,
+    idle_timeout: Mutex<Duration>,
+}
+
+#[derive(Default)]
+struct PoolEntry {
+    /// 建立连接期间持有，同一连接的其他租用者等待这一次认证完成后直接复用。
+    connecting: Mutex<()>,
+    /// 开通道、初始化 SFTP 或发送通道请求期间持有，同一连接上同时只有一个操作在等待回复。
+    opening: Mutex<()>,
+    state: Mutex<EntryState>,
+}
+
+#[derive(Default)]
+struct EntryState {
+    transport: Option<Transport>,
+    leases: usize,
+    /// 每次建立新连接时递增，旧连接的租约不再影响计数。
+    generation: u64,
+    /// 每次租用或归还时递增，空闲回收据此判断期间是否有人用过。
+    epoch: u64,
+}
+
+struct Transport {
+    session: SshSession,
+    tcp: TcpStream,
+    /// 首个请求 agent 转发的标签创建，连接上的所有标签共用。
+    agent: Option<Arc<Mutex<AgentRelay>>>,
+}
+
+impl Transport {
+    /// 通知远端并关闭套接字；仍持有 Session 的租约随后的读写会立即失败。
+    fn shutdown(self, description: &str) {
+        for _ in 0..50 {
+            match self.session.disconnect(None, description, None) {
+                Err(err) if is_session_would_block(&err) => {
+                    thread::sleep(Duration::from_millis(12));
+                }
+                _ => break,
+            }
+        }
+        let _ = self.tcp.shutdown(Shutdown::Both);
+    }
+}
+
+impl Default for ConnectionPool {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(PoolInner {
+                entries: Mutex::new(HashMap::new()),
+                idle_timeout: Mutex::new(DEFAULT_IDLE_TIMEOUT),
+            }),
+        }
+    }
+}
+
+/// 对池中连接的一次租用，释放时归还；持有期间连接不会被空闲回收。
+pub struct SshLease {
+    pool: Weak<PoolInner>,
+    entry: Arc<PoolEntry>,
+    session: SshSession,
+    generation: u64,
+    reused: bool,
+}
+
+impl ConnectionPool {
+    pub fn set_idle_timeout(&self, timeout: Duration) {
+        *self.inner.idle_timeout.lock().unwrap() = timeout;
+    }
+
+    /// 当前保持着的连接数（含空闲等待回收的）。
+    pub fn open_connections(&self) -> usize {
+        self.inner
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|entry| entry.state.lock().unwrap().transport.is_some())
+            .count()
+    }
+
+    /// 租用连接，没有可用连接时新建；`on_handshake` 只在新建连接、握手完成后调用。
+    pub fn acquire(
+        &self,
+        connection: &Connection,
+        secret: Option<&SessionSecret>,
+        on_handshake: impl FnOnce(),
+    ) -> Result<SshLease> {
+        let entry = self
+            .inner
+            .entries
+            .lock()
+            .unwrap()
+            .entry(pool_key(connection))
+            .or_default()
+            .clone();
+        let _connecting = entry.connecting.lock().unwrap();
+        if let Some(lease) = self.lease(&entry, true) {
+            return Ok(lease);
+        }
+
+        let (session, tcp) = connect_ssh(connection, secret, on_handshake)?;
+        tcp.set_nonblocking(true)
+            .context("设置 SSH 套接字为非阻塞失败")?;
+        session.set_blocking(false);
+        {
+            let mut state = entry.state.lock().unwrap();
+            state.generation += 1;
+            state.leases = 0;
+            state.transport = Some(Transport {
+                session,
+                tcp,
+                agent: None,
+            });
+        }
+        Ok(self
+            .lease(&entry, false)
+            .expect("transport was just established"))
+    }
+
+    /// 租用连接并开启一个 session 通道。复用的连接若已被远端断开，则作废后重连一次再试；
+    /// 服务器只是拒绝开通道（如超出 `MaxSessions`）时直接报错，不影响连接上的其他通道。
+    pub fn open_channel(
+        &self,
+        connection: &Connection,
+        secret: Option<&SessionSecret>,
+        mut on_handshake: impl FnMut(),
+    ) -> Result<(SshLease, SshChannel)> {
+        let lease = self.acquire(connection, secret, &mut on_handshake)?;
+        let opened = {
+            let _opening = lease.entry.opening.lock().unwrap();
+            wait_for_session(lease.session(), |session| session.channel_session())
+        };
+        match opened {
+            Ok(channel) => Ok((lease, channel)),
+            Err(err) if lease.reused() && is_transport_error(&err) => {
+                lease.invalidate();
+                drop(lease);
+                let lease = self.acquire(connection, secret, on_handshake)?;
+                let channel = lease.channel_session().map_err(|err| {
+                    SessionFailure::new(SessionErrorCode::ChannelFailed, err.to_string())
+                })?;
+                Ok((lease, channel))
+            }
+            Err(err) => Err(SessionFailure::new(
+                SessionErrorCode::ChannelFailed,
+                format!("channel_session: {err}"),
+            )
+            .into()),
+        }
+    }
+
+    fn lease(&self, entry: &Arc<PoolEntry>, reused: bool) -> Option<SshLease> {
+        let mut state = entry.state.lock().unwrap();
+        let session = state.transport.as_ref()?.session.clone();
+        state.leases += 1;
+        state.epoch += 1;
+        Some(SshLease {
+            pool: Arc::downgrade(&self.inner),
+            entry: entry.clone(),
+            session,
+            generation: state.generation,
+            reused,
+        })
+    }
+}
+
+impl SshLease {
+    pub fn session(&self) -> &SshSession {
+        &self.session
+    }
+
+    /// 是否复用了已有连接（没有重新握手与认证）。
+    pub fn reused(&self) -> bool {
+        self.reused
+    }
+
+    /// 传输层出错时调用：该连接不再分配给新的租用者，已有租约的读写随之失败。
+    pub fn invalidate(&self) {
+        let transport = {
+            let mut state = self.entry.state.lock().unwrap();
+            if state.generation != self.generation {
+                return;
+            }
+            state.transport.take()
+        };
+        if let Some(transport) = transport {
+            transport.shutdown("connection reset");
+        }
+    }
+
+    /// 连接上的 agent 转发中继，首次调用时注册回调；连接已作废时返回 None。
+    pub(super) fn agent_relay(&self) -> Option<Arc<Mutex<AgentRelay>>> {
+        let mut state = self.entry.state.lock().unwrap();
+        if state.generation != self.generation {
+            return None;
+        }
+        let transport = state.transport.as_mut()?;
+        let relay = transport
+            .agent
+            .get_or_insert_with(|| Arc::new(Mutex::new(AgentRelay::install(&transport.session))));
+        Some(relay.clone())
+    }
+
+    /// 在新通道上发送 setenv、pty、shell、exec 等请求期间持有，与其他租约的打开和请求错开。
+    pub fn lock_requests(&self) -> MutexGuard<'_, ()> {
+        self.entry.opening.lock().unwrap()
+    }
+
+    pub fn channel_session(&self) -> Result<SshChannel> {
+        let _opening = self.entry.opening.lock().unwrap();
+        wait_for_ssh("channel_session", || self.session.channel_session())
+    }
+
+    pub fn sftp(&self) -> Result<Sftp> {
+        let _opening = self.entry.opening.lock().unwrap();
+        wait_for_ssh("sftp", || self.session.sftp())
+    }
+
+    /// 经远端转发到 `host:port` 的通道（`ssh -L`）。
+    pub fn channel_direct_tcpip(&self, host: &str, port: u16) -> Result<SshChannel> {
+        let _opening = self.entry.opening.lock().unwrap();
+        wait_for_ssh("channel_direct_tcpip", || {
+            self.session.channel_direct_tcpip(host, port, None)
+        })
+    }
+}
+
+impl Drop for SshLease {
+    fn drop(&mut self) {
+        let epoch = {
+            let mut state = self.entry.state.lock().unwrap();
+            if state.generation != self.generation || state.transport.is_none() {
+                return;
+            }
+            state.leases = state.leases.saturating_sub(1);
+            state.epoch += 1;
+            if state.leases > 0 {
+                return;
+            }
+            state.epoch
+        };
+        let Some(pool) = self.pool.upgrade() else {
+            return;
+        };
+        let timeout = *pool.idle_timeout.lock().unwrap();
+        let entry = self.entry.clone();
+        let generation = self.generation;
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            let transport = {
+                let mut state = entry.state.lock().unwrap();
+                if state.generation != generation || state.epoch != epoch {
+                    return;
+                }
+                state.transport.take()
+            };
+            if let Some(transport) = transport {
+                transport.shutdown("idle timeout");
+            }
+        });
+    }
+}
+
+fn wait_for_session<T>(
+    session: &SshSession,
+    mut op: impl FnMut(&SshSession) -> Result<T, SshError>,
+) -> Result<T, SshError> {
+    loop {
+        match op(session) {
+            Err(err) if is_session_would_block(&err) => thread::sleep(Duration::from_millis(12)),
+            result => return result,
+        }
+    }
+}
+
+/// 套接字已断开或读写失败；服务器拒绝请求之类的协议层错误不算。
+fn is_transport_error(err: &SshError) -> bool {
+    matches!(
+        err.code(),
+        ErrorCode::Session(
+            raw::LIBSSH2_ERROR_SOCKET_SEND
+                | raw::LIBSSH2_ERROR_SOCKET_RECV
+                | raw::LIBSSH2_ERROR_SOCKET_DISCONNECT
+                | raw::LIBSSH2_ERROR_SOCKET_TIMEOUT
+                | raw::LIBSSH2_ERROR_TIMEOUT
+        )
+    )
+}
+
+/// 连接 id 之外再带上目标地址与用户名，编辑连接后不会误用旧的认证结果。
+fn pool_key(connection: &Connection) -> String {
+    format!(
+        "{}|{}@{}:{}",
+        connection.id, connection.username, connection.host, connection.port
+    )
+}
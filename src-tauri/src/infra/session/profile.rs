@@ -0,0 +1,239 @@
+//! 本地 shell 配置：新建本地标签时按配置启动程序，未设默认配置时沿用系统 shell。
+//!
+//! 用户保存的配置写入 `shell_profiles.json`；`/etc/shells` 中已安装的 shell 作为检测结果一并列出，
+//! 保存或设为默认后才写入文件。
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, bail, Context, Result};
+use tokio::process::Command;
+use uuid::Uuid;
+
+use crate::domain::models::{ShellProfile, ShellProfileSettings};
+
+use super::events::Events;
+use super::SessionManager;
+
+/// 系统登记的可用登录 shell 列表。
+pub const SHELLS_FILE: &str = "/etc/shells";
+
+#[derive(Clone, Default)]
+pub struct ShellProfiles {
+    inner: Arc<Mutex<ProfileInner>>,
+}
+
+#[derive(Default)]
+struct ProfileInner {
+    settings: ShellProfileSettings,
+    settings_path: Option<PathBuf>,
+}
+
+impl ProfileInner {
+    fn save(&self) -> Result<()> {
+        if let Some(path) = &self.settings_path {
+            fs::write(path, serde_json::to_string_pretty(&self.settings)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl ShellProfiles {
+    /// 读取 `settings_path` 中的配置，不存在时为空列表。
+    ///
+    /// 文件无法读取或格式错误时返回错误，不会用空列表静默覆盖已保存的配置。
+    pub fn init(&self, settings_path: PathBuf) -> Result<()> {
+        let settings = match fs::read_to_string(&settings_path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .with_context(|| format!("shell 配置格式错误: {}", settings_path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                ShellProfileSettings::default()
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("无法读取 shell 配置: {}", settings_path.display()))
+            }
+        };
+        let mut inner = self.inner.lock().unwrap();
+        inner.settings = settings;
+        inner.settings_path = Some(settings_path);
+        Ok(())
+    }
+}
+
+/// 未设默认配置（或默认配置已不存在）时启动的系统 shell。
+pub fn builtin_profile() -> ShellProfile {
+    #[cfg(target_os = "windows")]
+    let (program, args) = ("cmd", vec!["/K".to_string()]);
+    #[cfg(not(target_os = "windows"))]
+    let (program, args) = ("/bin/sh", vec!["-i".to_string()]);
+    ShellProfile {
+        id: "builtin".to_string(),
+        name: "本地 shell".to_string(),
+        program: program.to_string(),
+        args,
+        env: Default::default(),
+        cwd: None,
+        login: false,
+        detected: false,
+    }
+}
+
+/// 解析 `/etc/shells` 格式的文件：跳过注释、不存在的路径与 `nologin` 之类的占位程序，
+/// 同名 shell（如 `/bin/bash` 与 `/usr/bin/bash`）只保留第一个。
+pub fn detect_shell_profiles(shells_file: &Path) -> Vec<ShellProfile> {
+    let Ok(content) = fs::read_to_string(shells_file) else {
+        return Vec::new();
+    };
+    let mut seen = HashSet::new();
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter(|line| Path::new(line).is_file())
+        .filter_map(|line| {
+            let name = Path::new(line).file_name()?.to_str()?.to_string();
+            if matches!(name.as_str(), "nologin" | "false" | "true") || !seen.insert(name.clone()) {
+                return None;
+            }
+            Some(ShellProfile {
+                id: format!("detected-{name}"),
+                name,
+                program: line.to_string(),
+                args: vec!["-i".to_string()],
+                env: Default::default(),
+                cwd: None,
+                login: false,
+                detected: true,
+            })
+        })
+        .collect()
+}
+
+/// 按配置构造命令；`cwd` 覆盖配置中的目录（“在同一目录打开新标签”）。
+pub(super) fn shell_command(profile: &ShellProfile, cwd: Option<&str>) -> Command {
+    let mut cmd = Command::new(&profile.program);
+    cmd.args(&profile.args).envs(&profile.env);
+    #[cfg(unix)]
+    if profile.login {
+        let name = Path::new(&profile.program)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&profile.program);
+        cmd.arg0(format!("-{name}"));
+    }
+    if let Some(dir) = cwd.or(profile.cwd.as_deref()) {
+        cmd.current_dir(dir);
+    }
+    cmd
+}
+
+impl SessionManager {
+    /// 已保存的配置在前，其后是检测到且尚未保存的 shell（按程序路径去重）。
+    pub fn shell_profiles(&self) -> ShellProfileSettings {
+        let mut settings = self.profiles.inner.lock().unwrap().settings.clone();
+        let saved: HashSet<String> = settings
+            .profiles
+            .iter()
+            .map(|profile| profile.program.clone())
+            .collect();
+        settings.profiles.extend(
+            detect_shell_profiles(Path::new(SHELLS_FILE))
+                .into_iter()
+                .filter(|profile| !saved.contains(&profile.program)),
+        );
+        settings
+    }
+
+    /// 新建（`id` 为空）或覆盖同 id 的配置；保存检测到的配置会将其转为普通配置。
+    pub fn save_shell_profile(&self, mut profile: ShellProfile) -> Result<ShellProfile> {
+        if profile.name.trim().is_empty() {
+            bail!("配置名称不能为空");
+        }
+        if profile.program.trim().is_empty() {
+            bail!("启动程序不能为空");
+        }
+        if profile.id.is_empty() {
+            profile.id = format!("profile-{}", Uuid::new_v4().simple());
+        }
+        profile.detected = false;
+        let mut inner = self.profiles.inner.lock().unwrap();
+        let profiles = &mut inner.settings.profiles;
+        match profiles
+            .iter_mut()
+            .find(|existing| existing.id == profile.id)
+        {
+            Some(existing) => *existing = profile.clone(),
+            None => profiles.push(profile.clone()),
+        }
+        inner.save()?;
+        Ok(profile)
+    }
+
+    pub fn delete_shell_profile(&self, id: &str) -> Result<()> {
+        let mut inner = self.profiles.inner.lock().unwrap();
+        inner.settings.profiles.retain(|profile| profile.id != id);
+        if inner.settings.default_profile_id.as_deref() == Some(id) {
+            inner.settings.default_profile_id = None;
+        }
+        inner.save()
+    }
+
+    /// 设置新建本地标签使用的配置，`None` 恢复系统默认 shell；选中检测到的配置时一并保存。
+    pub fn set_default_shell_profile(&self, id: Option<&str>) -> Result<()> {
+        if let Some(id) = id {
+            let profile = self.find_shell_profile(id)?;
+            if profile.detected {
+                self.save_shell_profile(profile)?;
+            }
+        }
+        let mut inner = self.profiles.inner.lock().unwrap();
+        inner.settings.default_profile_id = id.map(str::to_string);
+        inner.save()
+    }
+
+    /// 开启后新建的本地会话由守护进程托管；已在运行的会话不受影响。
+    pub fn set_persistent_local_sessions(&self, enabled: bool) -> Result<()> {
+        let mut inner = self.profiles.inner.lock().unwrap();
+        inner.settings.persistent_sessions = enabled;
+        inner.save()
+    }
+
+    /// 按配置启动本地 shell，`profile_id` 为空时使用默认配置。
+    pub async fn create_local_shell(
+        &self,
+        events: Events,
+        profile_id: Option<&str>,
+    ) -> Result<String> {
+        let profile = match profile_id {
+            Some(id) => self.find_shell_profile(id)?,
+            None => self.default_shell_profile(),
+        };
+        self.spawn_local_shell(events, &profile, None).await
+    }
+
+    /// 默认配置已被删除时退回系统 shell。
+    pub(super) fn default_shell_profile(&self) -> ShellProfile {
+        let default_id = self
+            .profiles
+            .inner
+            .lock()
+            .unwrap()
+            .settings
+            .default_profile_id
+            .clone();
+        default_id
+            .and_then(|id| self.find_shell_profile(&id).ok())
+            .unwrap_or_else(builtin_profile)
+    }
+
+    fn find_shell_profile(&self, id: &str) -> Result<ShellProfile> {
+        self.shell_profiles()
+            .profiles
+            .into_iter()
+            .find(|profile| profile.id == id)
+            .ok_or_else(|| anyhow!("本地 shell 配置不存在"))
+    }
+}
@@ -0,0 +1,149 @@
+//! 服务端屏幕模型：把会话输出喂给 VT100/xterm 解析器（`vt100` crate），维护网格、光标、
+//! 字符属性与备用屏幕，供重新连接时的屏幕快照、按渲染结果搜索回滚缓冲以及标签缩略图使用。
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::domain::models::ScreenSnapshot;
+
+use super::output::OutputEvent;
+use super::SessionManager;
+
+/// 与远端 PTY 请求的尺寸一致。
+pub(super) const SCREEN_ROWS: u16 = 24;
+pub(super) const SCREEN_COLS: u16 = 80;
+/// 主屏幕保留的回滚行数。
+const SCROLLBACK_LINES: usize = 10_000;
+
+/// 每个会话一个解析器；会话结束时释放。
+#[derive(Clone, Default)]
+pub struct ScreenModels {
+    screens: Arc<Mutex<HashMap<String, vt100::Parser>>>,
+}
+
+impl ScreenModels {
+    /// 持续把会话输出写入屏幕模型，直到会话结束。
+    ///
+    /// 本地 shell 按行读取且没有 PTY，`line_mode` 为 true 时把换行补成 `\r\n`，避免内容阶梯状错位。
+    pub(super) fn watch(
+        &self,
+        session_id: &str,
+        mut output: broadcast::Receiver<OutputEvent>,
+        line_mode: bool,
+    ) {
+        self.screens.lock().unwrap().insert(
+            session_id.to_string(),
+            vt100::Parser::new(SCREEN_ROWS, SCREEN_COLS, SCROLLBACK_LINES),
+        );
+        let screens = self.clone();
+        let session_id = session_id.to_string();
+        tokio::spawn(async move {
+            loop {
+                match output.recv().await {
+                    Ok(OutputEvent::Data { data, .. }) if line_mode => {
+                        let data = data.replace("\r\n", "\n").replace('\n', "\r\n");
+                        screens.feed(&session_id, &data);
+                    }
+                    Ok(OutputEvent::Data { data, .. }) => screens.feed(&session_id, &data),
+                    Ok(OutputEvent::Closed(_)) | Err(RecvError::Closed) => break,
+                    // 丢失的输出无法补回，屏幕可能与前端不一致，直到下一次整屏重绘。
+                    Err(RecvError::Lagged(_)) => {}
+                }
+            }
+        });
+    }
+
+    fn feed(&self, session_id: &str, data: &str) {
+        if let Some(parser) = self.screens.lock().unwrap().get_mut(session_id) {
+            parser.process(data.as_bytes());
+        }
+    }
+
+    pub(super) fn forget(&self, session_id: &str) {
+        self.screens.lock().unwrap().remove(session_id);
+    }
+
+    fn with_parser<T>(
+        &self,
+        session_id: &str,
+        f: impl FnOnce(&mut vt100::Parser) -> T,
+    ) -> Option<T> {
+        self.screens.lock().unwrap().get_mut(session_id).map(f)
+    }
+}
+
+impl SessionManager {
+    /// 当前屏幕的完整快照，`formatted` 可直接写入前端终端以还原颜色与光标。
+    pub fn screen_snapshot(&self, session_id: &str) -> Option<ScreenSnapshot> {
+        self.screens.with_parser(session_id, |parser| {
+            let screen = parser.screen();
+            let (rows, cols) = screen.size();
+            let (cursor_row, cursor_col) = screen.cursor_position();
+            ScreenSnapshot {
+                rows,
+                cols,
+                cursor_row,
+                cursor_col,
+                cursor_visible: !screen.hide_cursor(),
+                alternate_screen: screen.alternate_screen(),
+                title: screen.title().to_string(),
+                lines: screen
+                    .rows(0, cols)
+                    .map(|row| row.trim_end().to_string())
+                    .collect(),
+                formatted: String::from_utf8_lossy(&screen.contents_formatted()).into_owned(),
+            }
+        })
+    }
+
+    /// 标签缩略图用的文本预览：取可见区域最后 `max_rows` 个非空行，每行截断到 `max_cols` 个字符。
+    pub fn screen_thumbnail(
+        &self,
+        session_id: &str,
+        max_rows: usize,
+        max_cols: usize,
+    ) -> Option<Vec<String>> {
+        let snapshot = self.screen_snapshot(session_id)?;
+        let mut lines = snapshot.lines;
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        let skip = lines.len().saturating_sub(max_rows);
+        Some(
+            lines
+                .into_iter()
+                .skip(skip)
+                .map(|line| line.chars().take(max_cols).collect())
+                .collect(),
+        )
+    }
+
+    /// 按渲染结果展开的回滚缓冲与可见区域，最旧的行在前。
+    ///
+    /// 备用屏幕（vim、less 等全屏程序）没有回滚缓冲，此时只返回当前屏幕。
+    pub fn screen_lines(&self, session_id: &str) -> Option<Vec<String>> {
+        self.screens.with_parser(session_id, |parser| {
+            let (rows, cols) = parser.screen().size();
+            parser.set_scrollback(usize::MAX);
+            let total = parser.screen().scrollback();
+            // vt100 0.15 的视口偏移超过屏幕高度时会在 `visible_rows` 中下溢，不能逐页向上翻；
+            // 改为临时把屏幕加高到能容纳全部回滚行，一次读完再恢复原尺寸。加高只在底部追加空行，
+            // 恢复时原样截掉，屏幕内容与光标不受影响。
+            let grown = rows.saturating_add(u16::try_from(total).unwrap_or(u16::MAX));
+            let offset = usize::from(grown - rows);
+            parser.set_size(grown, cols);
+            parser.set_scrollback(offset);
+            let lines = parser
+                .screen()
+                .rows(0, cols)
+                .take(offset + usize::from(rows))
+                .map(|row| row.trim_end().to_string())
+                .collect();
+            parser.set_scrollback(0);
+            parser.set_size(rows, cols);
+            lines
+        })
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use crate::domain::models::{AutomationScript, ScriptAction, ScriptJump};
+
+use super::events::Events;
+use super::exec::CancelToken;
+use super::output::OutputEvent;
+use super::SessionManager;
+
+const DEFAULT_EXPECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// 防止 goto 形成死循环。
+const MAX_EXECUTED_STEPS: usize = 1000;
+/// expect 缓冲上限，超出后丢弃最早的输出。
+const MAX_PENDING_BYTES: usize = 64 * 1024;
+
+#[derive(serde::Serialize, Clone)]
+pub struct ScriptProgressPayload {
+    pub run_id: String,
+    pub session_id: String,
+    pub step: usize,
+    pub label: Option<String>,
+    pub action: String,
+    pub message: Option<String>,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct ScriptFinishedPayload {
+    pub run_id: String,
+    pub session_id: String,
+    pub script_id: String,
+    pub success: bool,
+    pub cancelled: bool,
+    pub message: Option<String>,
+}
+
+enum Prepared {
+    Send(String),
+    Expect {
+        branches: Vec<(Regex, ScriptJump)>,
+        timeout: Duration,
+        on_timeout: Option<ScriptJump>,
+    },
+    Sleep(Duration),
+    Jump(ScriptJump),
+}
+
+impl Prepared {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Send(_) => "send",
+            Self::Expect { .. } => "expect",
+            Self::Sleep(_) => "sleep",
+            Self::Jump(_) => "jump",
+        }
+    }
+}
+
+enum Outcome {
+    Succeeded,
+    Failed(String),
+    Cancelled,
+}
+
+struct ScriptRun {
+    manager: SessionManager,
+    events: Events,
+    run_id: String,
+    session_id: String,
+    cancel: CancelToken,
+    output: broadcast::Receiver<OutputEvent>,
+    pending: String,
+}
+
+impl SessionManager {
+    /// 在活动会话上异步执行自动化脚本，返回 run id。
+    ///
+    /// 进度通过 `script-progress` 推送，结束时推送 `script-finished`；可用 `cancel_command` 取消。
+    pub async fn run_script(
+        &self,
+        events: Events,
+        session_id: &str,
+        script: AutomationScript,
+    ) -> Result<String> {
+        self.start_script(events, session_id, script, false)
+    }
+
+    /// 同 [`Self::run_script`]，但先把会话此前的输出交给 expect 匹配，
+    /// 用于刚创建的会话：登录提示等在脚本订阅之前到达的输出不会错过。
+    pub async fn run_script_from_start(
+        &self,
+        events: Events,
+        session_id: &str,
+        script: AutomationScript,
+    ) -> Result<String> {
+        self.start_script(events, session_id, script, true)
+    }
+
+    fn start_script(
+        &self,
+        events: Events,
+        session_id: &str,
+        script: AutomationScript,
+        replay: bool,
+    ) -> Result<String> {
+        let (steps, labels) = prepare(&script)?;
+        let (backlog, output) = self
+            .output
+            .subscribe_with_backlog(session_id)
+            .ok_or_else(|| anyhow!("session not found"))?;
+        let run_id = format!("script-{}", Uuid::new_v4().simple());
+        let mut run = ScriptRun {
+            manager: self.clone(),
+            events,
+            run_id: run_id.clone(),
+            session_id: session_id.to_string(),
+            cancel: self.jobs.register(&run_id),
+            output,
+            pending: if replay { backlog } else { String::new() },
+        };
+        tokio::spawn(async move {
+            let outcome = run.execute(&script, &steps, &labels).await;
+            run.manager.jobs.finish(&run.run_id);
+            let (success, cancelled, message) = match outcome {
+                Outcome::Succeeded => (true, false, None),
+                Outcome::Failed(message) => (false, false, Some(message)),
+                Outcome::Cancelled => (false, true, None),
+            };
+            let _ = run.events.emit(
+                "script-finished",
+                ScriptFinishedPayload {
+                    run_id: run.run_id.clone(),
+                    session_id: run.session_id.clone(),
+                    script_id: script.id.clone(),
+                    success,
+                    cancelled,
+                    message,
+                },
+            );
+        });
+        Ok(run_id)
+    }
+}
+
+/// 预编译正则并校验 goto 标签，尽早暴露脚本错误。
+fn prepare(script: &AutomationScript) -> Result<(Vec<Prepared>, HashMap<String, usize>)> {
+    let labels: HashMap<String, usize> = script
+        .steps
+        .iter()
+        .enumerate()
+        .filter_map(|(index, step)| step.label.clone().map(|label| (label, index)))
+        .collect();
+    let check_jump = |jump: &ScriptJump| match jump {
+        ScriptJump::Goto { label } if !labels.contains_key(label) => {
+            Err(anyhow!("脚本引用了不存在的标签: {label}"))
+        }
+        _ => Ok(()),
+    };
+
+    let mut steps = Vec::with_capacity(script.steps.len());
+    for step in &script.steps {
+        let prepared = match &step.action {
+            ScriptAction::Send { data } => Prepared::Send(data.clone()),
+            ScriptAction::Expect {
+                branches,
+                timeout_ms,
+                on_timeout,
+            } => {
+                let mut compiled = Vec::with_capacity(branches.len());
+                for branch in branches {
+                    check_jump(&branch.then)?;
+                    let regex = Regex::new(&branch.pattern)
+                        .map_err(|err| anyhow!("无效的匹配规则 {}: {err}", branch.pattern))?;
+                    compiled.push((regex, branch.then.clone()));
+                }
+                if let Some(jump) = on_timeout {
+                    check_jump(jump)?;
+                }
+                Prepared::Expect {
+                    branches: compiled,
+                    timeout: timeout_ms
+                        .map(Duration::from_millis)
+                        .unwrap_or(DEFAULT_EXPECT_TIMEOUT),
+                    on_timeout: on_timeout.clone(),
+                }
+            }
+            ScriptAction::Sleep { ms } => Prepared::Sleep(Duration::from_millis(*ms)),
+            ScriptAction::Jump { to } => {
+                check_jump(to)?;
+                Prepared::Jump(to.clone())
+            }
+        };
+        steps.push(prepared);
+    }
+    Ok((steps, labels))
+}
+
+impl ScriptRun {
+    async fn execute(
+        &mut self,
+        script: &AutomationScript,
+        steps: &[Prepared],
+        labels: &HashMap<String, usize>,
+    ) -> Outcome {
+        let mut index = 0;
+        let mut executed = 0;
+        while index < steps.len() {
+            executed += 1;
+            if executed > MAX_EXECUTED_STEPS {
+                return Outcome::Failed(format!(
+                    "执行步骤超过 {MAX_EXECUTED_STEPS} 次，疑似死循环"
+                ));
+            }
+            let step = &steps[index];
+            self.progress(index, script.steps[index].label.clone(), step.name(), None);
+
+            let jump = match step {
+                Prepared::Send(data) => {
+                    if let Err(err) = self.manager.send_input(&self.session_id, data).await {
+                        return Outcome::Failed(format!("发送失败: {err}"));
+                    }
+                    ScriptJump::Next
+                }
+                Prepared::Sleep(duration) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(*duration) => ScriptJump::Next,
+                        _ = self.cancel.cancelled() => return Outcome::Cancelled,
+                    }
+                }
+                Prepared::Jump(jump) => jump.clone(),
+                Prepared::Expect {
+                    branches,
+                    timeout,
+                    on_timeout,
+                } => match self.expect(branches, *timeout).await {
+                    Ok(Some((matched, jump))) => {
+                        self.progress(
+                            index,
+                            script.steps[index].label.clone(),
+                            "matched",
+                            Some(matched),
+                        );
+                        jump
+                    }
+                    Ok(None) => match on_timeout {
+                        Some(jump) => {
+                            self.progress(
+                                index,
+                                script.steps[index].label.clone(),
+                                "timeout",
+                                None,
+                            );
+                            jump.clone()
+                        }
+                        None => return Outcome::Failed(format!("第 {} 步等待输出超时", index + 1)),
+                    },
+                    Err(outcome) => return outcome,
+                },
+            };
+
+            index = match jump {
+                ScriptJump::Next => index + 1,
+                ScriptJump::Goto { label } => labels[&label],
+                ScriptJump::Succeed => return Outcome::Succeeded,
+                ScriptJump::Fail { message } => {
+                    return Outcome::Failed(
+                        message.unwrap_or_else(|| format!("脚本在第 {} 步失败", index + 1)),
+                    )
+                }
+            };
+        }
+        Outcome::Succeeded
+    }
+
+    /// 返回命中的文本与对应分支的跳转；超时返回 None。
+    async fn expect(
+        &mut self,
+        branches: &[(Regex, ScriptJump)],
+        timeout: Duration,
+    ) -> std::result::Result<Option<(String, ScriptJump)>, Outcome> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            for (regex, jump) in branches {
+                if let Some(found) = regex.find(&self.pending) {
+                    let matched = found.as_str().to_string();
+                    self.pending.drain(..found.end());
+                    return Ok(Some((matched, jump.clone())));
+                }
+            }
+            tokio::select! {
+                event = self.output.recv() => match event {
+                    Ok(OutputEvent::Data { data, .. }) => self.push_output(&data),
+                    Ok(OutputEvent::Closed(_)) | Err(RecvError::Closed) => {
+                        return Err(Outcome::Failed("会话已结束".into()))
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                },
+                _ = tokio::time::sleep_until(deadline) => return Ok(None),
+                _ = self.cancel.cancelled() => return Err(Outcome::Cancelled),
+            }
+        }
+    }
+
+    fn push_output(&mut self, chunk: &str) {
+        self.pending.push_str(chunk);
+        if self.pending.len() > MAX_PENDING_BYTES {
+            let mut cut = self.pending.len() - MAX_PENDING_BYTES;
+            while !self.pending.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.pending.drain(..cut);
+        }
+    }
+
+    fn progress(&self, step: usize, label: Option<String>, action: &str, message: Option<String>) {
+        let _ = self.events.emit(
+            "script-progress",
+            ScriptProgressPayload {
+                run_id: self.run_id.clone(),
+                session_id: self.session_id.clone(),
+                step,
+                label,
+                action: action.to_string(),
+                message,
+            },
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! 在服务端屏幕模型的回滚缓冲中搜索，覆盖单个会话或全部运行中会话。
+
+use anyhow::{anyhow, bail, Result};
+use regex::RegexBuilder;
+
+use crate::domain::models::{ScrollbackMatch, ScrollbackQuery};
+
+use super::SessionManager;
+
+const DEFAULT_CONTEXT_LINES: usize = 2;
+const DEFAULT_LIMIT: usize = 200;
+
+impl SessionManager {
+    /// `session_id` 为空时依次搜索全部运行中会话，先开始的会话在前；每行只记录第一处命中。
+    pub fn search_scrollback(
+        &self,
+        session_id: Option<&str>,
+        query: &ScrollbackQuery,
+    ) -> Result<Vec<ScrollbackMatch>> {
+        if query.pattern.is_empty() {
+            bail!("搜索内容不能为空");
+        }
+        let pattern = if query.regex {
+            query.pattern.clone()
+        } else {
+            regex::escape(&query.pattern)
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!query.case_sensitive)
+            .build()
+            .map_err(|err| anyhow!("无效的搜索表达式: {err}"))?;
+        let context = query.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES);
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+
+        let live = self.live_sessions();
+        let targets: Vec<_> = match session_id {
+            Some(id) => {
+                let summary = live
+                    .into_iter()
+                    .find(|summary| summary.id == id)
+                    .ok_or_else(|| anyhow!("会话不存在"))?;
+                vec![summary]
+            }
+            None => live,
+        };
+
+        let mut matches = Vec::new();
+        for summary in targets {
+            // 会话可能在列出之后结束，此时没有屏幕模型，直接跳过
+            let Some(lines) = self.screen_lines(&summary.id) else {
+                continue;
+            };
+            for (index, line) in lines.iter().enumerate() {
+                if matches.len() >= limit {
+                    return Ok(matches);
+                }
+                let Some(found) = regex.find(line) else {
+                    continue;
+                };
+                let after_end = (index + 1 + context).min(lines.len());
+                matches.push(ScrollbackMatch {
+                    session_id: summary.id.clone(),
+                    session_title: summary.title.clone(),
+                    line_number: index + 1,
+                    line: line.clone(),
+                    match_start: line[..found.start()].chars().count(),
+                    match_end: line[..found.end()].chars().count(),
+                    before: lines[index.saturating_sub(context)..index].to_vec(),
+                    after: lines[index + 1..after_end].to_vec(),
+                });
+            }
+        }
+        Ok(matches)
+    }
+}
@@ -0,0 +1,413 @@
+//! shell 集成：解析会话输出中的 OSC 133（提示符/命令标记）与 OSC 7（当前目录），
+//! 记录每个会话的命令历史。
+//!
+//! 约定的标记顺序为 `A`（提示符开始）→ `B`（提示符结束，用户开始输入）→ `C`（命令开始执行）
+//! → `D[;退出码]`（命令结束）。同样识别 VS Code 的 OSC 633，其中 `E;命令行;nonce` 由 shell 直接上报命令文本，
+//! 只有带着本会话 nonce 的上报才被采信（任何输出都能伪造不带 nonce 的序列，而命令会被 `rerun_last_command` 重新输入）；
+//! 没有可信上报时取 `B` 与 `C` 之间回显的输入，并按退格还原编辑。
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::domain::models::{Connection, ShellCommand};
+use crate::domain::services::session_service::CommandHistoryRepository;
+
+use super::ansi::{AnsiFilter, AnsiPiece};
+use super::events::Events;
+use super::output::OutputEvent;
+use super::SessionManager;
+
+/// 每个会话在内存中保留的命令条数。
+const MAX_COMMANDS_PER_SESSION: usize = 500;
+/// 单条命令文本的长度上限。
+const MAX_COMMAND_BYTES: usize = 4096;
+
+#[derive(serde::Serialize, Clone)]
+pub struct SessionCommandPayload {
+    pub session_id: String,
+    pub command: ShellCommand,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct SessionCwdPayload {
+    pub session_id: String,
+    pub cwd: String,
+}
+
+#[derive(Clone, Default)]
+pub struct ShellIntegration {
+    inner: Arc<Mutex<IntegrationInner>>,
+}
+
+#[derive(Default)]
+struct IntegrationInner {
+    sessions: HashMap<String, SessionShell>,
+    store: Option<Arc<dyn CommandHistoryRepository>>,
+}
+
+#[derive(Default)]
+struct SessionShell {
+    cwd: Option<String>,
+    commands: Vec<ShellCommand>,
+    /// 安装 shell 集成脚本时写入脚本，OSC 633 `E` 上报命令行需附带。
+    nonce: String,
+}
+
+impl ShellIntegration {
+    pub(super) fn forget(&self, session_id: &str) {
+        self.inner.lock().unwrap().sessions.remove(session_id);
+    }
+
+    /// 只更新仍在登记中的会话，会话结束后迟到的输出不会重新占用内存。
+    fn set_cwd(&self, session_id: &str, cwd: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(shell) = inner.sessions.get_mut(session_id) else {
+            return false;
+        };
+        if shell.cwd.as_deref() == Some(cwd) {
+            return false;
+        }
+        shell.cwd = Some(cwd.to_string());
+        true
+    }
+
+    fn cwd(&self, session_id: &str) -> Option<String> {
+        self.inner
+            .lock()
+            .unwrap()
+            .sessions
+            .get(session_id)
+            .and_then(|shell| shell.cwd.clone())
+    }
+
+    fn push(&self, command: &ShellCommand) {
+        let store = {
+            let mut inner = self.inner.lock().unwrap();
+            if let Some(shell) = inner.sessions.get_mut(&command.session_id) {
+                let commands = &mut shell.commands;
+                if commands.len() >= MAX_COMMANDS_PER_SESSION {
+                    commands.remove(0);
+                }
+                commands.push(command.clone());
+            }
+            inner.store.clone()
+        };
+        // 写数据库不占用集成状态的锁，其他会话的标记处理与查询不必等待磁盘
+        if let Some(store) = store.filter(|_| command.connection_id.is_some()) {
+            if let Err(err) = store.save(command) {
+                warn!(target: "session", session_id = %command.session_id, %err, "写入命令历史失败");
+            }
+        }
+    }
+}
+
+impl SessionManager {
+    /// 之后识别出的命令同时写入 `store`（仅限关联了连接的会话）。
+    pub fn set_command_store(&self, store: Arc<dyn CommandHistoryRepository>) {
+        self.shell.inner.lock().unwrap().store = Some(store);
+    }
+
+    /// 会话中已结束的命令，先执行的在前。
+    pub fn session_commands(&self, session_id: &str) -> Vec<ShellCommand> {
+        self.shell
+            .inner
+            .lock()
+            .unwrap()
+            .sessions
+            .get(session_id)
+            .map(|shell| shell.commands.clone())
+            .unwrap_or_default()
+    }
+
+    /// 会话的 shell 集成 nonce，安装集成脚本时写入（VS Code 脚本中的 `__vsc_nonce`）。
+    pub fn shell_integration_nonce(&self, session_id: &str) -> Option<String> {
+        self.shell
+            .inner
+            .lock()
+            .unwrap()
+            .sessions
+            .get(session_id)
+            .map(|shell| shell.nonce.clone())
+    }
+
+    /// shell 通过 OSC 7 上报的当前目录。
+    pub fn session_cwd(&self, session_id: &str) -> Option<String> {
+        self.shell.cwd(session_id)
+    }
+
+    /// 重新发送会话中最近一条命令，返回该命令文本。
+    pub async fn rerun_last_command(&self, session_id: &str) -> Result<String> {
+        let command = self
+            .session_commands(session_id)
+            .into_iter()
+            .rev()
+            .map(|entry| entry.command)
+            .find(|command| !command.is_empty())
+            .ok_or_else(|| anyhow!("该会话还没有可重新执行的命令"))?;
+        self.send_input(session_id, &format!("{command}\n")).await?;
+        Ok(command)
+    }
+
+    pub(super) fn watch_shell_integration(
+        &self,
+        events: &Events,
+        session_id: &str,
+        connection: Option<&Connection>,
+        mut output: broadcast::Receiver<OutputEvent>,
+    ) {
+        let nonce = Uuid::new_v4().simple().to_string();
+        self.shell.inner.lock().unwrap().sessions.insert(
+            session_id.to_string(),
+            SessionShell {
+                nonce: nonce.clone(),
+                ..SessionShell::default()
+            },
+        );
+        let mut tracker = CommandTracker {
+            shell: self.shell.clone(),
+            events: events.clone(),
+            session_id: session_id.to_string(),
+            connection_id: connection.map(|conn| conn.id.clone()),
+            filter: AnsiFilter::default(),
+            phase: Phase::Idle,
+            reported: None,
+            nonce,
+        };
+        tokio::spawn(async move {
+            loop {
+                match output.recv().await {
+                    Ok(OutputEvent::Data { data, .. }) => tracker.feed(&data),
+                    Ok(OutputEvent::Closed(_)) | Err(RecvError::Closed) => break,
+                    // 丢失的输出可能包含标记，放弃当前命令，等下一个提示符重新同步。
+                    Err(RecvError::Lagged(_)) => {
+                        tracker.phase = Phase::Idle;
+                        tracker.reported = None;
+                    }
+                }
+            }
+        });
+    }
+}
+
+enum Phase {
+    Idle,
+    /// `B` 之后，收集回显的输入。
+    Input(String),
+    /// `C` 之后，命令正在执行。
+    Running {
+        command: String,
+        cwd: Option<String>,
+        started_at: String,
+        started: Instant,
+    },
+}
+
+struct CommandTracker {
+    shell: ShellIntegration,
+    events: Events,
+    session_id: String,
+    connection_id: Option<String>,
+    filter: AnsiFilter,
+    phase: Phase,
+    /// shell 通过 OSC 633 `E` 上报的命令行，优先于回显的输入。
+    reported: Option<String>,
+    nonce: String,
+}
+
+enum Mark {
+    Osc(String),
+    Text(char),
+    Control(char),
+}
+
+impl CommandTracker {
+    fn feed(&mut self, data: &str) {
+        // 过滤器借用期间不能修改自身状态，先收集再逐个处理
+        let mut marks = Vec::new();
+        self.filter.feed(data, |piece| match piece {
+            AnsiPiece::Osc(payload) => marks.push(Mark::Osc(payload.to_string())),
+            AnsiPiece::Text(ch) => marks.push(Mark::Text(ch)),
+            AnsiPiece::Control(ch) => marks.push(Mark::Control(ch)),
+            AnsiPiece::Sgr(_) => {}
+        });
+        for mark in marks {
+            match mark {
+                Mark::Osc(payload) => self.handle_osc(&payload),
+                Mark::Text(ch) => {
+                    if let Phase::Input(input) = &mut self.phase {
+                        if input.len() < MAX_COMMAND_BYTES {
+                            input.push(ch);
+                        }
+                    }
+                }
+                // 行编辑器删除字符时回显退格（`\b \b` 或 `\b` 加清除到行尾）
+                Mark::Control('\u{8}' | '\u{7f}') => {
+                    if let Phase::Input(input) = &mut self.phase {
+                        input.pop();
+                    }
+                }
+                Mark::Control(_) => {}
+            }
+        }
+    }
+
+    fn handle_osc(&mut self, payload: &str) {
+        let mut fields = payload.split(';');
+        match fields.next() {
+            Some("133" | "633") => match fields.next() {
+                Some("A") => {
+                    self.finish(None);
+                    self.reported = None;
+                }
+                Some("B") => self.phase = Phase::Input(String::new()),
+                Some("C") => self.start(),
+                Some("D") => {
+                    let exit_code = fields.next().and_then(|code| code.trim().parse().ok());
+                    self.finish(exit_code);
+                }
+                Some("E") => {
+                    let command = fields.next();
+                    if fields.next() == Some(self.nonce.as_str()) {
+                        self.reported = command.map(unescape_command_line);
+                    }
+                }
+                Some("P") => {
+                    if let Some(cwd) = fields.next().and_then(|field| field.strip_prefix("Cwd=")) {
+                        self.update_cwd(unescape_command_line(cwd));
+                    }
+                }
+                _ => {}
+            },
+            Some("7") => {
+                let uri = payload
+                    .split_once(';')
+                    .map(|(_, uri)| uri)
+                    .unwrap_or_default();
+                if let Some(cwd) = parse_file_uri(uri) {
+                    self.update_cwd(cwd);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn update_cwd(&mut self, cwd: String) {
+        if self.shell.set_cwd(&self.session_id, &cwd) {
+            let _ = self.events.emit(
+                "session-cwd",
+                SessionCwdPayload {
+                    session_id: self.session_id.clone(),
+                    cwd,
+                },
+            );
+        }
+    }
+
+    fn start(&mut self) {
+        let echoed = match std::mem::replace(&mut self.phase, Phase::Idle) {
+            Phase::Input(input) => input,
+            _ => String::new(),
+        };
+        let command = self.reported.take().unwrap_or(echoed).trim().to_string();
+        self.phase = Phase::Running {
+            command,
+            cwd: self.shell.cwd(&self.session_id),
+            started_at: Utc::now().to_rfc3339(),
+            started: Instant::now(),
+        };
+    }
+
+    /// 结束正在执行的命令；未收到 `D` 就出现新提示符时退出码为空。
+    fn finish(&mut self, exit_code: Option<i32>) {
+        let Phase::Running {
+            command,
+            cwd,
+            started_at,
+            started,
+        } = std::mem::replace(&mut self.phase, Phase::Idle)
+        else {
+            return;
+        };
+        let entry = ShellCommand {
+            id: format!("cmd-{}", Uuid::new_v4().simple()),
+            session_id: self.session_id.clone(),
+            connection_id: self.connection_id.clone(),
+            command,
+            cwd,
+            started_at,
+            finished_at: Some(Utc::now().to_rfc3339()),
+            exit_code,
+            duration_ms: Some(started.elapsed().as_millis() as u64),
+        };
+        self.shell.push(&entry);
+        let _ = self.events.emit(
+            "session-command",
+            SessionCommandPayload {
+                session_id: self.session_id.clone(),
+                command: entry,
+            },
+        );
+    }
+}
+
+/// 还原 OSC 633 中转义的文本：`\\` 为反斜杠，`\xHH` 为对应字节（分号与控制字符都以此形式出现）。
+fn unescape_command_line(escaped: &str) -> String {
+    let bytes = escaped.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 2..index + 4)
+            .filter(|_| bytes.get(index + 1) == Some(&b'x'))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], bytes.get(index + 1), hex) {
+            (b'\\', _, Some(byte)) => {
+                decoded.push(byte);
+                index += 4;
+            }
+            (b'\\', Some(b'\\'), None) => {
+                decoded.push(b'\\');
+                index += 2;
+            }
+            (byte, _, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// 解析 `file://host/path` 形式的 OSC 7 内容，返回解码后的路径。
+fn parse_file_uri(uri: &str) -> Option<String> {
+    let rest = uri.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
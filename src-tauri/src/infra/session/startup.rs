@@ -0,0 +1,119 @@
+use std::io::Read;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use crossbeam_channel::Receiver;
+use regex::Regex;
+use ssh2::Channel as SshChannel;
+
+use crate::domain::models::{is_env_name, StartupActions, StartupStep};
+
+use super::{is_would_block, shell_quote, write_channel, SessionInput};
+
+const DEFAULT_EXPECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 启动动作执行期间用户关闭了会话。
+#[derive(Debug, thiserror::Error)]
+#[error("用户主动关闭")]
+pub(super) struct StartupClosed;
+
+/// 启动动作执行期间到达的会话输入：关闭请求立即生效，其余输入暂存，待启动动作结束后由主循环处理。
+pub(super) struct StartupInputs<'a> {
+    pub(super) input_rx: &'a Receiver<SessionInput>,
+    pub(super) deferred: Vec<SessionInput>,
+}
+
+impl StartupInputs<'_> {
+    fn poll(&mut self) -> Result<()> {
+        while let Ok(input) = self.input_rx.try_recv() {
+            match input {
+                SessionInput::Close => return Err(StartupClosed.into()),
+                other => self.deferred.push(other),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 在已建立的 shell 上执行连接的启动动作。
+///
+/// `rejected_env` 为 setenv 被拒绝的变量，会先以 `export` 补发；等待期间读到的输出交给 `on_output`，
+/// 收到关闭请求时返回 [`StartupClosed`]。
+pub(super) fn run_startup_actions(
+    channel: &mut SshChannel,
+    actions: &StartupActions,
+    rejected_env: &[(String, String)],
+    inputs: &mut StartupInputs<'_>,
+    on_output: &mut dyn FnMut(&str),
+) -> Result<()> {
+    // 变量名原样拼进 export 命令，不合法时整组启动动作都不发送
+    if let Some((key, _)) = rejected_env.iter().find(|(key, _)| !is_env_name(key)) {
+        bail!("无效的环境变量名: {key}");
+    }
+    for (key, value) in rejected_env {
+        write_channel(channel, &format!("export {key}={}\n", shell_quote(value)))?;
+    }
+    if let Some(dir) = &actions.working_dir {
+        write_channel(channel, &format!("cd {}\n", shell_quote(dir)))?;
+    }
+
+    let mut pending = String::new();
+    for step in &actions.steps {
+        match step {
+            StartupStep::Command { command } => {
+                write_channel(channel, &format!("{command}\n"))?;
+            }
+            StartupStep::Expect {
+                pattern,
+                send,
+                timeout_ms,
+            } => {
+                let regex = Regex::new(pattern)
+                    .map_err(|err| anyhow!("无效的匹配规则 {pattern}: {err}"))?;
+                let timeout = timeout_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or(DEFAULT_EXPECT_TIMEOUT);
+                expect(channel, &regex, timeout, &mut pending, inputs, on_output)?;
+                write_channel(channel, &format!("{send}\n"))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 读取输出直到 `regex` 匹配，匹配位置之前的缓冲会被丢弃，避免下一步重复命中。
+fn expect(
+    channel: &mut SshChannel,
+    regex: &Regex,
+    timeout: Duration,
+    pending: &mut String,
+    inputs: &mut StartupInputs<'_>,
+    on_output: &mut dyn FnMut(&str),
+) -> Result<()> {
+    let started = Instant::now();
+    let mut buffer = [0u8; 4096];
+    loop {
+        inputs.poll()?;
+        if let Some(found) = regex.find(pending) {
+            pending.drain(..found.end());
+            return Ok(());
+        }
+        if channel.eof() {
+            return Err(anyhow!("等待 {} 时远端已关闭", regex.as_str()));
+        }
+        if started.elapsed() >= timeout {
+            return Err(anyhow!("等待 {} 超时", regex.as_str()));
+        }
+        match channel.read(&mut buffer) {
+            Ok(0) => thread::sleep(Duration::from_millis(12)),
+            Ok(size) => {
+                let chunk = String::from_utf8_lossy(&buffer[..size]).to_string();
+                on_output(&chunk);
+                pending.push_str(&chunk);
+            }
+            Err(err) if is_would_block(&err) => thread::sleep(Duration::from_millis(12)),
+            Err(err) => return Err(anyhow!("read error: {err}")),
+        }
+    }
+}
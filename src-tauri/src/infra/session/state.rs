@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::domain::models::{SessionErrorCode, SessionState, SessionStateReason};
+
+use super::events::Events;
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct SessionStatePayload {
+    pub session_id: String,
+    pub state: SessionState,
+    pub reason: Option<SessionStateReason>,
+    pub error_code: Option<SessionErrorCode>,
+    pub message: Option<String>,
+    pub updated_at: String,
+}
+
+/// 一次状态迁移附带的原因；进入 connecting / connected 等状态时通常为空。
+#[derive(Clone, Debug, Default)]
+pub struct StateChange {
+    pub reason: Option<SessionStateReason>,
+    pub error_code: Option<SessionErrorCode>,
+    pub message: Option<String>,
+}
+
+impl StateChange {
+    pub fn reason(reason: SessionStateReason, message: impl Into<String>) -> Self {
+        Self {
+            reason: Some(reason),
+            error_code: None,
+            message: Some(message.into()),
+        }
+    }
+
+    pub fn failure(code: SessionErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            reason: Some(SessionStateReason::Error),
+            error_code: Some(code),
+            message: Some(message.into()),
+        }
+    }
+}
+
+/// 会话结束后终态在状态表中保留的时长，便于前端在收到 `session-closed` 之后仍能同步到原因。
+const TERMINAL_STATE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// 会话状态表。SSH 线程与 tokio 任务都会写入，因此使用同步锁。
+#[derive(Clone, Default)]
+pub struct SessionStateStore {
+    states: Arc<Mutex<HashMap<String, StateEntry>>>,
+}
+
+struct StateEntry {
+    payload: SessionStatePayload,
+    /// 进入终态的时间，超过 [`TERMINAL_STATE_TTL`] 后移除。
+    finished_at: Option<Instant>,
+}
+
+impl SessionStateStore {
+    /// 记录并推送一次状态迁移；状态未变化时忽略。终态之后只接受 reconnecting
+    /// （守护进程中仍在运行的会话重新附加），其余迁移一律忽略。
+    pub fn transition(
+        &self,
+        events: &Events,
+        session_id: &str,
+        state: SessionState,
+        change: StateChange,
+    ) -> bool {
+        let payload = {
+            let mut states = self.states.lock().unwrap();
+            prune(&mut states);
+            if let Some(current) = states.get(session_id).map(|entry| entry.payload.state) {
+                let reopening = state == SessionState::Reconnecting;
+                if (current.is_terminal() && !reopening) || current == state {
+                    return false;
+                }
+            }
+            let payload = SessionStatePayload {
+                session_id: session_id.to_string(),
+                state,
+                reason: change.reason,
+                error_code: change.error_code,
+                message: change.message,
+                updated_at: Utc::now().to_rfc3339(),
+            };
+            states.insert(
+                session_id.to_string(),
+                StateEntry {
+                    payload: payload.clone(),
+                    finished_at: state.is_terminal().then(Instant::now),
+                },
+            );
+            payload
+        };
+        let _ = events.emit("session-state", payload);
+        true
+    }
+
+    pub fn get(&self, session_id: &str) -> Option<SessionStatePayload> {
+        let mut states = self.states.lock().unwrap();
+        prune(&mut states);
+        states.get(session_id).map(|entry| entry.payload.clone())
+    }
+}
+
+/// 移除进入终态已超过保留时长的会话；运行中的会话不受影响。
+fn prune(states: &mut HashMap<String, StateEntry>) {
+    states.retain(|_, entry| {
+        entry
+            .finished_at
+            .is_none_or(|finished_at| finished_at.elapsed() < TERMINAL_STATE_TTL)
+    });
+}
+
+/// 带错误码的会话失败，供会话线程在结束时生成 failed 状态。
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct SessionFailure {
+    pub code: SessionErrorCode,
+    pub message: String,
+}
+
+impl SessionFailure {
+    pub fn new(code: SessionErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn code_of(err: &anyhow::Error) -> SessionErrorCode {
+        err.downcast_ref::<Self>()
+            .map(|failure| failure.code)
+            .unwrap_or(SessionErrorCode::Unknown)
+    }
+}
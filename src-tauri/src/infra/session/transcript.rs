@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+use crate::domain::models::{TranscriptFormat, TranscriptSettings};
+
+use super::ansi::{self, AnsiFilter, AnsiPiece};
+use super::output::OutputEvent;
+use super::SessionManager;
+
+/// 去除转义序列、带时间戳的纯文本日志。
+const PLAIN_EXT: &str = "log";
+/// 同样带时间戳但保留 SGR 颜色的副本，仅用于导出 HTML。
+const COLOR_EXT: &str = "ansi";
+
+/// 会话文本日志：每个会话一组文件，按大小轮转为 `<session>.<n>.log`，按修改时间清理过期文件（启动、修改配置与轮转时）。
+#[derive(Clone, Default)]
+pub struct TranscriptLogger {
+    inner: Arc<Mutex<LoggerInner>>,
+}
+
+#[derive(Default)]
+struct LoggerInner {
+    settings: TranscriptSettings,
+    settings_path: Option<PathBuf>,
+    default_dir: PathBuf,
+    files: HashMap<String, TranscriptFile>,
+}
+
+impl LoggerInner {
+    fn dir(&self) -> PathBuf {
+        self.settings
+            .directory
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| self.default_dir.clone())
+    }
+}
+
+struct TranscriptFile {
+    dir: PathBuf,
+    session_id: String,
+    plain: BufWriter<File>,
+    color: BufWriter<File>,
+    written: u64,
+    parts: u32,
+    filter: AnsiFilter,
+    at_line_start: bool,
+}
+
+impl TranscriptFile {
+    fn create(dir: &Path, session_id: &str, title: &str) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let mut file = Self {
+            dir: dir.to_path_buf(),
+            session_id: session_id.to_string(),
+            plain: open_append(&part_path(dir, session_id, None, PLAIN_EXT))?,
+            color: open_append(&part_path(dir, session_id, None, COLOR_EXT))?,
+            written: 0,
+            parts: 0,
+            filter: AnsiFilter::default(),
+            at_line_start: true,
+        };
+        let header = format!(
+            "# {title} ({session_id}) {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S")
+        );
+        file.write_both(&header, &header)?;
+        Ok(file)
+    }
+
+    /// 写入一段输出，返回是否发生了轮转。
+    fn append(&mut self, data: &str, max_bytes: u64) -> Result<bool> {
+        let mut plain = String::with_capacity(data.len());
+        let mut color = String::with_capacity(data.len());
+        let mut at_line_start = self.at_line_start;
+        self.filter.feed(data, |piece| match piece {
+            AnsiPiece::Text(ch) => {
+                if at_line_start && ch != '\n' {
+                    let stamp = format!("[{}] ", Local::now().format("%Y-%m-%d %H:%M:%S%.3f"));
+                    plain.push_str(&stamp);
+                    color.push_str(&stamp);
+                }
+                at_line_start = ch == '\n';
+                plain.push(ch);
+                color.push(ch);
+            }
+            AnsiPiece::Sgr(params) => {
+                color.push_str("\x1b[");
+                color.push_str(params);
+                color.push('m');
+            }
+            AnsiPiece::Osc(_) | AnsiPiece::Control(_) => {}
+        });
+        self.at_line_start = at_line_start;
+        self.write_both(&plain, &color)?;
+        if max_bytes > 0 && self.written >= max_bytes {
+            self.rotate()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn write_both(&mut self, plain: &str, color: &str) -> Result<()> {
+        self.plain.write_all(plain.as_bytes())?;
+        self.color.write_all(color.as_bytes())?;
+        // 带颜色的副本多出 SGR 序列，是否轮转以两份中较大的一份为准
+        self.written += plain.len().max(color.len()) as u64;
+        Ok(())
+    }
+
+    /// 把当前文件改名为下一个分片号并重新打开。
+    fn rotate(&mut self) -> Result<()> {
+        self.flush()?;
+        self.parts += 1;
+        for ext in [PLAIN_EXT, COLOR_EXT] {
+            fs::rename(
+                part_path(&self.dir, &self.session_id, None, ext),
+                part_path(&self.dir, &self.session_id, Some(self.parts), ext),
+            )?;
+        }
+        self.plain = open_append(&part_path(&self.dir, &self.session_id, None, PLAIN_EXT))?;
+        self.color = open_append(&part_path(&self.dir, &self.session_id, None, COLOR_EXT))?;
+        self.written = 0;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.plain.flush()?;
+        self.color.flush()?;
+        Ok(())
+    }
+}
+
+impl TranscriptLogger {
+    /// 读取 `settings_path` 中的配置（不存在时使用默认值）并清理过期日志。
+    pub fn init(&self, settings_path: PathBuf, default_dir: PathBuf) -> Result<()> {
+        let settings = match fs::read_to_string(&settings_path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .with_context(|| format!("会话日志配置格式错误: {}", settings_path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => TranscriptSettings::default(),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("无法读取会话日志配置: {}", settings_path.display()))
+            }
+        };
+        let mut inner = self.inner.lock().unwrap();
+        inner.settings = settings;
+        inner.settings_path = Some(settings_path);
+        inner.default_dir = default_dir;
+        cleanup(&inner.dir(), inner.settings.retention_days)
+    }
+
+    /// 日志开启时订阅会话输出并写入文件，直到会话结束。
+    pub(super) fn record(
+        &self,
+        session_id: &str,
+        title: &str,
+        mut output: broadcast::Receiver<OutputEvent>,
+    ) {
+        if !self.open(session_id, title) {
+            return;
+        }
+        let logger = self.clone();
+        let session_id = session_id.to_string();
+        tokio::spawn(async move {
+            loop {
+                match output.recv().await {
+                    Ok(OutputEvent::Data { data, .. }) => logger.append(&session_id, &data),
+                    Ok(OutputEvent::Closed(_)) | Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(skipped)) => {
+                        logger.append(&session_id, &format!("\n[已丢弃 {skipped} 段输出]\n"));
+                    }
+                }
+            }
+            logger.close(&session_id);
+        });
+    }
+
+    fn open(&self, session_id: &str, title: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.settings.enabled {
+            return false;
+        }
+        match TranscriptFile::create(&inner.dir(), session_id, title) {
+            Ok(file) => {
+                inner.files.insert(session_id.to_string(), file);
+                true
+            }
+            Err(err) => {
+                warn!(target: "transcript", session_id, %err, "无法创建会话日志");
+                false
+            }
+        }
+    }
+
+    fn append(&self, session_id: &str, data: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        let max_bytes = inner.settings.max_file_bytes;
+        let retention_days = inner.settings.retention_days;
+        let Some(file) = inner.files.get_mut(session_id) else {
+            return;
+        };
+        match file.append(data, max_bytes) {
+            // 长时间运行的会话不重启也会清理过期日志
+            Ok(true) => {
+                if let Err(err) = cleanup(&file.dir, retention_days) {
+                    warn!(target: "transcript", session_id, %err, "清理过期日志失败");
+                }
+            }
+            Ok(false) => {}
+            Err(err) => {
+                warn!(target: "transcript", session_id, %err, "写入会话日志失败");
+                inner.files.remove(session_id);
+            }
+        }
+    }
+
+    fn close(&self, session_id: &str) {
+        if let Some(mut file) = self.inner.lock().unwrap().files.remove(session_id) {
+            let _ = file.flush();
+        }
+    }
+}
+
+impl SessionManager {
+    pub fn transcript_settings(&self) -> TranscriptSettings {
+        self.transcripts.inner.lock().unwrap().settings.clone()
+    }
+
+    /// 保存新配置；只影响之后创建的会话，已在记录的会话继续写入原目录。
+    pub fn update_transcript_settings(&self, settings: TranscriptSettings) -> Result<()> {
+        let mut inner = self.transcripts.inner.lock().unwrap();
+        if let Some(path) = &inner.settings_path {
+            fs::write(path, serde_json::to_string_pretty(&settings)?)?;
+        }
+        inner.settings = settings;
+        cleanup(&inner.dir(), inner.settings.retention_days)
+    }
+
+    /// 合并会话的全部分片并按格式导出，会话仍在运行时先落盘已缓冲的内容。
+    pub fn export_transcript(
+        &self,
+        session_id: &str,
+        format: TranscriptFormat,
+        target: &Path,
+    ) -> Result<()> {
+        // 会话 ID 会拼进文件路径，只接受本模块生成的格式
+        if !is_session_id(session_id) {
+            return Err(anyhow!("无效的会话 ID"));
+        }
+        let dir = {
+            let mut inner = self.transcripts.inner.lock().unwrap();
+            match inner.files.get_mut(session_id) {
+                Some(file) => {
+                    file.flush()?;
+                    file.dir.clone()
+                }
+                None => inner.dir(),
+            }
+        };
+        let ext = match format {
+            TranscriptFormat::Text => PLAIN_EXT,
+            TranscriptFormat::Html => COLOR_EXT,
+        };
+        let content = read_parts(&dir, session_id, ext)?;
+        let output = match format {
+            TranscriptFormat::Text => content,
+            TranscriptFormat::Html => ansi::to_html(session_id, &content),
+        };
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(target, output)?;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> Result<BufWriter<File>> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(BufWriter::new(file))
+}
+
+fn part_path(dir: &Path, session_id: &str, part: Option<u32>, ext: &str) -> PathBuf {
+    match part {
+        Some(part) => dir.join(format!("{session_id}.{part}.{ext}")),
+        None => dir.join(format!("{session_id}.{ext}")),
+    }
+}
+
+/// 会话 ID 形如 `session-<32 位十六进制>`，与 `SessionManager` 和守护进程生成的一致。
+fn is_session_id(id: &str) -> bool {
+    id.strip_prefix("session-")
+        .is_some_and(|rest| rest.len() == 32 && rest.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// 是否为 `part_path` 生成的文件名：`<session>.<ext>` 或 `<session>.<n>.<ext>`。
+fn is_transcript_file(name: &str) -> bool {
+    let Some(stem) = name
+        .strip_suffix(&format!(".{PLAIN_EXT}"))
+        .or_else(|| name.strip_suffix(&format!(".{COLOR_EXT}")))
+    else {
+        return false;
+    };
+    let session_id = match stem.split_once('.') {
+        Some((session_id, part))
+            if !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            session_id
+        }
+        Some(_) => return false,
+        None => stem,
+    };
+    is_session_id(session_id)
+}
+
+fn read_parts(dir: &Path, session_id: &str, ext: &str) -> Result<String> {
+    let mut content = String::new();
+    let mut part = 1;
+    loop {
+        let path = part_path(dir, session_id, Some(part), ext);
+        if !path.exists() {
+            break;
+        }
+        content.push_str(&fs::read_to_string(path)?);
+        part += 1;
+    }
+    let current = part_path(dir, session_id, None, ext);
+    if current.exists() {
+        content.push_str(&fs::read_to_string(current)?);
+    } else if part == 1 {
+        return Err(anyhow!("没有找到该会话的日志"));
+    }
+    Ok(content)
+}
+
+/// 删除目录中修改时间超过保留天数的日志文件；日志目录可由用户指定，只处理本模块创建的文件。
+fn cleanup(dir: &Path, retention_days: u32) -> Result<()> {
+    if retention_days == 0 || !dir.exists() {
+        return Ok(());
+    }
+    let max_age = Duration::from_secs(u64::from(retention_days) * 24 * 60 * 60);
+    let now = SystemTime::now();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_transcript = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(is_transcript_file);
+        if !is_transcript {
+            continue;
+        }
+        let expired = fs::metadata(&path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > max_age);
+        if expired {
+            let _ = fs::remove_file(&path);
+        }
+    }
+    Ok(())
+}
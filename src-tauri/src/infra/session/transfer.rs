@@ -0,0 +1,557 @@
+//! 会话内嵌文件传输：在 SSH 输出字节流中识别 ZMODEM（`rz`/`sz`）与 trzsz（`trz`/`tsz`）的起始序列，
+//! 把会话切换到传输模式，等前端给出本地文件或保存目录后在 SSH 线程内完成协议交互，结束后恢复普通输出。
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use crossbeam_channel::{Receiver, RecvTimeoutError, TryRecvError};
+use uuid::Uuid;
+
+use crate::domain::models::{TransferDirection, TransferProtocol, TransferState};
+
+use super::events::Events;
+use super::{is_would_block, trzsz, zmodem, SessionInput, SessionKind, SessionManager};
+
+/// 等待前端确认的最长时间，超时按取消处理；远端 `rz`/`sz` 自身的等待也只有一分钟左右。
+const DECISION_TIMEOUT: Duration = Duration::from_secs(60);
+/// 进度事件的最短间隔。
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(serde::Serialize, Clone)]
+pub struct SessionTransferPayload {
+    pub session_id: String,
+    pub transfer_id: String,
+    pub protocol: TransferProtocol,
+    pub direction: TransferDirection,
+    pub state: TransferState,
+    /// 已完成时为本地文件路径（下载）或文件名（上传）。
+    pub files: Vec<String>,
+    pub message: Option<String>,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct SessionTransferProgressPayload {
+    pub session_id: String,
+    pub transfer_id: String,
+    pub file_name: String,
+    pub file_index: usize,
+    pub bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// 协议实现上报的单个文件进度。
+pub struct TransferProgress {
+    pub file_name: String,
+    pub file_index: usize,
+    pub bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// 用户取消或会话关闭导致传输中止。
+#[derive(Debug)]
+pub struct TransferCancelled;
+
+impl fmt::Display for TransferCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("传输已取消")
+    }
+}
+
+impl std::error::Error for TransferCancelled {}
+
+/// 前端对待确认传输的答复。
+pub(super) enum TransferAction {
+    /// 上传时为本地文件，下载时为唯一的保存目录。
+    Accept(Vec<PathBuf>),
+    Cancel,
+}
+
+const MARKERS: &[(&[u8], TransferProtocol, TransferDirection)] = &[
+    (
+        b"**\x18B00",
+        TransferProtocol::Zmodem,
+        TransferDirection::Download,
+    ),
+    (
+        b"**\x18B01",
+        TransferProtocol::Zmodem,
+        TransferDirection::Upload,
+    ),
+    (
+        b"::TRZSZ:TRANSFER:S:",
+        TransferProtocol::Trzsz,
+        TransferDirection::Download,
+    ),
+    (
+        b"::TRZSZ:TRANSFER:R:",
+        TransferProtocol::Trzsz,
+        TransferDirection::Upload,
+    ),
+    (
+        b"::TRZSZ:TRANSFER:D:",
+        TransferProtocol::Trzsz,
+        TransferDirection::Upload,
+    ),
+];
+
+/// 识别出的起始序列，`pending` 为序列本身及其后已读到的字节，交给协议实现继续解析。
+pub struct DetectedTransfer {
+    pub protocol: TransferProtocol,
+    pub direction: TransferDirection,
+    /// 远端要求上传目录（`trz -d`），目前只支持单个文件。
+    pub directory: bool,
+    pub pending: Vec<u8>,
+}
+
+/// 在输出字节流中查找起始序列；可能是序列开头的尾部字节会暂存到下一次读取。
+#[derive(Default)]
+pub struct TransferDetector {
+    carry: Vec<u8>,
+}
+
+impl TransferDetector {
+    /// 返回可以照常显示的字节，以及识别出的传输请求。
+    pub fn scan(&mut self, chunk: &[u8]) -> (Vec<u8>, Option<DetectedTransfer>) {
+        let mut data = std::mem::take(&mut self.carry);
+        data.extend_from_slice(chunk);
+        let found = MARKERS
+            .iter()
+            .filter_map(|(marker, protocol, direction)| {
+                find(&data, marker).map(|at| (at, *marker, *protocol, *direction))
+            })
+            .min_by_key(|(at, ..)| *at);
+        if let Some((at, marker, protocol, direction)) = found {
+            let pending = data.split_off(at);
+            return (
+                data,
+                Some(DetectedTransfer {
+                    protocol,
+                    direction,
+                    directory: marker.ends_with(b"D:"),
+                    pending,
+                }),
+            );
+        }
+        let keep = (1..data.len().min(longest_marker()))
+            .rev()
+            .find(|len| {
+                let tail = &data[data.len() - len..];
+                MARKERS.iter().any(|(marker, ..)| marker.starts_with(tail))
+            })
+            .unwrap_or(0);
+        self.carry = data.split_off(data.len() - keep);
+        (data, None)
+    }
+
+    /// 暂无新输出时交出暂存的字节，避免提示符末尾的 `*` 等字符迟迟不显示。
+    pub fn flush(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.carry)
+    }
+}
+
+fn longest_marker() -> usize {
+    MARKERS
+        .iter()
+        .map(|(marker, ..)| marker.len())
+        .max()
+        .unwrap_or(0)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// 协议层使用的收发通道：带超时的读取、处理非阻塞写入，并在等待期间检查取消请求。
+pub struct Link<'a, S> {
+    stream: &'a mut S,
+    buffer: VecDeque<u8>,
+    cancel: &'a mut dyn FnMut() -> bool,
+    pub received: u64,
+    pub sent: u64,
+}
+
+impl<'a, S: Read + Write> Link<'a, S> {
+    pub fn new(stream: &'a mut S, pending: Vec<u8>, cancel: &'a mut dyn FnMut() -> bool) -> Self {
+        Self {
+            stream,
+            buffer: pending.into(),
+            cancel,
+            received: 0,
+            sent: 0,
+        }
+    }
+
+    pub fn read_byte(&mut self, timeout: Duration) -> Result<u8> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(byte) = self.buffer.pop_front() {
+                return Ok(byte);
+            }
+            if !self.poll()? {
+                if (self.cancel)() {
+                    return Err(TransferCancelled.into());
+                }
+                if Instant::now() >= deadline {
+                    bail!("等待对方响应超时");
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+
+    /// 读一次当前可用的数据放入缓冲，不等待；返回是否读到了数据。
+    pub fn poll(&mut self) -> Result<bool> {
+        let mut chunk = [0u8; 8192];
+        match self.stream.read(&mut chunk) {
+            Ok(0) => bail!("远端已关闭连接"),
+            Ok(size) => {
+                self.received += size as u64;
+                self.buffer.extend(&chunk[..size]);
+                Ok(true)
+            }
+            Err(err) if is_would_block(&err) => Ok(false),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => Ok(false),
+            Err(err) => Err(anyhow!("读取失败: {err}")),
+        }
+    }
+
+    /// 已读入但尚未消费的字节。
+    pub fn buffered(&self) -> &VecDeque<u8> {
+        &self.buffer
+    }
+
+    pub fn discard_buffered(&mut self) {
+        self.buffer.clear();
+    }
+
+    pub fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        let mut remaining = data;
+        while !remaining.is_empty() {
+            match self.stream.write(remaining) {
+                Ok(0) => bail!("远端已关闭连接"),
+                Ok(written) => {
+                    self.sent += written as u64;
+                    remaining = &remaining[written..];
+                }
+                Err(err) if is_would_block(&err) => self.wait()?,
+                Err(err) => return Err(anyhow!("写入失败: {err}")),
+            }
+        }
+        loop {
+            match self.stream.flush() {
+                Ok(()) => return Ok(()),
+                Err(err) if is_would_block(&err) => self.wait()?,
+                Err(err) => return Err(anyhow!("刷新失败: {err}")),
+            }
+        }
+    }
+
+    fn wait(&mut self) -> Result<()> {
+        if (self.cancel)() {
+            return Err(TransferCancelled.into());
+        }
+        thread::sleep(POLL_INTERVAL);
+        Ok(())
+    }
+}
+
+/// 传输期间对会话输入的处理：普通按键会破坏协议数据，直接丢弃；记住关闭请求留给会话循环处理。
+pub(super) struct TransferControl<'a> {
+    input_rx: &'a Receiver<SessionInput>,
+    pub(super) close_requested: bool,
+}
+
+impl<'a> TransferControl<'a> {
+    pub(super) fn new(input_rx: &'a Receiver<SessionInput>) -> Self {
+        Self {
+            input_rx,
+            close_requested: false,
+        }
+    }
+
+    fn wait_decision(&mut self) -> Option<Vec<PathBuf>> {
+        let deadline = Instant::now() + DECISION_TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.input_rx.recv_timeout(timeout) {
+                Ok(SessionInput::Transfer(TransferAction::Accept(paths))) => return Some(paths),
+                Ok(SessionInput::Transfer(TransferAction::Cancel)) => return None,
+                Ok(SessionInput::Close) => {
+                    self.close_requested = true;
+                    return None;
+                }
+                Ok(SessionInput::Data(_)) | Ok(SessionInput::Agent(_)) => {}
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => {
+                    return None
+                }
+            }
+        }
+    }
+
+    fn cancelled(&mut self) -> bool {
+        loop {
+            match self.input_rx.try_recv() {
+                Ok(SessionInput::Transfer(TransferAction::Cancel)) => return true,
+                Ok(SessionInput::Close) => {
+                    self.close_requested = true;
+                    return true;
+                }
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => return true,
+            }
+        }
+    }
+}
+
+/// 正在进行或等待确认的传输，每个会话至多一个。
+#[derive(Clone, Default)]
+pub struct ActiveTransfers {
+    inner: Arc<Mutex<HashMap<String, SessionTransferPayload>>>,
+}
+
+impl ActiveTransfers {
+    fn update(&self, events: &Events, payload: &SessionTransferPayload) {
+        let mut inner = self.inner.lock().unwrap();
+        match payload.state {
+            TransferState::Pending | TransferState::Running => {
+                inner.insert(payload.session_id.clone(), payload.clone());
+            }
+            _ => {
+                inner.remove(&payload.session_id);
+            }
+        }
+        drop(inner);
+        let _ = events.emit("session-transfer", payload.clone());
+    }
+
+    pub(super) fn forget(&self, session_id: &str) {
+        self.inner.lock().unwrap().remove(session_id);
+    }
+}
+
+impl SessionManager {
+    /// 会话当前正在进行或等待确认的文件传输。
+    pub fn active_transfer(&self, session_id: &str) -> Option<SessionTransferPayload> {
+        self.transfers
+            .inner
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .cloned()
+    }
+
+    /// 确认待处理的传输：上传时给出本地文件，下载时给出唯一的保存目录。
+    pub async fn accept_transfer(&self, session_id: &str, paths: Vec<PathBuf>) -> Result<()> {
+        let transfer = self
+            .active_transfer(session_id)
+            .filter(|transfer| transfer.state == TransferState::Pending)
+            .ok_or_else(|| anyhow!("当前没有待确认的文件传输"))?;
+        match transfer.direction {
+            TransferDirection::Upload => {
+                if paths.is_empty() {
+                    bail!("请选择要上传的文件");
+                }
+                if let Some(path) = paths.iter().find(|path| !path.is_file()) {
+                    bail!("不是可上传的文件: {}", path.display());
+                }
+            }
+            TransferDirection::Download => {
+                if paths.len() != 1 || !paths[0].is_dir() {
+                    bail!("请选择一个已存在的保存目录");
+                }
+            }
+        }
+        self.send_transfer_action(session_id, TransferAction::Accept(paths))
+            .await
+    }
+
+    pub async fn cancel_transfer(&self, session_id: &str) -> Result<()> {
+        if self.active_transfer(session_id).is_none() {
+            bail!("当前没有进行中的文件传输");
+        }
+        self.send_transfer_action(session_id, TransferAction::Cancel)
+            .await
+    }
+
+    async fn send_transfer_action(&self, session_id: &str, action: TransferAction) -> Result<()> {
+        let sessions = self.sessions.lock().await;
+        let handle = sessions
+            .get(session_id)
+            .ok_or_else(|| anyhow!("session not found"))?;
+        match &handle.kind {
+            SessionKind::Ssh2 { tx } => tx
+                .send(SessionInput::Transfer(action))
+                .map_err(|err| anyhow!("发送传输指令失败: {err}")),
+            SessionKind::Local { .. } => bail!("本地 shell 不支持文件传输"),
+        }
+    }
+
+    /// 在 SSH 线程内完成一次传输，期间会话输出暂停；结果经 `session-transfer` 推送并写入终端。
+    pub(super) fn run_transfer<S: Read + Write>(
+        &self,
+        events: &Events,
+        session_id: &str,
+        stream: &mut S,
+        control: &mut TransferControl<'_>,
+        detected: DetectedTransfer,
+    ) {
+        let mut payload = SessionTransferPayload {
+            session_id: session_id.to_string(),
+            transfer_id: format!("transfer-{}", Uuid::new_v4().simple()),
+            protocol: detected.protocol,
+            direction: detected.direction,
+            state: TransferState::Pending,
+            files: Vec::new(),
+            message: None,
+        };
+        self.transfers.update(events, &payload);
+        let paths = control.wait_decision();
+
+        let transfer_id = payload.transfer_id.clone();
+        let mut last_progress: Option<Instant> = None;
+        let mut progress = |progress: TransferProgress| {
+            let done = progress.bytes >= progress.total_bytes;
+            if !done && last_progress.is_some_and(|at| at.elapsed() < PROGRESS_INTERVAL) {
+                return;
+            }
+            last_progress = Some(Instant::now());
+            let _ = events.emit(
+                "session-transfer-progress",
+                SessionTransferProgressPayload {
+                    session_id: session_id.to_string(),
+                    transfer_id: transfer_id.clone(),
+                    file_name: progress.file_name,
+                    file_index: progress.file_index,
+                    bytes: progress.bytes,
+                    total_bytes: progress.total_bytes,
+                },
+            );
+        };
+        let mut cancel = || control.cancelled();
+        let mut link = Link::new(stream, detected.pending, &mut cancel);
+        let result = match paths {
+            None => Err(TransferCancelled.into()),
+            Some(_) if detected.directory => Err(anyhow!("暂不支持上传目录")),
+            Some(paths) => {
+                payload.state = TransferState::Running;
+                self.transfers.update(events, &payload);
+                run_protocol(
+                    &mut link,
+                    payload.protocol,
+                    payload.direction,
+                    &paths,
+                    &mut progress,
+                )
+            }
+        };
+        if let Err(err) = &result {
+            abort(&mut link, payload.protocol, &err.to_string());
+        }
+        self.history
+            .add_bytes_in(session_id, link.received as usize);
+        self.history.add_bytes_out(session_id, link.sent as usize);
+
+        let notice = match result {
+            Ok(files) => {
+                payload.state = TransferState::Completed;
+                let notice = format!("\r\n文件传输完成，共 {} 个文件\r\n", files.len());
+                payload.files = files;
+                notice
+            }
+            Err(err) if err.is::<TransferCancelled>() => {
+                payload.state = TransferState::Cancelled;
+                "\r\n文件传输已取消\r\n".to_string()
+            }
+            Err(err) => {
+                payload.state = TransferState::Failed;
+                payload.message = Some(err.to_string());
+                format!("\r\n文件传输失败: {err}\r\n")
+            }
+        };
+        self.transfers.update(events, &payload);
+        self.publish_output(session_id, "stdout", &notice);
+    }
+}
+
+fn run_protocol<S: Read + Write>(
+    link: &mut Link<'_, S>,
+    protocol: TransferProtocol,
+    direction: TransferDirection,
+    paths: &[PathBuf],
+    progress: &mut dyn FnMut(TransferProgress),
+) -> Result<Vec<String>> {
+    match (protocol, direction) {
+        (TransferProtocol::Zmodem, TransferDirection::Upload) => {
+            zmodem::send(link, paths, progress)?;
+            Ok(file_names(paths))
+        }
+        (TransferProtocol::Zmodem, TransferDirection::Download) => {
+            zmodem::receive(link, &paths[0], progress)
+        }
+        (TransferProtocol::Trzsz, TransferDirection::Upload) => {
+            trzsz::upload(link, paths, progress)?;
+            Ok(file_names(paths))
+        }
+        (TransferProtocol::Trzsz, TransferDirection::Download) => {
+            trzsz::download(link, &paths[0], progress)
+        }
+    }
+}
+
+/// 通知远端放弃传输，远端程序随即退出并回到 shell。
+fn abort<S: Read + Write>(link: &mut Link<'_, S>, protocol: TransferProtocol, reason: &str) {
+    let _ = match protocol {
+        TransferProtocol::Zmodem => zmodem::abort(link),
+        TransferProtocol::Trzsz => trzsz::abort(link, reason),
+    };
+}
+
+fn file_names(paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|path| file_name(path).unwrap_or_default())
+        .collect()
+}
+
+pub(super) fn file_name(path: &Path) -> Option<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// 远端给出的文件名只取最后一段，保存目录下已有同名文件时追加序号。
+/// 最后一段必须是单个普通路径组件，`C:evil` 这类带盘符前缀的名字在 Windows 上会跳出保存目录。
+pub(super) fn local_target(dir: &Path, remote_name: &str) -> Result<PathBuf> {
+    let name = remote_name
+        .rsplit(['/', '\\'])
+        .next()
+        .filter(|name| {
+            let mut components = Path::new(name).components();
+            matches!(
+                (components.next(), components.next()),
+                (Some(Component::Normal(_)), None)
+            )
+        })
+        .ok_or_else(|| anyhow!("远端文件名无效: {remote_name}"))?;
+    let candidate = dir.join(name);
+    if !candidate.exists() {
+        return Ok(candidate);
+    }
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{extension}")),
+        _ => (name, String::new()),
+    };
+    (1..)
+        .map(|index| dir.join(format!("{stem} ({index}){extension}")))
+        .find(|path| !path.exists())
+        .ok_or_else(|| anyhow!("无法生成保存文件名"))
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use regex::Regex;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::domain::models::{Connection, TriggerAction, TriggerRule};
+
+use super::ansi::{AnsiFilter, AnsiPiece};
+use super::events::Events;
+use super::output::OutputEvent;
+use super::SessionManager;
+
+/// 超长的无换行输出只保留末尾部分参与匹配。
+const MAX_LINE_BYTES: usize = 4096;
+
+#[derive(serde::Serialize, Clone)]
+pub struct SessionTriggerPayload {
+    pub session_id: String,
+    pub connection_id: Option<String>,
+    pub rule_id: String,
+    pub rule_name: String,
+    pub action: TriggerAction,
+    /// 命中所在的行（去除转义序列，不含换行符），`start`/`end` 为该行内的字节偏移。
+    pub line: String,
+    pub start: usize,
+    pub end: usize,
+    pub matched: String,
+}
+
+struct CompiledTrigger {
+    rule: TriggerRule,
+    regex: Regex,
+}
+
+/// 已启用规则的快照；更新时整体替换，扫描任务每次读取最新版本。
+#[derive(Clone, Default)]
+pub struct TriggerRules {
+    current: Arc<RwLock<Arc<Vec<CompiledTrigger>>>>,
+}
+
+impl TriggerRules {
+    fn snapshot(&self) -> Arc<Vec<CompiledTrigger>> {
+        self.current.read().unwrap().clone()
+    }
+}
+
+impl SessionManager {
+    /// 替换生效的触发规则，已打开的会话立即按新规则扫描。无效正则的规则会被跳过。
+    pub fn set_trigger_rules(&self, rules: &[TriggerRule]) {
+        let compiled = rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .filter_map(|rule| {
+                Regex::new(&rule.pattern).ok().map(|regex| CompiledTrigger {
+                    rule: rule.clone(),
+                    regex,
+                })
+            })
+            .collect();
+        *self.triggers.current.write().unwrap() = Arc::new(compiled);
+    }
+
+    /// 去除转义序列后按行扫描会话输出，直到会话结束。
+    pub(super) fn watch_triggers(
+        &self,
+        events: &Events,
+        session_id: &str,
+        connection: Option<&Connection>,
+        mut output: broadcast::Receiver<OutputEvent>,
+    ) {
+        let mut scanner = LineScanner {
+            rules: self.triggers.clone(),
+            events: events.clone(),
+            session_id: session_id.to_string(),
+            connection: connection.cloned(),
+            filter: AnsiFilter::default(),
+            line: String::new(),
+            reported: HashMap::new(),
+        };
+        tokio::spawn(async move {
+            loop {
+                match output.recv().await {
+                    Ok(OutputEvent::Data { data, .. }) => scanner.feed(&data),
+                    Ok(OutputEvent::Closed(_)) | Err(RecvError::Closed) => break,
+                    // 丢失的输出无法补回，从下一行重新开始匹配。
+                    Err(RecvError::Lagged(_)) => scanner.resync(),
+                }
+            }
+        });
+    }
+}
+
+struct LineScanner {
+    rules: TriggerRules,
+    events: Events,
+    session_id: String,
+    connection: Option<Connection>,
+    /// 颜色等序列可能拆在两个分块之间，过滤器跨分块保持状态。
+    filter: AnsiFilter,
+    /// 尚未遇到换行的当前行，跨分块累积。
+    line: String,
+    /// 当前行内每条规则已上报到的位置，避免同一命中在后续分块中重复推送。
+    reported: HashMap<String, usize>,
+}
+
+impl LineScanner {
+    fn feed(&mut self, chunk: &str) {
+        let mut text = String::with_capacity(chunk.len());
+        self.filter.feed(chunk, |piece| {
+            if let AnsiPiece::Text(ch) = piece {
+                text.push(ch);
+            }
+        });
+        for segment in text.split_inclusive('\n') {
+            let complete = segment.ends_with('\n');
+            self.line.push_str(segment.trim_end_matches('\n'));
+            self.truncate();
+            self.scan();
+            if complete {
+                self.reset();
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+        self.reported.clear();
+    }
+
+    fn resync(&mut self) {
+        self.filter = AnsiFilter::default();
+        self.reset();
+    }
+
+    fn truncate(&mut self) {
+        if self.line.len() <= MAX_LINE_BYTES {
+            return;
+        }
+        let mut cut = self.line.len() - MAX_LINE_BYTES;
+        while !self.line.is_char_boundary(cut) {
+            cut += 1;
+        }
+        self.line.drain(..cut);
+        for offset in self.reported.values_mut() {
+            *offset = offset.saturating_sub(cut);
+        }
+    }
+
+    fn scan(&mut self) {
+        let rules = self.rules.snapshot();
+        for trigger in rules.iter() {
+            if !trigger.rule.scope.applies_to(self.connection.as_ref()) {
+                continue;
+            }
+            let mut from = self.reported.get(&trigger.rule.id).copied().unwrap_or(0);
+            while let Some(found) = trigger.regex.find_at(&self.line, from) {
+                if found.is_empty() {
+                    break;
+                }
+                from = found.end();
+                let _ = self.events.emit(
+                    "session-trigger",
+                    SessionTriggerPayload {
+                        session_id: self.session_id.clone(),
+                        connection_id: self.connection.as_ref().map(|conn| conn.id.clone()),
+                        rule_id: trigger.rule.id.clone(),
+                        rule_name: trigger.rule.name.clone(),
+                        action: trigger.rule.action,
+                        line: self.line.clone(),
+                        start: found.start(),
+                        end: found.end(),
+                        matched: found.as_str().to_string(),
+                    },
+                );
+            }
+            self.reported.insert(trigger.rule.id.clone(), from);
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! trzsz 客户端（配合远端 `trz`/`tsz`）。
+//!
+//! 协议按行交换 `#类型:内容\n`：字符串与二进制内容经 zlib 压缩后 base64 编码，整数直接用十进制。
+//! 动作报文中声明不使用二进制模式，数据块同样以 base64 传输，免去转义表的协商。
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use md5::{Digest, Md5};
+
+use super::transfer::{file_name, local_target, Link, TransferProgress};
+
+/// 声明给服务端的协议版本。
+const CLIENT_VERSION: &str = "1.1.6";
+const CHUNK_SIZE: usize = 32 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// 单行报文的长度上限。
+const MAX_LINE: usize = 4 * 1024 * 1024;
+
+struct Trzsz<'l, 'a, S> {
+    link: &'l mut Link<'a, S>,
+}
+
+impl<'l, 'a, S: Read + Write> Trzsz<'l, 'a, S> {
+    fn send_line(&mut self, kind: &str, body: &str) -> Result<()> {
+        self.link.write_all(format!("#{kind}:{body}\n").as_bytes())
+    }
+
+    fn send_integer(&mut self, kind: &str, value: u64) -> Result<()> {
+        self.send_line(kind, &value.to_string())
+    }
+
+    fn send_binary(&mut self, kind: &str, data: &[u8]) -> Result<()> {
+        self.send_line(kind, &encode(data)?)
+    }
+
+    fn send_string(&mut self, kind: &str, value: &str) -> Result<()> {
+        self.send_binary(kind, value.as_bytes())
+    }
+
+    /// 读取期望类型的一行；`junk` 为 true 时允许行首夹杂远端 shell 的其他输出。
+    fn recv(&mut self, kind: &str, junk: bool) -> Result<String> {
+        let line = loop {
+            let line = self.read_line()?;
+            if !line.is_empty() {
+                break line;
+            }
+        };
+        let prefix = format!("#{kind}:");
+        let start = if junk {
+            line.rfind(&prefix).or_else(|| line.rfind('#'))
+        } else {
+            line.find('#')
+        }
+        .ok_or_else(|| anyhow!("trzsz 报文格式错误"))?;
+        let (found, body) = line[start + 1..]
+            .split_once(':')
+            .ok_or_else(|| anyhow!("trzsz 报文格式错误"))?;
+        if found == kind {
+            return Ok(body.to_string());
+        }
+        match found {
+            "fail" | "FAIL" | "EXIT" => {
+                let message = decode(body)
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                    .unwrap_or_else(|_| body.to_string());
+                bail!("远端结束了传输: {message}")
+            }
+            _ => bail!("trzsz 报文类型不符: 期望 {kind}，收到 {found}"),
+        }
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = Vec::new();
+        loop {
+            match self.link.read_byte(READ_TIMEOUT)? {
+                b'\n' => break,
+                byte => line.push(byte),
+            }
+            if line.len() > MAX_LINE {
+                bail!("trzsz 报文过长");
+            }
+        }
+        while line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        Ok(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    fn recv_integer(&mut self, kind: &str) -> Result<u64> {
+        let body = self.recv(kind, false)?;
+        body.trim()
+            .parse()
+            .map_err(|_| anyhow!("trzsz 整数格式错误: {body}"))
+    }
+
+    fn recv_binary(&mut self, kind: &str) -> Result<Vec<u8>> {
+        decode(&self.recv(kind, false)?)
+    }
+
+    fn recv_string(&mut self, kind: &str) -> Result<String> {
+        Ok(String::from_utf8_lossy(&self.recv_binary(kind)?).into_owned())
+    }
+
+    fn check_integer(&mut self, expected: u64) -> Result<()> {
+        let value = self.recv_integer("SUCC")?;
+        if value != expected {
+            bail!("trzsz 校验失败: 期望 {expected}，收到 {value}");
+        }
+        Ok(())
+    }
+
+    /// 回复动作并读取服务端配置；服务端坚持二进制模式时无法继续。
+    fn handshake(&mut self) -> Result<()> {
+        let action = serde_json::json!({
+            "lang": "rs",
+            "confirm": true,
+            "version": CLIENT_VERSION,
+            "support_dir": false,
+            "binary": false,
+        });
+        self.send_string("ACT", &action.to_string())?;
+        let config = self.recv("CFG", true).and_then(|body| decode(&body))?;
+        let config: serde_json::Value =
+            serde_json::from_slice(&config).context("trzsz 配置格式错误")?;
+        if config["binary"].as_bool() == Some(true) {
+            bail!("远端要求二进制模式，暂不支持");
+        }
+        if config["directory"].as_bool() == Some(true) {
+            bail!("暂不支持传输目录");
+        }
+        Ok(())
+    }
+
+    fn exit(&mut self, message: &str) -> Result<()> {
+        self.send_string("EXIT", message)
+    }
+}
+
+/// 把本地文件上传给远端 `trz`。
+pub fn upload<S: Read + Write>(
+    link: &mut Link<'_, S>,
+    files: &[PathBuf],
+    progress: &mut dyn FnMut(TransferProgress),
+) -> Result<()> {
+    let mut tz = Trzsz { link };
+    tz.handshake()?;
+    tz.send_integer("NUM", files.len() as u64)?;
+    tz.check_integer(files.len() as u64)?;
+    for (index, path) in files.iter().enumerate() {
+        let name = file_name(path).ok_or_else(|| anyhow!("无效的文件名"))?;
+        let mut file =
+            File::open(path).with_context(|| format!("无法打开文件 {}", path.display()))?;
+        let size = file.metadata()?.len();
+        tz.send_string("NAME", &name)?;
+        tz.recv_string("SUCC")?;
+        tz.send_integer("SIZE", size)?;
+        tz.check_integer(size)?;
+
+        let mut hasher = Md5::new();
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut sent = 0u64;
+        while sent < size {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                bail!("文件 {name} 在上传过程中被截断");
+            }
+            tz.send_binary("DATA", &buffer[..read])?;
+            tz.check_integer(read as u64)?;
+            hasher.update(&buffer[..read]);
+            sent += read as u64;
+            progress(TransferProgress {
+                file_name: name.clone(),
+                file_index: index,
+                bytes: sent,
+                total_bytes: size,
+            });
+        }
+        let digest = hasher.finalize().to_vec();
+        tz.send_binary("MD5", &digest)?;
+        if tz.recv_binary("SUCC")? != digest {
+            bail!("文件 {name} 校验不一致");
+        }
+    }
+    tz.exit(&format!("已上传 {} 个文件", files.len()))
+}
+
+/// 接收远端 `tsz` 发送的文件，保存到 `dir`，返回本地路径。
+pub fn download<S: Read + Write>(
+    link: &mut Link<'_, S>,
+    dir: &Path,
+    progress: &mut dyn FnMut(TransferProgress),
+) -> Result<Vec<String>> {
+    let mut tz = Trzsz { link };
+    tz.handshake()?;
+    let count = tz.recv_integer("NUM")?;
+    tz.send_integer("SUCC", count)?;
+    let mut saved = Vec::new();
+    for index in 0..count as usize {
+        let remote_name = tz.recv_string("NAME")?;
+        let path = local_target(dir, &remote_name)?;
+        let name = file_name(&path).unwrap_or(remote_name);
+        let mut file =
+            File::create(&path).with_context(|| format!("无法创建文件 {}", path.display()))?;
+        tz.send_string("SUCC", &name)?;
+        let size = tz.recv_integer("SIZE")?;
+        tz.send_integer("SUCC", size)?;
+
+        let mut hasher = Md5::new();
+        let mut received = 0u64;
+        while received < size {
+            let data = tz.recv_binary("DATA")?;
+            if data.is_empty() {
+                bail!("文件 {name} 数据为空");
+            }
+            file.write_all(&data)
+                .with_context(|| format!("写入文件失败 {}", path.display()))?;
+            hasher.update(&data);
+            received += data.len() as u64;
+            tz.send_integer("SUCC", data.len() as u64)?;
+            progress(TransferProgress {
+                file_name: name.clone(),
+                file_index: index,
+                bytes: received,
+                total_bytes: size,
+            });
+        }
+        let digest = hasher.finalize().to_vec();
+        if tz.recv_binary("MD5")? != digest {
+            bail!("文件 {name} 校验不一致");
+        }
+        tz.send_binary("SUCC", &digest)?;
+        saved.push(path.to_string_lossy().into_owned());
+    }
+    tz.exit(&format!(
+        "已保存 {} 个文件到 {}",
+        saved.len(),
+        dir.display()
+    ))?;
+    Ok(saved)
+}
+
+/// 告知远端客户端放弃，`trz`/`tsz` 收到后打印原因并退出。
+pub fn abort<S: Read + Write>(link: &mut Link<'_, S>, reason: &str) -> Result<()> {
+    Trzsz { link }.send_string("fail", reason)
+}
+
+fn encode(data: &[u8]) -> Result<String> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(B64.encode(encoder.finish()?))
+}
+
+fn decode(body: &str) -> Result<Vec<u8>> {
+    let compressed = B64
+        .decode(body.trim())
+        .map_err(|err| anyhow!("trzsz 数据解码失败: {err}"))?;
+    let mut data = Vec::new();
+    ZlibDecoder::new(compressed.as_slice())
+        .read_to_end(&mut data)
+        .context("trzsz 数据解压失败")?;
+    Ok(data)
+}
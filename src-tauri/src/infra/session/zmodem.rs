@@ -0,0 +1,714 @@
+//! ZMODEM 发送与接收，兼容 lrzsz 的 `rz`/`sz`。
+//!
+//! 只实现文件传输所需的子集：十六进制与二进制（CRC16/CRC32）帧头、ZDLE 转义的数据子包、
+//! 断点重发（`ZRPOS`）与接收方缓冲区窗口。不执行远端命令（`ZCOMMAND`），也不协商压缩与加密。
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::Crc;
+
+use super::transfer::{file_name, local_target, Link, TransferProgress};
+
+const ZPAD: u8 = b'*';
+const ZDLE: u8 = 0x18;
+const ZBIN: u8 = b'A';
+const ZHEX: u8 = b'B';
+const ZBIN32: u8 = b'C';
+
+const ZRQINIT: u8 = 0;
+const ZRINIT: u8 = 1;
+const ZSINIT: u8 = 2;
+const ZACK: u8 = 3;
+const ZFILE: u8 = 4;
+const ZSKIP: u8 = 5;
+const ZNAK: u8 = 6;
+const ZABORT: u8 = 7;
+const ZFIN: u8 = 8;
+const ZRPOS: u8 = 9;
+const ZDATA: u8 = 10;
+const ZEOF: u8 = 11;
+const ZFERR: u8 = 12;
+const ZCHALLENGE: u8 = 14;
+const ZCAN: u8 = 16;
+const ZFREECNT: u8 = 17;
+const ZCOMMAND: u8 = 18;
+
+/// 数据子包结尾：`E` 帧结束、`G` 继续不应答、`Q` 继续需应答、`W` 等待应答。
+const ZCRCE: u8 = b'h';
+const ZCRCG: u8 = b'i';
+const ZCRCQ: u8 = b'j';
+const ZCRCW: u8 = b'k';
+const ZRUB0: u8 = b'l';
+const ZRUB1: u8 = b'm';
+
+/// `ZRINIT` 能力位（ZF0）。
+const CANFDX: u8 = 0x01;
+const CANOVIO: u8 = 0x02;
+const CANFC32: u8 = 0x20;
+const ESCCTL: u8 = 0x40;
+/// 本端作为接收方声明的能力：全双工、可边收边写、CRC32；缓冲区长度为 0 表示不限。
+const RECEIVER_CAPS: [u8; 4] = [0, 0, 0, CANFDX | CANOVIO | CANFC32];
+
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+
+/// 发送方每个数据子包的长度。
+const BLOCK_SIZE: usize = 1024;
+/// 接收数据子包的长度上限，超过即视为数据损坏。
+const MAX_SUBPACKET: usize = 8192;
+/// 两次收到对方数据之间的最长间隔。
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// 同一位置连续出错的重试次数。
+const MAX_RETRIES: usize = 10;
+/// 寻找帧头时允许跳过的杂乱字节数。
+const MAX_GARBAGE: usize = 64 * 1024;
+
+struct Header {
+    kind: u8,
+    data: [u8; 4],
+}
+
+impl Header {
+    /// 位置类帧头（ZRPOS、ZDATA、ZEOF、ZACK）低字节在前。
+    fn position(&self) -> u64 {
+        u32::from_le_bytes(self.data) as u64
+    }
+
+    /// 标志类帧头（ZRINIT、ZFILE）的 ZF0 位于最后一个字节。
+    fn zf0(&self) -> u8 {
+        self.data[3]
+    }
+}
+
+enum Unit {
+    Byte(u8),
+    End(u8),
+    Invalid,
+}
+
+/// 在 [`Link`] 之上处理 ZDLE 转义、CRC 与取消序列。
+struct Zmodem<'l, 'a, S> {
+    link: &'l mut Link<'a, S>,
+    /// 最近一次读到的二进制帧头是否使用 CRC32，其后的数据子包沿用同一校验方式。
+    rx_crc32: bool,
+    tx_crc32: bool,
+    escape_control: bool,
+    cancels: usize,
+    last_sent: u8,
+}
+
+impl<'l, 'a, S: Read + Write> Zmodem<'l, 'a, S> {
+    fn new(link: &'l mut Link<'a, S>) -> Self {
+        Self {
+            link,
+            rx_crc32: false,
+            tx_crc32: false,
+            escape_control: false,
+            cancels: 0,
+            last_sent: 0,
+        }
+    }
+
+    /// 连续 5 个 CAN（与 ZDLE 同值）表示对方中止传输，正常数据中不会出现。
+    fn read_raw(&mut self) -> Result<u8> {
+        let byte = self.link.read_byte(READ_TIMEOUT)?;
+        if byte == ZDLE {
+            self.cancels += 1;
+            if self.cancels >= 5 {
+                bail!("对方取消了传输");
+            }
+        } else {
+            self.cancels = 0;
+        }
+        Ok(byte)
+    }
+
+    fn read_unit(&mut self) -> Result<Unit> {
+        loop {
+            match self.read_raw()? {
+                ZDLE => break,
+                XON | XOFF | 0x91 | 0x93 => continue,
+                byte => return Ok(Unit::Byte(byte)),
+            }
+        }
+        loop {
+            return Ok(match self.read_raw()? {
+                ZDLE | XON | XOFF | 0x91 | 0x93 => continue,
+                end @ (ZCRCE | ZCRCG | ZCRCQ | ZCRCW) => Unit::End(end),
+                ZRUB0 => Unit::Byte(0x7f),
+                ZRUB1 => Unit::Byte(0xff),
+                byte if byte & 0x60 == 0x40 => Unit::Byte(byte ^ 0x40),
+                _ => Unit::Invalid,
+            });
+        }
+    }
+
+    fn read_escaped(&mut self) -> Result<Option<u8>> {
+        Ok(match self.read_unit()? {
+            Unit::Byte(byte) => Some(byte),
+            _ => None,
+        })
+    }
+
+    /// 读取下一个帧头；校验失败或格式不对时返回 None，由调用方决定如何要求重发。
+    fn read_header(&mut self) -> Result<Option<Header>> {
+        let mut skipped = 0;
+        loop {
+            let byte = self.read_raw()?;
+            if byte != ZPAD {
+                skipped += 1;
+                if skipped > MAX_GARBAGE {
+                    bail!("没有收到 ZMODEM 帧头");
+                }
+                continue;
+            }
+            let mut byte = self.read_raw()?;
+            while byte == ZPAD {
+                byte = self.read_raw()?;
+            }
+            if byte != ZDLE {
+                continue;
+            }
+            return match self.read_raw()? {
+                ZHEX => self.read_hex_header(),
+                ZBIN => self.read_binary_header(false),
+                ZBIN32 => self.read_binary_header(true),
+                _ => Ok(None),
+            };
+        }
+    }
+
+    fn read_hex_header(&mut self) -> Result<Option<Header>> {
+        let mut bytes = [0u8; 7];
+        for byte in &mut bytes {
+            let high = hex_value(self.read_raw()?);
+            let low = hex_value(self.read_raw()?);
+            match (high, low) {
+                (Some(high), Some(low)) => *byte = (high << 4) | low,
+                _ => return Ok(None),
+            }
+        }
+        if crc16(&bytes[..5]) != u16::from_be_bytes([bytes[5], bytes[6]]) {
+            return Ok(None);
+        }
+        Ok(Some(Header {
+            kind: bytes[0],
+            data: [bytes[1], bytes[2], bytes[3], bytes[4]],
+        }))
+    }
+
+    fn read_binary_header(&mut self, crc32: bool) -> Result<Option<Header>> {
+        let length = if crc32 { 9 } else { 7 };
+        let mut bytes = [0u8; 9];
+        for byte in bytes.iter_mut().take(length) {
+            match self.read_escaped()? {
+                Some(value) => *byte = value,
+                None => return Ok(None),
+            }
+        }
+        let valid = if crc32 {
+            crc32_of(&bytes[..5]) == u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]])
+        } else {
+            crc16(&bytes[..5]) == u16::from_be_bytes([bytes[5], bytes[6]])
+        };
+        if !valid {
+            return Ok(None);
+        }
+        self.rx_crc32 = crc32;
+        Ok(Some(Header {
+            kind: bytes[0],
+            data: [bytes[1], bytes[2], bytes[3], bytes[4]],
+        }))
+    }
+
+    /// 读取一个数据子包，返回内容与结尾类型；校验失败返回 None。
+    fn read_subpacket(&mut self) -> Result<Option<(Vec<u8>, u8)>> {
+        let mut data = Vec::with_capacity(BLOCK_SIZE);
+        let end = loop {
+            match self.read_unit()? {
+                Unit::Byte(byte) => {
+                    if data.len() >= MAX_SUBPACKET {
+                        return Ok(None);
+                    }
+                    data.push(byte);
+                }
+                Unit::End(end) => break end,
+                Unit::Invalid => return Ok(None),
+            }
+        };
+        let crc_length = if self.rx_crc32 { 4 } else { 2 };
+        let mut crc = [0u8; 4];
+        for byte in crc.iter_mut().take(crc_length) {
+            match self.read_escaped()? {
+                Some(value) => *byte = value,
+                None => return Ok(None),
+            }
+        }
+        data.push(end);
+        let valid = if self.rx_crc32 {
+            crc32_of(&data) == u32::from_le_bytes(crc)
+        } else {
+            crc16(&data) == u16::from_be_bytes([crc[0], crc[1]])
+        };
+        data.pop();
+        Ok(valid.then_some((data, end)))
+    }
+
+    fn send_hex_header(&mut self, kind: u8, data: [u8; 4]) -> Result<()> {
+        let mut bytes = vec![kind];
+        bytes.extend_from_slice(&data);
+        bytes.extend_from_slice(&crc16(&bytes).to_be_bytes());
+        let mut frame = vec![ZPAD, ZPAD, ZDLE, ZHEX];
+        for byte in bytes {
+            frame.extend_from_slice(format!("{byte:02x}").as_bytes());
+        }
+        frame.extend_from_slice(b"\r\x8a");
+        if kind != ZFIN && kind != ZACK {
+            frame.push(XON);
+        }
+        self.link.write_all(&frame)
+    }
+
+    fn send_binary_header(&mut self, kind: u8, data: [u8; 4]) -> Result<()> {
+        let mut bytes = vec![kind];
+        bytes.extend_from_slice(&data);
+        let mut frame = vec![ZPAD, ZDLE];
+        if self.tx_crc32 {
+            let crc = crc32_of(&bytes).to_le_bytes();
+            bytes.extend_from_slice(&crc);
+            frame.push(ZBIN32);
+        } else {
+            let crc = crc16(&bytes).to_be_bytes();
+            bytes.extend_from_slice(&crc);
+            frame.push(ZBIN);
+        }
+        for byte in bytes {
+            self.escape_into(&mut frame, byte);
+        }
+        self.link.write_all(&frame)
+    }
+
+    fn send_subpacket(&mut self, data: &[u8], end: u8) -> Result<()> {
+        let mut frame = Vec::with_capacity(data.len() * 2 + 16);
+        for &byte in data {
+            self.escape_into(&mut frame, byte);
+        }
+        frame.push(ZDLE);
+        frame.push(end);
+        let mut checked = data.to_vec();
+        checked.push(end);
+        if self.tx_crc32 {
+            for byte in crc32_of(&checked).to_le_bytes() {
+                self.escape_into(&mut frame, byte);
+            }
+        } else {
+            for byte in crc16(&checked).to_be_bytes() {
+                self.escape_into(&mut frame, byte);
+            }
+        }
+        if end == ZCRCW {
+            frame.push(XON);
+        }
+        self.link.write_all(&frame)
+    }
+
+    /// 与 lrzsz 相同：转义 ZDLE、流控字符以及 `@` 之后的回车；对方要求时转义全部控制字符。
+    fn escape_into(&mut self, frame: &mut Vec<u8>, byte: u8) {
+        let escape = match byte {
+            ZDLE | 0x10 | 0x90 | XON | 0x91 | XOFF | 0x93 => true,
+            0x0d | 0x8d => self.last_sent & 0x7f == b'@',
+            _ => self.escape_control && byte & 0x60 == 0,
+        };
+        if escape {
+            frame.push(ZDLE);
+            frame.push(byte ^ 0x40);
+        } else {
+            frame.push(byte);
+        }
+        self.last_sent = byte;
+    }
+}
+
+/// 接收远端 `sz` 发送的文件，保存到 `dir`，返回本地路径。
+pub fn receive<S: Read + Write>(
+    link: &mut Link<'_, S>,
+    dir: &Path,
+    progress: &mut dyn FnMut(TransferProgress),
+) -> Result<Vec<String>> {
+    let mut zm = Zmodem::new(link);
+    let mut saved = Vec::new();
+    let mut current: Option<Incoming> = None;
+    let mut retries = 0;
+    // 远端 sz 的 ZRQINIT 在待解析字节里，读到后回复 ZRINIT 即开始
+    loop {
+        let Some(header) = zm.read_header()? else {
+            retries += 1;
+            if retries > MAX_RETRIES {
+                bail!("ZMODEM 帧头连续校验失败");
+            }
+            match &current {
+                Some(file) => zm.send_hex_header(ZRPOS, position_bytes(file.offset))?,
+                None => zm.send_hex_header(ZNAK, [0; 4])?,
+            }
+            continue;
+        };
+        match header.kind {
+            ZRQINIT if current.is_none() => zm.send_hex_header(ZRINIT, RECEIVER_CAPS)?,
+            ZSINIT => {
+                if zm.read_subpacket()?.is_some() {
+                    zm.send_hex_header(ZACK, [0; 4])?;
+                } else {
+                    zm.send_hex_header(ZNAK, [0; 4])?;
+                }
+            }
+            ZFILE => {
+                let Some((info, _)) = zm.read_subpacket()? else {
+                    zm.send_hex_header(ZNAK, [0; 4])?;
+                    continue;
+                };
+                if let Some(file) = &current {
+                    // 对方没收到 ZRPOS 而重发了同一个文件头
+                    zm.send_hex_header(ZRPOS, position_bytes(file.offset))?;
+                    continue;
+                }
+                let file = Incoming::create(dir, &info, saved.len())?;
+                zm.send_hex_header(ZRPOS, [0; 4])?;
+                current = Some(file);
+            }
+            ZDATA => {
+                let Some(file) = current.as_mut() else {
+                    zm.send_hex_header(ZRINIT, RECEIVER_CAPS)?;
+                    continue;
+                };
+                if header.position() != file.offset {
+                    zm.send_hex_header(ZRPOS, position_bytes(file.offset))?;
+                    continue;
+                }
+                loop {
+                    let Some((data, end)) = zm.read_subpacket()? else {
+                        retries += 1;
+                        if retries > MAX_RETRIES {
+                            bail!("ZMODEM 数据连续校验失败");
+                        }
+                        zm.send_hex_header(ZRPOS, position_bytes(file.offset))?;
+                        break;
+                    };
+                    retries = 0;
+                    file.write(&data)?;
+                    progress(file.progress());
+                    match end {
+                        ZCRCW | ZCRCQ => zm.send_hex_header(ZACK, position_bytes(file.offset))?,
+                        ZCRCE => break,
+                        _ => {}
+                    }
+                    if end == ZCRCW {
+                        break;
+                    }
+                }
+            }
+            ZEOF => {
+                // 位置不符说明还有数据在路上，忽略本次 ZEOF
+                let Some(file) = current.take_if(|file| file.offset == header.position()) else {
+                    continue;
+                };
+                saved.push(file.finish()?);
+                zm.send_hex_header(ZRINIT, RECEIVER_CAPS)?;
+            }
+            ZFIN => {
+                zm.send_hex_header(ZFIN, [0; 4])?;
+                // 吃掉对方最后发出的 "OO"，免得显示在终端里
+                for _ in 0..2 {
+                    if zm.link.read_byte(Duration::from_secs(1)).is_err() {
+                        break;
+                    }
+                }
+                return Ok(saved);
+            }
+            ZFREECNT => zm.send_hex_header(ZACK, [0xff, 0xff, 0xff, 0x7f])?,
+            ZCOMMAND => {
+                zm.read_subpacket()?;
+                bail!("拒绝执行远端发来的命令");
+            }
+            ZCAN | ZABORT | ZFERR => bail!("对方中止了传输"),
+            _ => {}
+        }
+        if header.kind != ZDATA {
+            retries = 0;
+        }
+    }
+}
+
+/// 正在接收的文件。
+struct Incoming {
+    file: File,
+    path: PathBuf,
+    name: String,
+    index: usize,
+    offset: u64,
+    size: u64,
+}
+
+impl Incoming {
+    /// 文件信息子包：`文件名\0长度 修改时间(八进制) 权限(八进制) ...\0`。
+    fn create(dir: &Path, info: &[u8], index: usize) -> Result<Self> {
+        let mut fields = info.splitn(2, |byte| *byte == 0);
+        let remote_name = String::from_utf8_lossy(fields.next().unwrap_or_default()).into_owned();
+        let size = fields
+            .next()
+            .map(|rest| String::from_utf8_lossy(rest).into_owned())
+            .and_then(|rest| rest.split_whitespace().next().map(str::to_string))
+            .and_then(|size| size.trim_end_matches('\0').parse().ok())
+            .unwrap_or(0);
+        let path = local_target(dir, &remote_name)?;
+        let file =
+            File::create(&path).with_context(|| format!("无法创建文件 {}", path.display()))?;
+        Ok(Self {
+            file,
+            name: file_name(&path).unwrap_or(remote_name),
+            path,
+            index,
+            offset: 0,
+            size,
+        })
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.file
+            .write_all(data)
+            .with_context(|| format!("写入文件失败 {}", self.path.display()))?;
+        self.offset += data.len() as u64;
+        Ok(())
+    }
+
+    fn progress(&self) -> TransferProgress {
+        TransferProgress {
+            file_name: self.name.clone(),
+            file_index: self.index,
+            bytes: self.offset,
+            total_bytes: self.size.max(self.offset),
+        }
+    }
+
+    fn finish(mut self) -> Result<String> {
+        self.file.flush()?;
+        Ok(self.path.to_string_lossy().into_owned())
+    }
+}
+
+/// 把本地文件发给远端等待中的 `rz`。
+pub fn send<S: Read + Write>(
+    link: &mut Link<'_, S>,
+    files: &[PathBuf],
+    progress: &mut dyn FnMut(TransferProgress),
+) -> Result<()> {
+    let mut zm = Zmodem::new(link);
+    let init = wait_for(&mut zm, &[ZRINIT])?;
+    zm.tx_crc32 = init.zf0() & CANFC32 != 0;
+    zm.escape_control = init.zf0() & ESCCTL != 0;
+    // 接收方缓冲区长度，0 表示可以连续发送
+    let window = u16::from_le_bytes([init.data[0], init.data[1]]) as u64;
+
+    let mut remaining_bytes: u64 = files
+        .iter()
+        .filter_map(|path| path.metadata().ok())
+        .map(|meta| meta.len())
+        .sum();
+    for (index, path) in files.iter().enumerate() {
+        let mut file =
+            File::open(path).with_context(|| format!("无法打开文件 {}", path.display()))?;
+        let meta = file.metadata()?;
+        let size = meta.len();
+        let name = file_name(path).ok_or_else(|| anyhow!("无效的文件名"))?;
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let info = format!(
+            "{name}\0{size} {mtime:o} 100644 0 {} {remaining_bytes}\0",
+            files.len() - index
+        );
+
+        let mut retries = 0;
+        let start = 'offer: loop {
+            zm.send_binary_header(ZFILE, [0; 4])?;
+            zm.send_subpacket(info.as_bytes(), ZCRCW)?;
+            loop {
+                let reply = wait_for(&mut zm, &[ZRPOS, ZSKIP, ZRINIT, ZNAK])?;
+                match reply.kind {
+                    ZRPOS => break 'offer Some(reply.position()),
+                    ZSKIP => break 'offer None,
+                    ZRINIT if header_follows(&mut zm)? => continue,
+                    _ => break,
+                }
+            }
+            retries += 1;
+            if retries > MAX_RETRIES {
+                bail!("对方没有接受文件 {name}");
+            }
+        };
+        remaining_bytes = remaining_bytes.saturating_sub(size);
+        let Some(mut offset) = start else {
+            continue;
+        };
+
+        let mut retries = 0;
+        'file: loop {
+            file.seek(SeekFrom::Start(offset))?;
+            zm.send_binary_header(ZDATA, position_bytes(offset))?;
+            let mut since_ack = 0;
+            let mut buffer = [0u8; BLOCK_SIZE];
+            loop {
+                let read = file.read(&mut buffer)?;
+                offset += read as u64;
+                since_ack += read as u64;
+                let end = if offset >= size || read == 0 {
+                    ZCRCE
+                } else if window > 0 && since_ack + BLOCK_SIZE as u64 > window {
+                    ZCRCW
+                } else {
+                    ZCRCG
+                };
+                zm.send_subpacket(&buffer[..read], end)?;
+                progress(TransferProgress {
+                    file_name: name.clone(),
+                    file_index: index,
+                    bytes: offset,
+                    total_bytes: size,
+                });
+                if end == ZCRCE {
+                    break;
+                }
+                if end == ZCRCW {
+                    // 与 lrzsz 一致：应答之后以新的 ZDATA 帧头继续
+                    let reply = wait_for(&mut zm, &[ZACK, ZRPOS])?;
+                    if reply.kind == ZRPOS {
+                        offset = reply.position();
+                    }
+                    continue 'file;
+                }
+                // 连续发送期间检查对方是否要求从某个位置重发
+                if let Some(position) = pending_rpos(&mut zm)? {
+                    retries += 1;
+                    if retries > MAX_RETRIES {
+                        bail!("文件 {name} 重发次数过多");
+                    }
+                    offset = position;
+                    continue 'file;
+                }
+            }
+            zm.send_binary_header(ZEOF, position_bytes(offset))?;
+            let reply = wait_for(&mut zm, &[ZRINIT, ZRPOS, ZSKIP])?;
+            match reply.kind {
+                ZRPOS => {
+                    retries += 1;
+                    if retries > MAX_RETRIES {
+                        bail!("文件 {name} 重发次数过多");
+                    }
+                    offset = reply.position();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    // 只发一次 ZFIN：对方退出后多余的帧头会落到 shell 里被当作命令输入
+    zm.send_hex_header(ZFIN, [0; 4])?;
+    wait_for(&mut zm, &[ZFIN])?;
+    zm.link.write_all(b"OO")
+}
+
+/// 等待指定类型的帧头，期间应答对方的 ZCHALLENGE，忽略其余帧。
+fn wait_for<S: Read + Write>(zm: &mut Zmodem<'_, '_, S>, kinds: &[u8]) -> Result<Header> {
+    for _ in 0..MAX_RETRIES * 4 {
+        let Some(header) = zm.read_header()? else {
+            continue;
+        };
+        if kinds.contains(&header.kind) {
+            return Ok(header);
+        }
+        match header.kind {
+            ZCHALLENGE => zm.send_hex_header(ZACK, header.data)?,
+            ZCAN | ZABORT | ZFERR => bail!("对方中止了传输"),
+            _ => {}
+        }
+    }
+    bail!("ZMODEM 应答异常")
+}
+
+/// 短时间内是否还有帧头到达，用来识别对方等待期间积压的重复 ZRINIT。
+fn header_follows<S: Read + Write>(zm: &mut Zmodem<'_, '_, S>) -> Result<bool> {
+    let deadline = Instant::now() + Duration::from_millis(500);
+    loop {
+        zm.link.poll()?;
+        if zm.link.buffered().contains(&ZPAD) {
+            return Ok(true);
+        }
+        if Instant::now() >= deadline {
+            return Ok(false);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// 不等待地检查对方是否发来 ZRPOS；缓冲中没有帧头起始符时丢弃杂乱字节。
+fn pending_rpos<S: Read + Write>(zm: &mut Zmodem<'_, '_, S>) -> Result<Option<u64>> {
+    zm.link.poll()?;
+    let buffered = zm.link.buffered();
+    if buffered.iter().filter(|byte| **byte == ZDLE).count() >= 5 {
+        bail!("对方取消了传输");
+    }
+    if !buffered.contains(&ZPAD) {
+        zm.link.discard_buffered();
+        return Ok(None);
+    }
+    match zm.read_header()? {
+        Some(header) if header.kind == ZRPOS => Ok(Some(header.position())),
+        Some(header) if matches!(header.kind, ZCAN | ZABORT | ZFERR) => {
+            bail!("对方中止了传输")
+        }
+        _ => Ok(None),
+    }
+}
+
+/// lrzsz 的中止序列：8 个 CAN 后跟 8 个退格，清掉对方终端上残留的 CAN。
+pub fn abort<S: Read + Write>(link: &mut Link<'_, S>) -> Result<()> {
+    let mut sequence = vec![ZDLE; 8];
+    sequence.extend_from_slice(&[0x08; 8]);
+    link.write_all(&sequence)
+}
+
+fn position_bytes(position: u64) -> [u8; 4] {
+    (position as u32).to_le_bytes()
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|value| value as u8)
+}
+
+/// XMODEM CRC16（多项式 0x1021，初值 0）。
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc32_of(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
@@ -1,7 +1,7 @@
 mod app_state;
 mod cmd;
-mod domain;
-mod infra;
+pub mod domain;
+pub mod infra;
 
 use tauri::Manager;
 
@@ -19,15 +19,60 @@ pub fn run() {
             cmd::connections::list_connections,
             cmd::connections::create_connection,
             cmd::connections::update_connection,
+            cmd::connections::update_connection_startup,
+            cmd::connections::update_connection_agent_forwarding,
             cmd::connections::delete_connection,
             cmd::sessions::list_session_summaries,
             cmd::sessions::create_shell_session,
             cmd::sessions::send_session_input,
             cmd::sessions::close_shell_session,
+            cmd::sessions::get_session_state,
+            cmd::sessions::list_session_history,
+            cmd::sessions::list_recent_sessions,
+            cmd::sessions::get_screen_snapshot,
+            cmd::sessions::get_screen_thumbnail,
+            cmd::sessions::search_sessions,
+            cmd::sessions::list_session_commands,
+            cmd::sessions::list_connection_commands,
+            cmd::sessions::rerun_last_command,
+            cmd::sessions::get_shell_integration_nonce,
+            cmd::sessions::open_session_in_cwd,
+            cmd::sessions::get_active_transfer,
+            cmd::sessions::accept_file_transfer,
+            cmd::sessions::cancel_file_transfer,
+            cmd::sessions::respond_agent_request,
+            cmd::sessions::attach_session_observer,
+            cmd::sessions::detach_session_observer,
+            cmd::sessions::list_session_observers,
+            cmd::sessions::create_broadcast_group,
+            cmd::sessions::add_broadcast_member,
+            cmd::sessions::remove_broadcast_member,
+            cmd::sessions::list_broadcast_members,
+            cmd::sessions::dissolve_broadcast_group,
+            cmd::sessions::get_transcript_settings,
+            cmd::sessions::update_transcript_settings,
+            cmd::sessions::get_session_policy_settings,
+            cmd::sessions::update_session_policy_settings,
+            cmd::sessions::list_shell_profiles,
+            cmd::sessions::save_shell_profile,
+            cmd::sessions::delete_shell_profile,
+            cmd::sessions::set_default_shell_profile,
+            cmd::sessions::set_persistent_local_sessions,
+            cmd::sessions::reattach_local_sessions,
+            cmd::sessions::export_session_transcript,
+            cmd::scripts::list_scripts,
+            cmd::scripts::save_script,
+            cmd::scripts::delete_script,
+            cmd::scripts::run_script,
             cmd::settings::load_settings,
             cmd::sync::export_encrypted_conf,
             cmd::sync::import_encrypted_conf,
             cmd::terminal::run_local_command,
+            cmd::terminal::run_multi_host_command,
+            cmd::terminal::cancel_command,
+            cmd::triggers::list_trigger_rules,
+            cmd::triggers::save_trigger_rule,
+            cmd::triggers::delete_trigger_rule,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
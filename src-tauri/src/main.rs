@@ -2,5 +2,21 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // 本地会话守护进程与应用共用同一个可执行文件，升级后仍可由新版本重新附加。
+    #[cfg(unix)]
+    {
+        use tauri_app_lib::infra::session::daemon;
+
+        let mut args = std::env::args().skip(1);
+        if args.next().as_deref() == Some(daemon::DAEMON_ARG) {
+            if let Some(socket) = args.next() {
+                if let Err(err) = daemon::run_daemon_blocking(socket.into()) {
+                    eprintln!("本地会话守护进程异常退出: {err:#}");
+                    std::process::exit(1);
+                }
+                return;
+            }
+        }
+    }
     tauri_app_lib::run()
 }
@@ -0,0 +1,53 @@
+use tauri_app_lib::infra::session::ansi::{to_html, AnsiFilter, AnsiPiece};
+
+fn filter_text(chunks: &[&str]) -> (String, Vec<String>) {
+    let mut filter = AnsiFilter::default();
+    let mut text = String::new();
+    let mut sgr = Vec::new();
+    for chunk in chunks {
+        filter.feed(chunk, |piece| match piece {
+            AnsiPiece::Text(ch) => text.push(ch),
+            AnsiPiece::Sgr(params) => sgr.push(params.to_string()),
+            AnsiPiece::Osc(_) | AnsiPiece::Control(_) => {}
+        });
+    }
+    (text, sgr)
+}
+
+#[test]
+fn escape_sequences_with_intermediates_are_dropped() {
+    // 字符集切换与 DEC 对齐测试的结束字节不能漏进文本
+    let (text, _) = filter_text(&["a\x1b(Bb\x1b)0c\x1b#8d\x1b7e\x1b8"]);
+    assert_eq!(text, "abcde");
+    // 序列跨分块时同样完整丢弃
+    let (text, _) = filter_text(&["x\x1b(", "By", "\x1b", " ", "Fz"]);
+    assert_eq!(text, "xyz");
+}
+
+#[test]
+fn sgr_and_osc_are_split_from_text() {
+    let (text, sgr) =
+        filter_text(&["\x1b]0;title\x07\x1b[1;31mred\x1b[0m \x1b[2Jplain\x1b]633;E;ls\x1b\\\n"]);
+    assert_eq!(text, "red plain\n");
+    assert_eq!(sgr, vec!["1;31".to_string(), "0".to_string()]);
+}
+
+#[test]
+fn html_export_keeps_colors_and_escapes_text() {
+    let html = to_html("<log>", "\x1b[31mred\x1b[0m & \x1b[1;38;2;1;2;3mrgb\x1b[0m");
+    assert!(html.contains("<title>&lt;log&gt;</title>"));
+    assert!(html.contains("<span style=\"color:#cd0000;\">red</span> &amp; "));
+    assert!(html.contains("<span style=\"color:#010203;font-weight:bold;\">rgb</span>"));
+}
+
+#[test]
+fn html_export_understands_colon_sub_parameters() {
+    let html = to_html(
+        "t",
+        "\x1b[38:2::255:128:0ma\x1b[48:5:196mb\x1b[0m\x1b[38:2:0:0:255;4:3mc\x1b[4:0md\x1b[0m",
+    );
+    assert!(html.contains("<span style=\"color:#ff8000;\">a</span>"));
+    assert!(html.contains("<span style=\"color:#ff8000;background-color:#ff0000;\">b</span>"));
+    assert!(html.contains("<span style=\"color:#0000ff;text-decoration:underline;\">c</span>"));
+    assert!(html.contains("<span style=\"color:#0000ff;\">d</span>"));
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+
+use tauri_app_lib::domain::models::{Connection, Protocol, StartupActions};
+use tauri_app_lib::domain::services::connection_service::{
+    ConnectionRepository, ConnectionService,
+};
+
+#[derive(Default)]
+struct MemoryRepository {
+    connections: Mutex<Vec<Connection>>,
+}
+
+impl ConnectionRepository for MemoryRepository {
+    fn list(&self) -> Result<Vec<Connection>> {
+        Ok(self.connections.lock().unwrap().clone())
+    }
+
+    fn create(&self, connection: Connection) -> Result<Connection> {
+        self.connections.lock().unwrap().push(connection.clone());
+        Ok(connection)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Connection>> {
+        Ok(self.list()?.into_iter().find(|conn| conn.id == id))
+    }
+
+    fn update(&self, connection: Connection) -> Result<Connection> {
+        let mut connections = self.connections.lock().unwrap();
+        if let Some(existing) = connections.iter_mut().find(|conn| conn.id == connection.id) {
+            *existing = connection.clone();
+        }
+        Ok(connection)
+    }
+
+    fn delete(&self, id: &str) -> Result<()> {
+        self.connections
+            .lock()
+            .unwrap()
+            .retain(|conn| conn.id != id);
+        Ok(())
+    }
+}
+
+fn connection(id: &str, protocol: Protocol, group_id: &str) -> Connection {
+    Connection {
+        id: id.into(),
+        name: id.into(),
+        protocol,
+        host: format!("{id}.example.com"),
+        port: 22,
+        username: "deploy".into(),
+        group_id: Some(group_id.into()),
+        ..Connection::default()
+    }
+}
+
+#[test]
+fn group_connections_skip_protocols_without_remote_exec() {
+    let repo = Arc::new(MemoryRepository::default());
+    for conn in [
+        connection("web", Protocol::Ssh, "grp-production"),
+        connection("files", Protocol::Sftp, "grp-production"),
+        connection("legacy-ftp", Protocol::Ftp, "grp-production"),
+        connection("staging", Protocol::Ssh, "grp-staging"),
+    ] {
+        repo.create(conn).unwrap();
+    }
+    let service = ConnectionService::new(repo);
+
+    let ids: Vec<String> = service
+        .list_group_connections("grp-production")
+        .unwrap()
+        .into_iter()
+        .map(|conn| conn.id)
+        .collect();
+    assert_eq!(ids, ["web", "files"]);
+    assert!(service
+        .list_group_connections("grp-missing")
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn startup_env_names_must_be_identifiers() {
+    let repo = Arc::new(MemoryRepository::default());
+    repo.create(connection("web", Protocol::Ssh, "grp-production"))
+        .unwrap();
+    let service = ConnectionService::new(repo);
+    let startup = |key: &str| StartupActions {
+        env: HashMap::from([(key.to_string(), "1".to_string())]),
+        ..StartupActions::default()
+    };
+
+    for key in ["X=1; rm -rf ~;", "1ST", "A-B", ""] {
+        let err = service
+            .update_startup_actions("web", startup(key))
+            .unwrap_err();
+        assert!(err.to_string().contains("无效的环境变量名"), "{key}: {err}");
+    }
+    assert!(service
+        .get_connection("web")
+        .unwrap()
+        .unwrap()
+        .startup
+        .env
+        .is_empty());
+
+    let saved = service
+        .update_startup_actions("web", startup("_APP_MODE2"))
+        .unwrap();
+    assert_eq!(saved.startup.env["_APP_MODE2"], "1");
+}
@@ -0,0 +1,342 @@
+use tauri_app_lib::domain::models::{TransferDirection, TransferProtocol};
+use tauri_app_lib::infra::session::transfer::TransferDetector;
+
+#[test]
+fn detector_finds_start_sequence_split_across_reads() {
+    let mut detector = TransferDetector::default();
+    let (text, detected) = detector.scan(b"$ sz report.csv\r\n**");
+    assert_eq!(text, b"$ sz report.csv\r\n");
+    assert!(detected.is_none());
+
+    let (text, detected) = detector.scan(b"\x18B00000000000000\r\x8a\x11");
+    assert!(text.is_empty());
+    let detected = detected.unwrap();
+    assert_eq!(detected.protocol, TransferProtocol::Zmodem);
+    assert_eq!(detected.direction, TransferDirection::Download);
+    assert!(detected.pending.starts_with(b"**\x18B00"));
+
+    let (_, detected) = detector.scan(b"\x1b7\x07::TRZSZ:TRANSFER:R:1.1.6:1234567890\r\n");
+    let detected = detected.unwrap();
+    assert_eq!(detected.protocol, TransferProtocol::Trzsz);
+    assert_eq!(detected.direction, TransferDirection::Upload);
+    assert!(!detected.directory);
+}
+
+#[test]
+fn detector_releases_held_back_bytes_when_idle() {
+    let mut detector = TransferDetector::default();
+    let (text, detected) = detector.scan(b"rating: **");
+    assert_eq!(text, b"rating: ");
+    assert!(detected.is_none());
+    assert_eq!(detector.flush(), b"**");
+    assert!(detector.flush().is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn zmodem_round_trip_over_nonblocking_stream() {
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+
+    use tauri_app_lib::infra::session::transfer::Link;
+    use tauri_app_lib::infra::session::zmodem;
+
+    let root = std::env::temp_dir().join(format!("zmodem-{}", uuid::Uuid::new_v4()));
+    let source_dir = root.join("source");
+    let target_dir = root.join("target");
+    std::fs::create_dir_all(&source_dir).unwrap();
+    std::fs::create_dir_all(&target_dir).unwrap();
+
+    // 覆盖全部字节值（含 ZDLE、XON/XOFF 与 `@` 后的回车），并跨越多个数据子包
+    let binary: Vec<u8> = (0..150_000u32)
+        .map(|index| (index * 7 % 256) as u8)
+        .chain(*b"@\r@\x8d\x18\x18\x18\x18\x18")
+        .collect();
+    let files: Vec<PathBuf> = vec![source_dir.join("data.bin"), source_dir.join("empty.txt")];
+    std::fs::write(&files[0], &binary).unwrap();
+    std::fs::write(&files[1], b"").unwrap();
+    std::fs::write(target_dir.join("data.bin"), b"existing").unwrap();
+
+    let (mut local, mut remote) = UnixStream::pair().unwrap();
+    local.set_nonblocking(true).unwrap();
+    remote.set_nonblocking(true).unwrap();
+
+    let sender = std::thread::spawn(move || {
+        let mut cancel = || false;
+        let mut link = Link::new(&mut remote, Vec::new(), &mut cancel);
+        zmodem::send(&mut link, &files, &mut |_| {})
+    });
+
+    // 检测器交给接收方的待解析字节以远端 sz 的 ZRQINIT 开头
+    let mut cancel = || false;
+    let mut link = Link::new(
+        &mut local,
+        b"**\x18B00000000000000\r\x8a\x11".to_vec(),
+        &mut cancel,
+    );
+    let mut received = 0;
+    let saved = zmodem::receive(&mut link, &target_dir, &mut |progress| {
+        if progress.file_index == 0 {
+            received = progress.bytes;
+        }
+    })
+    .unwrap();
+    sender.join().unwrap().unwrap();
+
+    assert_eq!(saved.len(), 2);
+    assert!(saved[0].ends_with("data (1).bin"));
+    assert_eq!(std::fs::read(&saved[0]).unwrap(), binary);
+    assert!(std::fs::read(&saved[1]).unwrap().is_empty());
+    assert_eq!(
+        std::fs::read(target_dir.join("data.bin")).unwrap(),
+        b"existing"
+    );
+    assert_eq!(received, binary.len() as u64);
+    let _ = std::fs::remove_dir_all(root);
+}
+
+/// 模拟远端 `trz`/`tsz` 的一端：阻塞读写，报文编码与客户端相同。
+#[cfg(unix)]
+struct TrzszRemote {
+    reader: std::io::BufReader<std::os::unix::net::UnixStream>,
+    writer: std::os::unix::net::UnixStream,
+}
+
+#[cfg(unix)]
+impl TrzszRemote {
+    fn new(stream: std::os::unix::net::UnixStream) -> Self {
+        Self {
+            reader: std::io::BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+        }
+    }
+
+    fn send(&mut self, kind: &str, body: &str) {
+        use std::io::Write;
+        self.writer
+            .write_all(format!("#{kind}:{body}\n").as_bytes())
+            .unwrap();
+    }
+
+    fn send_binary(&mut self, kind: &str, data: &[u8]) {
+        self.send(kind, &trzsz_encode(data));
+    }
+
+    fn recv(&mut self, kind: &str) -> String {
+        use std::io::BufRead;
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        let body = line
+            .trim_end()
+            .strip_prefix(&format!("#{kind}:"))
+            .unwrap_or_else(|| panic!("期望 {kind}，收到 {line:?}"));
+        body.to_string()
+    }
+
+    fn recv_integer(&mut self, kind: &str) -> u64 {
+        self.recv(kind).parse().unwrap()
+    }
+
+    fn recv_binary(&mut self, kind: &str) -> Vec<u8> {
+        trzsz_decode(&self.recv(kind))
+    }
+
+    /// 读取客户端的动作报文并回复配置。
+    fn handshake(&mut self) {
+        let action: serde_json::Value = serde_json::from_slice(&self.recv_binary("ACT")).unwrap();
+        assert_eq!(action["binary"], false);
+        let config = serde_json::json!({ "binary": false, "directory": false });
+        self.send_binary("CFG", config.to_string().as_bytes());
+    }
+}
+
+#[cfg(unix)]
+fn trzsz_encode(data: &[u8]) -> String {
+    use base64::Engine as _;
+    use std::io::Write;
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    base64::engine::general_purpose::STANDARD.encode(encoder.finish().unwrap())
+}
+
+#[cfg(unix)]
+fn trzsz_decode(body: &str) -> Vec<u8> {
+    use base64::Engine as _;
+    use std::io::Read;
+    let compressed = base64::engine::general_purpose::STANDARD
+        .decode(body)
+        .unwrap();
+    let mut data = Vec::new();
+    flate2::read::ZlibDecoder::new(compressed.as_slice())
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+#[cfg(unix)]
+fn trzsz_md5(data: &[u8]) -> Vec<u8> {
+    use md5::Digest;
+    md5::Md5::digest(data).to_vec()
+}
+
+#[cfg(unix)]
+fn trzsz_pair() -> (
+    std::os::unix::net::UnixStream,
+    std::os::unix::net::UnixStream,
+) {
+    let (local, remote) = std::os::unix::net::UnixStream::pair().unwrap();
+    local.set_nonblocking(true).unwrap();
+    (local, remote)
+}
+
+#[cfg(unix)]
+#[test]
+fn trzsz_upload_round_trip() {
+    use tauri_app_lib::infra::session::transfer::Link;
+    use tauri_app_lib::infra::session::trzsz;
+
+    let root = std::env::temp_dir().join(format!("trzsz-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&root).unwrap();
+    // 超过一个数据块，最后一块不满
+    let binary: Vec<u8> = (0..80_000u32).map(|index| (index % 251) as u8).collect();
+    let files = vec![root.join("data.bin"), root.join("empty.txt")];
+    std::fs::write(&files[0], &binary).unwrap();
+    std::fs::write(&files[1], b"").unwrap();
+
+    let (mut local, remote) = trzsz_pair();
+    let receiver = std::thread::spawn(move || {
+        let mut remote = TrzszRemote::new(remote);
+        remote.handshake();
+        let count = remote.recv_integer("NUM");
+        remote.send("SUCC", &count.to_string());
+        let mut received = Vec::new();
+        for _ in 0..count {
+            let name = String::from_utf8(remote.recv_binary("NAME")).unwrap();
+            remote.send_binary("SUCC", name.as_bytes());
+            let size = remote.recv_integer("SIZE");
+            remote.send("SUCC", &size.to_string());
+            let mut data = Vec::new();
+            while (data.len() as u64) < size {
+                let chunk = remote.recv_binary("DATA");
+                remote.send("SUCC", &chunk.len().to_string());
+                data.extend(chunk);
+            }
+            let digest = remote.recv_binary("MD5");
+            assert_eq!(digest, trzsz_md5(&data));
+            remote.send_binary("SUCC", &digest);
+            received.push((name, data));
+        }
+        let message = String::from_utf8(remote.recv_binary("EXIT")).unwrap();
+        (received, message)
+    });
+
+    let mut cancel = || false;
+    let mut link = Link::new(&mut local, Vec::new(), &mut cancel);
+    let mut uploaded = 0;
+    trzsz::upload(&mut link, &files, &mut |progress| {
+        if progress.file_index == 0 {
+            uploaded = progress.bytes;
+        }
+    })
+    .unwrap();
+    let (received, message) = receiver.join().unwrap();
+
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0].0, "data.bin");
+    assert_eq!(received[0].1, binary);
+    assert_eq!(received[1].0, "empty.txt");
+    assert!(received[1].1.is_empty());
+    assert_eq!(uploaded, binary.len() as u64);
+    assert!(message.contains('2'));
+    let _ = std::fs::remove_dir_all(root);
+}
+
+#[cfg(unix)]
+#[test]
+fn trzsz_download_round_trip_keeps_files_inside_target_dir() {
+    use tauri_app_lib::infra::session::transfer::Link;
+    use tauri_app_lib::infra::session::trzsz;
+
+    let root = std::env::temp_dir().join(format!("trzsz-{}", uuid::Uuid::new_v4()));
+    let target_dir = root.join("target");
+    std::fs::create_dir_all(&target_dir).unwrap();
+    std::fs::write(target_dir.join("report.csv"), b"existing").unwrap();
+
+    let report: Vec<u8> = (0..50_000u32).map(|index| (index % 97) as u8).collect();
+    // 远端给出带目录的名字时只保留最后一段
+    let sent = vec![
+        ("report.csv".to_string(), report.clone()),
+        ("../../escape.txt".to_string(), b"hello".to_vec()),
+    ];
+    let (mut local, remote) = trzsz_pair();
+    let sender = std::thread::spawn(move || {
+        let mut remote = TrzszRemote::new(remote);
+        remote.handshake();
+        remote.send("NUM", &sent.len().to_string());
+        assert_eq!(remote.recv_integer("SUCC"), sent.len() as u64);
+        for (name, data) in &sent {
+            remote.send_binary("NAME", name.as_bytes());
+            remote.recv_binary("SUCC");
+            remote.send("SIZE", &data.len().to_string());
+            assert_eq!(remote.recv_integer("SUCC"), data.len() as u64);
+            for chunk in data.chunks(16 * 1024) {
+                remote.send_binary("DATA", chunk);
+                assert_eq!(remote.recv_integer("SUCC"), chunk.len() as u64);
+            }
+            let digest = trzsz_md5(data);
+            remote.send_binary("MD5", &digest);
+            assert_eq!(remote.recv_binary("SUCC"), digest);
+        }
+        remote.recv_binary("EXIT");
+    });
+
+    let mut cancel = || false;
+    let mut link = Link::new(&mut local, Vec::new(), &mut cancel);
+    let saved = trzsz::download(&mut link, &target_dir, &mut |_| {}).unwrap();
+    sender.join().unwrap();
+
+    assert_eq!(saved.len(), 2);
+    assert!(saved[0].ends_with("report (1).csv"));
+    assert_eq!(std::fs::read(&saved[0]).unwrap(), report);
+    assert_eq!(
+        std::path::Path::new(&saved[1]),
+        target_dir.join("escape.txt")
+    );
+    assert_eq!(std::fs::read(&saved[1]).unwrap(), b"hello");
+    assert!(!root.join("escape.txt").exists());
+    assert_eq!(
+        std::fs::read(target_dir.join("report.csv")).unwrap(),
+        b"existing"
+    );
+    let _ = std::fs::remove_dir_all(root);
+}
+
+#[cfg(unix)]
+#[test]
+fn trzsz_stops_on_remote_fail_and_sends_abort_reason() {
+    use tauri_app_lib::infra::session::transfer::Link;
+    use tauri_app_lib::infra::session::trzsz;
+
+    let target_dir = std::env::temp_dir().join(format!("trzsz-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&target_dir).unwrap();
+
+    for kind in ["fail", "EXIT"] {
+        let (mut local, remote) = trzsz_pair();
+        let remote_side = std::thread::spawn(move || {
+            let mut remote = TrzszRemote::new(remote);
+            remote.handshake();
+            remote.send_binary(kind, "用户取消".as_bytes());
+            // 客户端出错后由会话层调用 abort，远端应收到原因
+            String::from_utf8(remote.recv_binary("fail")).unwrap()
+        });
+
+        let mut cancel = || false;
+        let mut link = Link::new(&mut local, Vec::new(), &mut cancel);
+        let err = trzsz::download(&mut link, &target_dir, &mut |_| {}).unwrap_err();
+        assert!(err.to_string().contains("用户取消"), "{kind}: {err}");
+        trzsz::abort(&mut link, "本地已放弃").unwrap();
+        assert_eq!(remote_side.join().unwrap(), "本地已放弃");
+    }
+    assert_eq!(std::fs::read_dir(&target_dir).unwrap().count(), 0);
+    let _ = std::fs::remove_dir_all(target_dir);
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use tauri_app_lib::domain::models::{ExpectBranch, ScriptAction, ScriptJump, ScriptStep};
+use tauri_app_lib::domain::services::script_service::{SaveScript, ScriptService};
+use tauri_app_lib::infra::db::sqlite::SqliteScriptRepository;
+
+fn step(label: Option<&str>, action: ScriptAction) -> ScriptStep {
+    ScriptStep {
+        label: label.map(str::to_string),
+        action,
+    }
+}
+
+fn save(steps: Vec<ScriptStep>) -> SaveScript {
+    SaveScript {
+        id: None,
+        name: "deploy".into(),
+        description: None,
+        steps,
+    }
+}
+
+#[test]
+fn save_script_rejects_bad_patterns_and_unknown_labels() {
+    let path = std::env::temp_dir().join(format!("scripts-{}.db", uuid::Uuid::new_v4()));
+    let service = ScriptService::new(Arc::new(SqliteScriptRepository::new(&path).unwrap()));
+    let expect = |pattern: &str, then: ScriptJump| ScriptAction::Expect {
+        branches: vec![ExpectBranch {
+            pattern: pattern.into(),
+            then,
+        }],
+        timeout_ms: None,
+        on_timeout: None,
+    };
+
+    let err = service
+        .save_script(save(vec![step(
+            None,
+            expect("(unclosed", ScriptJump::Next),
+        )]))
+        .unwrap_err();
+    assert!(err.to_string().contains("无效的匹配规则"), "{err}");
+    let err = service
+        .save_script(save(vec![step(
+            None,
+            ScriptAction::Jump {
+                to: ScriptJump::Goto {
+                    label: "missing".into(),
+                },
+            },
+        )]))
+        .unwrap_err();
+    assert!(err.to_string().contains("不存在的标签: missing"), "{err}");
+    let err = service
+        .save_script(save(vec![
+            step(Some("again"), ScriptAction::Sleep { ms: 10 }),
+            step(Some("again"), ScriptAction::Sleep { ms: 10 }),
+        ]))
+        .unwrap_err();
+    assert!(err.to_string().contains("标签重复"), "{err}");
+    assert!(service.list_scripts().unwrap().is_empty());
+
+    let saved = service
+        .save_script(save(vec![
+            step(
+                Some("login"),
+                expect(
+                    r"\$ $",
+                    ScriptJump::Goto {
+                        label: "login".into(),
+                    },
+                ),
+            ),
+            step(
+                None,
+                ScriptAction::Send {
+                    data: "deploy\n".into(),
+                },
+            ),
+        ]))
+        .unwrap();
+    assert_eq!(service.list_scripts().unwrap()[0].id, saved.id);
+    let _ = std::fs::remove_file(&path);
+}
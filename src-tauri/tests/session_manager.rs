@@ -0,0 +1,1642 @@
+mod support;
+
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::Value;
+use tauri_app_lib::domain::models::{
+    AutomationScript, ExpectBranch, ScriptAction, ScriptJump, ScriptStep, ScrollbackQuery,
+    SessionPolicy, SessionPolicySettings, SessionState, SessionStateReason, ShellProfile,
+    TranscriptFormat, TranscriptSettings,
+};
+use tauri_app_lib::domain::services::session_service::SessionHistoryRepository;
+use tauri_app_lib::infra::db::sqlite::SqliteSessionHistoryRepository;
+use tauri_app_lib::infra::session::events::Events;
+use tauri_app_lib::infra::session::exec::ExecRequest;
+use tauri_app_lib::infra::session::profile::detect_shell_profiles;
+use tauri_app_lib::infra::session::SessionManager;
+
+use support::{ssh_connection, Collector};
+
+#[cfg(unix)]
+#[tokio::test]
+async fn local_shell_runs_input_and_reports_exit() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+
+    manager
+        .send_input(&session_id, "echo headless-ok\n")
+        .await
+        .unwrap();
+    collector
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "headless-ok"
+        })
+        .await;
+
+    manager.send_input(&session_id, "exit 3\n").await.unwrap();
+    let closed = collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(closed["exit_code"], 3);
+    assert_eq!(closed["reason"], "process_exited");
+    assert!(collector
+        .output_of(&session_id)
+        .contains("本地 shell 已启动"));
+
+    // 结束后终态仍可查询，便于前端在收到 session-closed 之后同步原因
+    let state = manager.session_state(&session_id).unwrap();
+    assert_eq!(state.state, SessionState::Closed);
+    assert_eq!(state.reason, Some(SessionStateReason::ProcessExited));
+    let record = manager
+        .session_history()
+        .into_iter()
+        .find(|record| record.session_id == session_id)
+        .unwrap();
+    assert_eq!(record.exit_code, Some(3));
+    assert_eq!(record.close_reason, Some(SessionStateReason::ProcessExited));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn closing_local_shell_records_user_close() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+
+    manager.close_session(&session_id).await.unwrap();
+    let closed = collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(closed["reason"], "user_closed");
+    assert!(manager.send_input(&session_id, "ls\n").await.is_err());
+}
+
+#[tokio::test]
+async fn ssh_connect_failure_marks_session_failed() {
+    // 绑定后立即释放，得到一个大概率无人监听的端口。
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(ssh_connection(port)),
+            None,
+        )
+        .await
+        .unwrap();
+
+    let failed = collector
+        .wait_for("session-state", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["state"] == "failed"
+        })
+        .await;
+    assert_eq!(failed["error_code"], "connect_failed");
+    collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+
+    let states: Vec<Value> = collector
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(name, _)| name == "session-state")
+        .map(|(_, payload)| payload["state"].clone())
+        .collect();
+    assert_eq!(states, vec!["connecting", "failed"]);
+    assert!(collector.output_of(&session_id).contains("SSH 会话错误"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn finished_session_is_persisted_with_traffic() {
+    let db_path =
+        std::env::temp_dir().join(format!("session-history-{}.sqlite3", uuid::Uuid::new_v4()));
+    let store = Arc::new(SqliteSessionHistoryRepository::new(&db_path).unwrap());
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    manager.set_history_store(store.clone());
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+
+    let live = manager.live_sessions();
+    let summary = live
+        .iter()
+        .find(|summary| summary.id == session_id)
+        .unwrap();
+    assert_eq!(summary.state, SessionState::Connected);
+    assert_eq!(summary.connection_id, None);
+
+    manager
+        .send_input(&session_id, "echo persisted\n")
+        .await
+        .unwrap();
+    collector
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "persisted"
+        })
+        .await;
+    manager.send_input(&session_id, "exit 0\n").await.unwrap();
+    collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert!(manager
+        .live_sessions()
+        .iter()
+        .all(|summary| summary.id != session_id));
+
+    let records = store.recent(10).unwrap();
+    let record = records
+        .iter()
+        .find(|record| record.session_id == session_id)
+        .unwrap();
+    assert_eq!(record.exit_code, Some(0));
+    assert_eq!(
+        record.bytes_out,
+        ("echo persisted\n".len() + "exit 0\n".len()) as u64
+    );
+    assert!(record.bytes_in >= "persisted\n".len() as u64);
+    assert!(record.duration_ms.is_some());
+    let _ = std::fs::remove_file(db_path);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn shell_integration_marks_record_commands_and_cwd() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+
+    // 模拟一个输出 OSC 7 / OSC 133 标记的 shell：上报目录，输入 `make test`，以退出码 2 结束。
+    let marks = concat!(
+        r"printf '\033]7;file://host/tmp/build%%20dir\007\033]133;A\007$ \033]133;B\007make test\n",
+        r"\033]133;C\007running\n\033]133;D;2\007\n'",
+        "\n",
+    );
+    manager.send_input(&session_id, marks).await.unwrap();
+    let event = collector
+        .wait_for("session-command", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(event["command"]["command"], "make test");
+    assert_eq!(event["command"]["exitCode"], 2);
+    assert_eq!(event["command"]["cwd"], "/tmp/build dir");
+    collector
+        .wait_for("session-cwd", |payload| payload["cwd"] == "/tmp/build dir")
+        .await;
+    assert_eq!(
+        manager.session_cwd(&session_id).as_deref(),
+        Some("/tmp/build dir")
+    );
+
+    // 回显里带退格编辑时还原成最终文本；OSC 633 `E` 只有带本会话 nonce 时才以上报为准，
+    // 伪造的上报（如远端输出的文件内容）被忽略。
+    let nonce = manager.shell_integration_nonce(&session_id).unwrap();
+    let marks = [
+        r"printf '\033]133;A\007$ \033]133;B\007mkae\b\b\b\033[Kake x\bbuild\n",
+        r"\033]633;E;rm -rf ~\007\033]633;E;rm -rf ~;forged\007",
+        r"\033]133;C\007\033]133;D;0\007",
+        r"\033]633;A\007$ \033]633;B\007gti stauts\r$ git status\n",
+        &format!(r"\033]633;E;git status\\x3b echo \\\\done;{nonce}\033\\"),
+        r"\033]633;C\007\033]633;D;0\007\n'",
+        "\n",
+    ]
+    .concat();
+    manager.send_input(&session_id, &marks).await.unwrap();
+    collector
+        .wait_for("session-command", |payload| {
+            payload["session_id"] == session_id.as_str()
+                && payload["command"]["command"] == "git status; echo \\done"
+        })
+        .await;
+    let commands: Vec<String> = manager
+        .session_commands(&session_id)
+        .into_iter()
+        .map(|entry| entry.command)
+        .collect();
+    assert_eq!(
+        commands,
+        ["make test", "make build", "git status; echo \\done"]
+    );
+    assert!(manager.rerun_last_command(&session_id).await.is_ok());
+    manager.close_session(&session_id).await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn screen_model_tracks_rendered_output() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+
+    manager
+        .send_input(&session_id, "echo first\necho second\n")
+        .await
+        .unwrap();
+    collector
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "second"
+        })
+        .await;
+    // 屏幕模型与事件推送各自订阅输出，稍等解析器处理完同一批数据
+    for _ in 0..50 {
+        let lines = manager.screen_lines(&session_id).unwrap();
+        if lines.iter().any(|line| line == "second") {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+
+    let snapshot = manager.screen_snapshot(&session_id).unwrap();
+    assert_eq!((snapshot.rows, snapshot.cols), (24, 80));
+    assert!(!snapshot.alternate_screen);
+    let first = snapshot.lines.iter().position(|line| line == "first");
+    let second = snapshot.lines.iter().position(|line| line == "second");
+    assert!(first.is_some() && first < second);
+
+    let thumbnail = manager.screen_thumbnail(&session_id, 1, 3).unwrap();
+    assert_eq!(thumbnail, vec!["sec".to_string()]);
+    assert!(manager
+        .screen_lines(&session_id)
+        .unwrap()
+        .contains(&"first".to_string()));
+
+    manager.close_session(&session_id).await.unwrap();
+    collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert!(manager.screen_snapshot(&session_id).is_none());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn screen_lines_include_scrollback_beyond_one_screen() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+
+    manager
+        .send_input(
+            &session_id,
+            "i=1; while [ $i -le 60 ]; do echo row-$i; i=$((i+1)); done\n",
+        )
+        .await
+        .unwrap();
+    collector
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "row-60"
+        })
+        .await;
+    let mut lines = Vec::new();
+    for _ in 0..50 {
+        lines = manager.screen_lines(&session_id).unwrap();
+        if lines.iter().any(|line| line == "row-60") {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+
+    let rows: Vec<&String> = lines
+        .iter()
+        .filter(|line| line.starts_with("row-"))
+        .collect();
+    let expected: Vec<String> = (1..=60).map(|i| format!("row-{i}")).collect();
+    assert_eq!(rows, expected.iter().collect::<Vec<_>>());
+    // 读取回滚后屏幕尺寸与可见内容不变，之后的输出照常写入
+    let snapshot = manager.screen_snapshot(&session_id).unwrap();
+    assert_eq!((snapshot.rows, snapshot.cols), (24, 80));
+    assert!(snapshot.lines.contains(&"row-60".to_string()));
+    assert!(!snapshot.lines.contains(&"row-1".to_string()));
+    manager
+        .send_input(&session_id, "echo tail\n")
+        .await
+        .unwrap();
+    for _ in 0..50 {
+        if manager
+            .screen_lines(&session_id)
+            .unwrap()
+            .contains(&"tail".to_string())
+        {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(manager
+        .screen_snapshot(&session_id)
+        .unwrap()
+        .lines
+        .contains(&"tail".to_string()));
+
+    manager.close_session(&session_id).await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn search_scrollback_across_live_sessions() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let mut sessions = Vec::new();
+    for marker in ["alpha-17", "beta-42"] {
+        let session_id = manager
+            .create_shell_session(Events::new(collector.clone()), None, None)
+            .await
+            .unwrap();
+        manager
+            .send_input(
+                &session_id,
+                &format!("echo before\necho {marker}\necho after\n"),
+            )
+            .await
+            .unwrap();
+        sessions.push(session_id);
+    }
+    for session_id in &sessions {
+        for _ in 0..50 {
+            let lines = manager.screen_lines(session_id).unwrap();
+            if lines.iter().any(|line| line == "after") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    }
+
+    let query = |pattern: &str, regex: bool| ScrollbackQuery {
+        pattern: pattern.into(),
+        regex,
+        case_sensitive: false,
+        context_lines: Some(1),
+        limit: None,
+    };
+    let mut hits = manager
+        .search_scrollback(None, &query(r"(alpha|beta)-\d+", true))
+        .unwrap();
+    assert_eq!(hits.len(), 2);
+    // 两个会话几乎同时开始，不依赖会话之间的顺序
+    hits.sort_by(|a, b| a.line.cmp(&b.line));
+    assert_eq!(hits[0].session_id, sessions[0]);
+    assert_eq!(hits[0].line, "alpha-17");
+    assert_eq!((hits[0].match_start, hits[0].match_end), (0, 8));
+    assert_eq!(hits[0].before, vec!["before".to_string()]);
+    assert_eq!(hits[0].after, vec!["after".to_string()]);
+    assert_eq!(hits[1].session_id, sessions[1]);
+
+    let hits = manager
+        .search_scrollback(Some(&sessions[1]), &query("BETA-", false))
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].line, "beta-42");
+    assert!(manager
+        .search_scrollback(Some(&sessions[1]), &query("(", true))
+        .is_err());
+    assert!(manager
+        .search_scrollback(Some("missing"), &query("beta", false))
+        .is_err());
+
+    for session_id in &sessions {
+        manager.close_session(session_id).await.unwrap();
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn search_finds_lines_scrolled_off_screen() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+    manager
+        .send_input(
+            &session_id,
+            "echo needle-early\ni=1; while [ $i -le 200 ]; do echo filler-$i; i=$((i+1)); done\n",
+        )
+        .await
+        .unwrap();
+    collector
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "filler-200"
+        })
+        .await;
+    for _ in 0..50 {
+        let lines = manager.screen_lines(&session_id).unwrap();
+        if lines.iter().any(|line| line == "filler-200") {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+
+    let query = |pattern: &str| ScrollbackQuery {
+        pattern: pattern.into(),
+        regex: true,
+        case_sensitive: true,
+        context_lines: Some(1),
+        limit: None,
+    };
+    let hits = manager
+        .search_scrollback(Some(&session_id), &query("needle-early"))
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].after, vec!["filler-1".to_string()]);
+    let lines = manager.screen_lines(&session_id).unwrap();
+    assert_eq!(lines[hits[0].line_number - 1], "needle-early");
+
+    let hits = manager
+        .search_scrollback(Some(&session_id), &query(r"^filler-\d+$"))
+        .unwrap();
+    assert_eq!(hits.len(), 200);
+    assert_eq!(hits[0].line, "filler-1");
+    assert_eq!(hits[199].line, "filler-200");
+    assert!(hits
+        .windows(2)
+        .all(|pair| pair[1].line_number == pair[0].line_number + 1));
+
+    manager.close_session(&session_id).await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn observer_receives_output_but_cannot_type() {
+    let owner = Collector::default();
+    let observer = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(owner.clone()).for_target("main"), None, None)
+        .await
+        .unwrap();
+
+    let attachment = manager
+        .attach_observer(&session_id, "observer", Events::new(observer.clone()))
+        .await
+        .unwrap();
+    assert_eq!(attachment.window, "observer");
+    assert!(attachment.snapshot.is_some());
+    owner
+        .wait_for("session-observers", |payload| {
+            payload["sessionId"] == session_id.as_str() && payload["windows"][0] == "observer"
+        })
+        .await;
+
+    manager
+        .send_input_from("main", &session_id, "echo pairing\n")
+        .await
+        .unwrap();
+    observer
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "pairing"
+        })
+        .await;
+
+    let err = manager
+        .send_input_from("observer", &session_id, "echo intruder\n")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("只读"));
+    assert!(manager
+        .ensure_writable("observer", &session_id)
+        .await
+        .is_err());
+    // 脚本同样会写入输入，观察窗口不能发起
+    let script = AutomationScript {
+        id: "script-pairing".into(),
+        name: "pairing".into(),
+        description: None,
+        steps: vec![ScriptStep {
+            label: None,
+            action: ScriptAction::Send {
+                data: "echo from-script\n".into(),
+            },
+        }],
+        created_at: String::new(),
+        updated_at: String::new(),
+    };
+    let err = manager
+        .run_script_from(
+            "observer",
+            Events::new(observer.clone()),
+            &session_id,
+            script.clone(),
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("只读"));
+    manager
+        .run_script_from("main", Events::new(owner.clone()), &session_id, script)
+        .await
+        .unwrap();
+    owner
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "from-script"
+        })
+        .await;
+
+    // 只有附加所在的窗口或会话所属窗口可以解除附加
+    let other = manager
+        .attach_observer(&session_id, "other", Events::new(observer.clone()))
+        .await
+        .unwrap();
+    assert!(manager
+        .detach_observer("other", &attachment.attachment_id)
+        .await
+        .is_err());
+    manager
+        .detach_observer("main", &other.attachment_id)
+        .await
+        .unwrap();
+    manager
+        .detach_observer("observer", &attachment.attachment_id)
+        .await
+        .unwrap();
+    assert!(manager.list_observers(&session_id).is_empty());
+    manager
+        .send_input_from("observer", &session_id, "echo after-detach\n")
+        .await
+        .unwrap();
+    owner
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "after-detach"
+        })
+        .await;
+    assert!(!observer.output_of(&session_id).contains("after-detach"));
+    assert!(!owner.output_of(&session_id).contains("intruder"));
+    manager.close_session(&session_id).await.unwrap();
+}
+
+fn script(steps: Vec<(Option<&str>, ScriptAction)>) -> AutomationScript {
+    AutomationScript {
+        id: "script-test".into(),
+        name: "test".into(),
+        description: None,
+        steps: steps
+            .into_iter()
+            .map(|(label, action)| ScriptStep {
+                label: label.map(str::to_string),
+                action,
+            })
+            .collect(),
+        created_at: String::new(),
+        updated_at: String::new(),
+    }
+}
+
+fn expect(pattern: &str, then: ScriptJump) -> ExpectBranch {
+    ExpectBranch {
+        pattern: pattern.into(),
+        then,
+    }
+}
+
+fn goto(label: &str) -> ScriptJump {
+    ScriptJump::Goto {
+        label: label.into(),
+    }
+}
+
+async fn script_finished(collector: &Collector, run_id: &str) -> Value {
+    collector
+        .wait_for("script-finished", |payload| payload["run_id"] == run_id)
+        .await
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn script_branches_on_expected_output_and_falls_back_on_timeout() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+
+    // 输出由 shell 计算得出，命中的只可能是真实输出而不是回显的输入
+    let run_id = manager
+        .run_script(
+            Events::new(collector.clone()),
+            &session_id,
+            script(vec![
+                (
+                    None,
+                    ScriptAction::Send {
+                        data: "echo ready-$((40 + 2))\n".into(),
+                    },
+                ),
+                (
+                    Some("wait"),
+                    ScriptAction::Expect {
+                        branches: vec![
+                            expect("never-printed", ScriptJump::Next),
+                            expect(r"ready-\d+", goto("quiet")),
+                        ],
+                        timeout_ms: Some(5000),
+                        on_timeout: None,
+                    },
+                ),
+                (
+                    None,
+                    ScriptAction::Jump {
+                        to: ScriptJump::Fail {
+                            message: Some("不应执行到这里".into()),
+                        },
+                    },
+                ),
+                (
+                    Some("quiet"),
+                    ScriptAction::Expect {
+                        branches: vec![expect("never-printed", ScriptJump::Next)],
+                        timeout_ms: Some(100),
+                        on_timeout: Some(goto("done")),
+                    },
+                ),
+                (
+                    None,
+                    ScriptAction::Jump {
+                        to: ScriptJump::Fail { message: None },
+                    },
+                ),
+                (Some("done"), ScriptAction::Sleep { ms: 10 }),
+            ]),
+        )
+        .await
+        .unwrap();
+    let finished = script_finished(&collector, &run_id).await;
+    assert_eq!(finished["success"], true, "{finished}");
+    let progress: Vec<(u64, String, Value)> = collector
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(name, payload)| name == "script-progress" && payload["run_id"] == run_id)
+        .map(|(_, payload)| {
+            (
+                payload["step"].as_u64().unwrap(),
+                payload["action"].as_str().unwrap().to_string(),
+                payload["message"].clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        progress,
+        [
+            (0, "send".to_string(), Value::Null),
+            (1, "expect".to_string(), Value::Null),
+            (1, "matched".to_string(), Value::from("ready-42")),
+            (3, "expect".to_string(), Value::Null),
+            (3, "timeout".to_string(), Value::Null),
+            (5, "sleep".to_string(), Value::Null),
+        ]
+    );
+
+    // 没有超时分支时脚本失败，并指出是第几步
+    let run_id = manager
+        .run_script(
+            Events::new(collector.clone()),
+            &session_id,
+            script(vec![(
+                None,
+                ScriptAction::Expect {
+                    branches: vec![expect("never-printed", ScriptJump::Succeed)],
+                    timeout_ms: Some(100),
+                    on_timeout: None,
+                },
+            )]),
+        )
+        .await
+        .unwrap();
+    let finished = script_finished(&collector, &run_id).await;
+    assert_eq!(finished["success"], false);
+    assert_eq!(finished["message"], "第 1 步等待输出超时");
+    manager.close_session(&session_id).await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn script_on_new_session_matches_output_from_before_it_started() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+    manager
+        .send_input(&session_id, "echo early-$((1 + 1))\n")
+        .await
+        .unwrap();
+    collector
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "early-2"
+        })
+        .await;
+
+    let wait_early = || {
+        script(vec![(
+            None,
+            ScriptAction::Expect {
+                branches: vec![expect(r"early-\d", ScriptJump::Succeed)],
+                timeout_ms: Some(300),
+                on_timeout: None,
+            },
+        )])
+    };
+    // 普通运行只匹配之后的输出
+    let run_id = manager
+        .run_script(Events::new(collector.clone()), &session_id, wait_early())
+        .await
+        .unwrap();
+    assert_eq!(script_finished(&collector, &run_id).await["success"], false);
+    // 刚创建的会话从头匹配，脚本订阅之前的输出也不会错过
+    let run_id = manager
+        .run_script_from_start(Events::new(collector.clone()), &session_id, wait_early())
+        .await
+        .unwrap();
+    let finished = script_finished(&collector, &run_id).await;
+    assert_eq!(finished["success"], true, "{finished}");
+    manager.close_session(&session_id).await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn script_rejects_bad_steps_and_stops_endless_loops() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+
+    let err = manager
+        .run_script(
+            Events::new(collector.clone()),
+            &session_id,
+            script(vec![(
+                None,
+                ScriptAction::Jump {
+                    to: goto("missing"),
+                },
+            )]),
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("不存在的标签: missing"));
+    let err = manager
+        .run_script(
+            Events::new(collector.clone()),
+            &session_id,
+            script(vec![(
+                None,
+                ScriptAction::Expect {
+                    branches: vec![expect("(unclosed", ScriptJump::Next)],
+                    timeout_ms: None,
+                    on_timeout: None,
+                },
+            )]),
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("无效的匹配规则"));
+
+    let run_id = manager
+        .run_script(
+            Events::new(collector.clone()),
+            &session_id,
+            script(vec![(
+                Some("again"),
+                ScriptAction::Jump { to: goto("again") },
+            )]),
+        )
+        .await
+        .unwrap();
+    let finished = script_finished(&collector, &run_id).await;
+    assert_eq!(finished["success"], false);
+    assert!(finished["message"]
+        .as_str()
+        .is_some_and(|message| message.contains("死循环")));
+    manager.close_session(&session_id).await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn script_stops_on_cancel_and_when_session_ends() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+
+    let run_id = manager
+        .run_script(
+            Events::new(collector.clone()),
+            &session_id,
+            script(vec![(None, ScriptAction::Sleep { ms: 60_000 })]),
+        )
+        .await
+        .unwrap();
+    assert!(manager.cancel_command(&run_id));
+    let finished = script_finished(&collector, &run_id).await;
+    assert_eq!(finished["cancelled"], true);
+    assert_eq!(finished["success"], false);
+
+    let run_id = manager
+        .run_script(
+            Events::new(collector.clone()),
+            &session_id,
+            script(vec![(
+                None,
+                ScriptAction::Expect {
+                    branches: vec![expect("never-printed", ScriptJump::Succeed)],
+                    timeout_ms: Some(60_000),
+                    on_timeout: None,
+                },
+            )]),
+        )
+        .await
+        .unwrap();
+    manager.close_session(&session_id).await.unwrap();
+    let finished = script_finished(&collector, &run_id).await;
+    assert_eq!(finished["success"], false);
+    assert_eq!(finished["cancelled"], false);
+    assert_eq!(finished["message"], "会话已结束");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn idle_session_is_warned_then_closed_by_policy() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    manager
+        .update_session_policy_settings(SessionPolicySettings {
+            global: SessionPolicy {
+                idle_timeout_secs: Some(2),
+                max_duration_secs: Some(3600),
+            },
+            warning_secs: 1,
+            ..Default::default()
+        })
+        .unwrap();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+
+    let warning = collector
+        .wait_for("session-policy-warning", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(warning["reason"], "idle_timeout");
+    let closed = collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(closed["reason"], "idle_timeout");
+    assert!(closed["message"].as_str().unwrap().contains("2 秒"));
+
+    let record = manager
+        .session_history()
+        .into_iter()
+        .find(|record| record.session_id == session_id)
+        .unwrap();
+    assert_eq!(record.close_reason, Some(SessionStateReason::IdleTimeout));
+}
+
+#[test]
+fn session_policy_file_is_reloaded_and_malformed_file_is_rejected() {
+    let data_dir = std::env::temp_dir().join(format!("session-policy-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let manager = SessionManager::new();
+    manager.init_session_policy(&data_dir).unwrap();
+    assert_eq!(
+        manager.session_policy_settings().global,
+        SessionPolicy::default()
+    );
+    manager
+        .update_session_policy_settings(SessionPolicySettings {
+            global: SessionPolicy {
+                idle_timeout_secs: Some(600),
+                max_duration_secs: None,
+            },
+            ..Default::default()
+        })
+        .unwrap();
+
+    let reloaded = SessionManager::new();
+    reloaded.init_session_policy(&data_dir).unwrap();
+    assert_eq!(
+        reloaded.session_policy_settings().global.idle_timeout_secs,
+        Some(600)
+    );
+
+    // 格式错误时报错，而不是退回默认值后在下次保存时覆盖用户的文件
+    let path = data_dir.join("session_policy.json");
+    std::fs::write(&path, "{ \"global\": ").unwrap();
+    let err = SessionManager::new()
+        .init_session_policy(&data_dir)
+        .unwrap_err();
+    assert!(err.to_string().contains("格式错误"), "{err}");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ \"global\": ");
+    let _ = std::fs::remove_dir_all(data_dir);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn default_shell_profile_starts_new_local_tabs() {
+    let data_dir = std::env::temp_dir().join(format!("shell-profiles-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    manager.init_shell_profiles(&data_dir).unwrap();
+    let profile = manager
+        .save_shell_profile(ShellProfile {
+            id: String::new(),
+            name: "work sh".into(),
+            program: "/bin/sh".into(),
+            args: vec!["-i".into()],
+            env: [("PROFILE_MARK".to_string(), "from-profile".to_string())].into(),
+            cwd: Some("/".into()),
+            login: true,
+            detected: false,
+        })
+        .unwrap();
+    manager
+        .set_default_shell_profile(Some(&profile.id))
+        .unwrap();
+
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+    manager
+        .send_input(&session_id, "echo \"$PROFILE_MARK:$(pwd)\"\n")
+        .await
+        .unwrap();
+    collector
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "from-profile:/"
+        })
+        .await;
+    manager.close_session(&session_id).await.unwrap();
+
+    // 重新加载后默认配置仍在；删除后退回系统 shell。
+    let reloaded = SessionManager::new();
+    reloaded.init_shell_profiles(&data_dir).unwrap();
+    assert_eq!(
+        reloaded.shell_profiles().default_profile_id.as_deref(),
+        Some(profile.id.as_str())
+    );
+    reloaded.delete_shell_profile(&profile.id).unwrap();
+    assert!(reloaded.shell_profiles().default_profile_id.is_none());
+    assert!(manager
+        .create_local_shell(Events::new(collector.clone()), Some("missing"))
+        .await
+        .is_err());
+
+    // 格式错误的配置文件报错并保持原样，不会被空列表覆盖
+    let path = data_dir.join("shell_profiles.json");
+    std::fs::write(&path, "{ \"profiles\": [").unwrap();
+    let err = SessionManager::new()
+        .init_shell_profiles(&data_dir)
+        .unwrap_err();
+    assert!(err.to_string().contains("格式错误"), "{err}");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ \"profiles\": [");
+}
+
+#[test]
+fn installed_shells_are_detected_from_shells_file() {
+    let shells = std::env::temp_dir().join(format!("shells-{}", uuid::Uuid::new_v4()));
+    std::fs::write(
+        &shells,
+        "# /etc/shells: valid login shells\n/bin/sh\n/usr/bin/sh\n/usr/sbin/nologin\n/no/such/zsh\n",
+    )
+    .unwrap();
+    let detected = detect_shell_profiles(&shells);
+    assert_eq!(detected.len(), 1);
+    assert_eq!(detected[0].name, "sh");
+    assert_eq!(detected[0].program, "/bin/sh");
+    assert!(detected[0].detected);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn daemon_sessions_survive_app_restart_with_scrollback() {
+    use tauri_app_lib::infra::session::daemon::run_daemon;
+
+    let socket = std::env::temp_dir().join(format!("sessiond-{}.sock", uuid::Uuid::new_v4()));
+    let daemon = tokio::spawn({
+        let socket = socket.clone();
+        async move { run_daemon(&socket).await }
+    });
+    while !socket.exists() {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+
+    let before = Collector::default();
+    let first_app = SessionManager::new();
+    first_app.set_local_daemon(socket.clone(), None);
+    first_app.set_persistent_local_sessions(true).unwrap();
+    let session_id = first_app
+        .create_shell_session(Events::new(before.clone()), None, None)
+        .await
+        .unwrap();
+    first_app
+        .send_input(&session_id, "echo before-restart\n")
+        .await
+        .unwrap();
+    before
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "before-restart"
+        })
+        .await;
+
+    // 新的应用实例从守护进程重新附加，回滚缓冲中的旧输出一并回放。
+    let after = Collector::default();
+    let second_app = SessionManager::new();
+    second_app.set_local_daemon(socket.clone(), None);
+    second_app.set_persistent_local_sessions(true).unwrap();
+    let attached = second_app
+        .reattach_local_sessions(Events::new(after.clone()))
+        .await
+        .unwrap();
+    assert_eq!(attached, vec![session_id.clone()]);
+    after
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str()
+                && payload["data"]
+                    .as_str()
+                    .is_some_and(|data| data.contains("before-restart"))
+        })
+        .await;
+    // 重新附加先报告 reconnecting，附加完成后回到 connected
+    let states: Vec<String> = after
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(event, payload)| {
+            event == "session-state" && payload["session_id"] == session_id.as_str()
+        })
+        .map(|(_, payload)| payload["state"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(states, vec!["reconnecting", "connected"]);
+    assert_eq!(
+        second_app.session_state(&session_id).unwrap().state,
+        SessionState::Connected
+    );
+
+    second_app
+        .send_input(&session_id, "echo after-restart\n")
+        .await
+        .unwrap();
+    after
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "after-restart"
+        })
+        .await;
+
+    second_app.close_session(&session_id).await.unwrap();
+    let closed = after
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(closed["reason"], "user_closed");
+    tokio::time::timeout(std::time::Duration::from_secs(10), daemon)
+        .await
+        .expect("最后一个会话结束后守护进程应退出")
+        .unwrap()
+        .unwrap();
+    assert!(!socket.exists());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn daemon_keeps_shell_that_exits_before_attach() {
+    use tauri_app_lib::infra::session::daemon::run_daemon;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    let socket = std::env::temp_dir().join(format!("sessiond-{}.sock", uuid::Uuid::new_v4()));
+    let daemon = tokio::spawn({
+        let socket = socket.clone();
+        async move { run_daemon(&socket).await }
+    });
+    while !socket.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // 直接按协议请求启动，不附加：shell 在任何客户端附加之前就已退出
+    let profile = ShellProfile {
+        id: "short".into(),
+        name: "short".into(),
+        program: "/bin/sh".into(),
+        args: vec!["-c".into(), "echo short-lived; exit 3".into()],
+        env: Default::default(),
+        cwd: None,
+        login: false,
+        detected: false,
+    };
+    let stream = tokio::net::UnixStream::connect(&socket).await.unwrap();
+    let (read, mut write) = stream.into_split();
+    let request = serde_json::json!({ "type": "spawn", "profile": profile, "cwd": null });
+    write
+        .write_all(format!("{request}\n").as_bytes())
+        .await
+        .unwrap();
+    let reply = tokio::io::BufReader::new(read)
+        .lines()
+        .next_line()
+        .await
+        .unwrap()
+        .unwrap();
+    let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+    let session_id = reply["id"].as_str().unwrap().to_string();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    manager.set_local_daemon(socket.clone(), None);
+    manager.set_persistent_local_sessions(true).unwrap();
+    let attached = manager
+        .reattach_local_sessions(Events::new(collector.clone()))
+        .await
+        .unwrap();
+    assert_eq!(attached, vec![session_id.clone()]);
+    collector
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str()
+                && payload["data"]
+                    .as_str()
+                    .is_some_and(|data| data.contains("short-lived"))
+        })
+        .await;
+    let closed = collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(closed["exit_code"], 3);
+    tokio::time::timeout(Duration::from_secs(10), daemon)
+        .await
+        .expect("已退出的会话被取走后守护进程应退出")
+        .unwrap()
+        .unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_reattach_attaches_each_session_once() {
+    use tauri_app_lib::infra::session::daemon::run_daemon;
+
+    let socket = std::env::temp_dir().join(format!("sessiond-{}.sock", uuid::Uuid::new_v4()));
+    let daemon = tokio::spawn({
+        let socket = socket.clone();
+        async move { run_daemon(&socket).await }
+    });
+    while !socket.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let first_app = SessionManager::new();
+    first_app.set_local_daemon(socket.clone(), None);
+    first_app.set_persistent_local_sessions(true).unwrap();
+    let session_id = first_app
+        .create_shell_session(Events::new(Collector::default()), None, None)
+        .await
+        .unwrap();
+
+    let collector = Collector::default();
+    let second_app = SessionManager::new();
+    second_app.set_local_daemon(socket.clone(), None);
+    second_app.set_persistent_local_sessions(true).unwrap();
+    let (left, right) = tokio::join!(
+        second_app.reattach_local_sessions(Events::new(collector.clone())),
+        second_app.reattach_local_sessions(Events::new(collector.clone())),
+    );
+    let mut attached = left.unwrap();
+    attached.extend(right.unwrap());
+    assert_eq!(attached, vec![session_id.clone()]);
+
+    second_app.close_session(&session_id).await.unwrap();
+    collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    tokio::time::timeout(Duration::from_secs(10), daemon)
+        .await
+        .expect("最后一个会话结束后守护进程应退出")
+        .unwrap()
+        .unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn local_command_job_streams_output_and_feeds_large_stdin() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let result = manager
+        .run_command_job(
+            Events::new(collector.clone()),
+            "job-stream".into(),
+            None,
+            None,
+            ExecRequest {
+                command: "printf out; printf err >&2; exit 4".into(),
+                ..ExecRequest::default()
+            },
+            true,
+        )
+        .await
+        .unwrap();
+    assert_eq!(result.stdout, "out");
+    assert_eq!(result.stderr, "err");
+    assert_eq!(result.exit_code, Some(4));
+    let streamed: Vec<(String, String)> = collector
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(name, payload)| name == "command-output" && payload["job_id"] == "job-stream")
+        .map(|(_, payload)| {
+            (
+                payload["stream"].as_str().unwrap().to_string(),
+                payload["data"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    assert!(streamed.contains(&("stdout".into(), "out".into())));
+    assert!(streamed.contains(&("stderr".into(), "err".into())));
+
+    // 输入远大于管道容量，且 cat 要等输入关闭才会退出
+    let input = "0123456789abcdef\n".repeat(64 * 1024);
+    let result = manager
+        .run_command_job(
+            Events::new(collector.clone()),
+            "job-stdin".into(),
+            None,
+            None,
+            ExecRequest {
+                command: "cat".into(),
+                stdin: Some(input.clone()),
+                timeout: Some(Duration::from_secs(10)),
+                ..ExecRequest::default()
+            },
+            false,
+        )
+        .await
+        .unwrap();
+    assert!(!result.timed_out);
+    assert_eq!(result.exit_code, Some(0));
+    assert_eq!(result.stdout.len(), input.len());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn local_command_job_times_out_while_stdin_is_unread() {
+    let manager = SessionManager::new();
+    let started = std::time::Instant::now();
+    let result = manager
+        .run_command_job(
+            Events::new(Collector::default()),
+            "job-timeout".into(),
+            None,
+            None,
+            ExecRequest {
+                command: "sleep 5".into(),
+                stdin: Some("x".repeat(1024 * 1024)),
+                timeout: Some(Duration::from_millis(200)),
+                ..ExecRequest::default()
+            },
+            false,
+        )
+        .await
+        .unwrap();
+    assert!(result.timed_out);
+    assert!(!result.cancelled);
+    assert_eq!(result.exit_code, None);
+    assert!(started.elapsed() < Duration::from_secs(3));
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn local_command_job_timeout_kills_background_children() {
+    let manager = SessionManager::new();
+    let collector = Collector::default();
+    let result = manager
+        .run_command_job(
+            Events::new(collector.clone()),
+            "job-tree".into(),
+            None,
+            None,
+            ExecRequest {
+                command: "sleep 30 & echo $!; wait".into(),
+                timeout: Some(Duration::from_millis(300)),
+                ..ExecRequest::default()
+            },
+            false,
+        )
+        .await
+        .unwrap();
+    assert!(result.timed_out);
+    let pid = result.stdout.trim().to_string();
+    assert!(!pid.is_empty(), "{result:?}");
+
+    // 被终止的后台进程交由 init 回收，可能短暂停留为僵尸进程
+    let deadline = std::time::Instant::now() + Duration::from_secs(3);
+    loop {
+        let alive = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            .map(|stat| {
+                !stat
+                    .rsplit(')')
+                    .next()
+                    .unwrap_or("")
+                    .trim_start()
+                    .starts_with('Z')
+            })
+            .unwrap_or(false);
+        if !alive {
+            break;
+        }
+        assert!(
+            std::time::Instant::now() < deadline,
+            "后台进程 {pid} 仍在运行"
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn local_command_job_can_be_cancelled() {
+    let manager = SessionManager::new();
+    let job = tokio::spawn({
+        let manager = manager.clone();
+        async move {
+            manager
+                .run_command_job(
+                    Events::new(Collector::default()),
+                    "job-cancel".into(),
+                    None,
+                    None,
+                    ExecRequest {
+                        command: "sleep 5".into(),
+                        ..ExecRequest::default()
+                    },
+                    false,
+                )
+                .await
+        }
+    });
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while !manager.cancel_command("job-cancel") {
+        assert!(std::time::Instant::now() < deadline, "命令任务未登记");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let result = tokio::time::timeout(Duration::from_secs(3), job)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(result.cancelled);
+    assert!(!result.timed_out);
+    assert!(!manager.cancel_command("job-cancel"));
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn broadcast_drops_ended_members_and_stuck_writes_do_not_block_others() {
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    // 不读取输入的进程：写满管道后对它的写入会一直挂起
+    let stuck_profile = manager
+        .save_shell_profile(ShellProfile {
+            id: String::new(),
+            name: "stuck".into(),
+            program: "sleep".into(),
+            args: vec!["5".into()],
+            env: Default::default(),
+            cwd: None,
+            login: false,
+            detected: false,
+        })
+        .unwrap();
+    let stuck = manager
+        .create_local_shell(Events::new(collector.clone()), Some(&stuck_profile.id))
+        .await
+        .unwrap();
+    let mut shells = Vec::new();
+    for _ in 0..2 {
+        shells.push(
+            manager
+                .create_shell_session(Events::new(collector.clone()), None, None)
+                .await
+                .unwrap(),
+        );
+    }
+    let (live, ended) = (&shells[0], &shells[1]);
+    let group = manager
+        .create_broadcast_group(vec![stuck.clone(), live.clone(), ended.clone()])
+        .await
+        .unwrap();
+
+    manager.send_input(ended, "exit 0\n").await.unwrap();
+    collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == ended.as_str()
+        })
+        .await;
+    assert_eq!(
+        manager.broadcast_members(&group).await.unwrap(),
+        vec![stuck.clone(), live.clone()]
+    );
+    assert!(manager.send_input(ended, "echo gone\n").await.is_err());
+
+    let filler = tokio::spawn({
+        let manager = manager.clone();
+        let group = group.clone();
+        async move { manager.send_input(&group, &"x".repeat(1 << 20)).await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!filler.is_finished());
+
+    // 挂起的写入不占用会话表：其他会话照常输入，卡住的会话也能关闭
+    tokio::time::timeout(
+        Duration::from_secs(2),
+        manager.send_input(live, "echo still-responsive\n"),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    collector
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == live.as_str() && payload["data"] == "still-responsive"
+        })
+        .await;
+    tokio::time::timeout(Duration::from_secs(2), manager.close_session(&stuck))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        manager.broadcast_members(&group).await.unwrap(),
+        vec![live.clone()]
+    );
+    filler.abort();
+    manager.close_session(live).await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn transcripts_rotate_clean_up_and_export() {
+    let dir = std::env::temp_dir().join(format!("transcripts-{}", uuid::Uuid::new_v4()));
+    let manager = SessionManager::new();
+    manager
+        .update_transcript_settings(TranscriptSettings {
+            enabled: true,
+            directory: Some(dir.to_string_lossy().into_owned()),
+            max_file_bytes: 600,
+            retention_days: 1,
+        })
+        .unwrap();
+    // 配置保存之后才出现的过期日志，只能由轮转时的清理删除
+    std::fs::create_dir_all(&dir).unwrap();
+    // 同目录下的其他文件即使过期也不能被删除
+    let stale = dir.join(format!("session-{}.2.log", uuid::Uuid::new_v4().simple()));
+    let foreign = dir.join("notes.log");
+    for path in [&stale, &foreign] {
+        std::fs::write(path, "old\n").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - Duration::from_secs(3 * 24 * 3600))
+            .unwrap();
+    }
+
+    let collector = Collector::default();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), None, None)
+        .await
+        .unwrap();
+    // 第二行几乎全是颜色序列：纯文本远不到上限，带颜色的副本会先超过
+    let heavy = r"\033[31mx\033[32my".repeat(75);
+    let command = format!(
+        r"printf '\033[31mred-text\033[0m\n{heavy}\033[0m\n'; for i in 1 2 3; do echo line-$i; done"
+    );
+    manager
+        .send_input(&session_id, &format!("{command}\n"))
+        .await
+        .unwrap();
+    collector
+        .wait_for("session-data", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["data"] == "line-3"
+        })
+        .await;
+    manager.close_session(&session_id).await.unwrap();
+    collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+
+    let text_target = dir.join("export/session.txt");
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    loop {
+        manager
+            .export_transcript(&session_id, TranscriptFormat::Text, &text_target)
+            .unwrap();
+        if std::fs::read_to_string(&text_target)
+            .unwrap()
+            .contains("line-3")
+        {
+            break;
+        }
+        assert!(std::time::Instant::now() < deadline, "日志未写完");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let text = std::fs::read_to_string(&text_target).unwrap();
+    assert!(text.contains("red-text"));
+    assert!(!text.contains('\x1b'));
+    let lines = text.lines().filter(|line| line.contains("] line-")).count();
+    assert_eq!(lines, 3);
+
+    // 轮转由带颜色的副本触发，两份文件一起轮转
+    assert!(text.len() < 600);
+    for ext in ["log", "ansi"] {
+        assert!(dir.join(format!("{session_id}.1.{ext}")).exists());
+    }
+    assert!(!stale.exists());
+    assert!(foreign.exists());
+
+    // 会话 ID 会拼进路径，不能借此读取日志目录之外的文件
+    std::fs::write(dir.join("x.log"), "secret\n").unwrap();
+    let err = manager
+        .export_transcript("export/../x", TranscriptFormat::Text, &text_target)
+        .unwrap_err();
+    assert!(err.to_string().contains("无效的会话 ID"), "{err}");
+
+    let html_target = dir.join("export/session.html");
+    manager
+        .export_transcript(&session_id, TranscriptFormat::Html, &html_target)
+        .unwrap();
+    let html = std::fs::read_to_string(&html_target).unwrap();
+    // 时间戳写在颜色序列之后，落在同一个 span 里
+    assert!(html.contains("<span style=\"color:#cd0000;\">["));
+    assert!(html.contains("] red-text</span>"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn malformed_transcript_settings_are_reported() {
+    let data_dir =
+        std::env::temp_dir().join(format!("transcript-settings-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+    SessionManager::new().init_transcripts(&data_dir).unwrap();
+
+    // 与会话策略一致：报错并保留原文件，不会退回默认值关闭日志
+    let path = data_dir.join("transcript.json");
+    std::fs::write(&path, "{ \"enabled\": tru").unwrap();
+    let err = SessionManager::new()
+        .init_transcripts(&data_dir)
+        .unwrap_err();
+    assert!(err.to_string().contains("格式错误"), "{err}");
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "{ \"enabled\": tru"
+    );
+    let _ = std::fs::remove_dir_all(data_dir);
+}
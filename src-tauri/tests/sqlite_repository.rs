@@ -0,0 +1,93 @@
+use rusqlite::{params, Connection};
+use tauri_app_lib::domain::models::{
+    AutomationScript, ScriptAction, ScriptStep, TriggerAction, TriggerRule, TriggerScope,
+};
+use tauri_app_lib::domain::services::script_service::ScriptRepository;
+use tauri_app_lib::domain::services::trigger_service::TriggerRepository;
+use tauri_app_lib::infra::db::sqlite::{SqliteScriptRepository, SqliteTriggerRepository};
+
+fn temp_db(prefix: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("{prefix}-{}.db", uuid::Uuid::new_v4()))
+}
+
+fn rule(id: &str, scope: TriggerScope) -> TriggerRule {
+    TriggerRule {
+        id: id.to_string(),
+        name: id.to_string(),
+        pattern: "error".to_string(),
+        scope,
+        action: TriggerAction::Highlight,
+        enabled: true,
+        created_at: "2024-01-01T00:00:00Z".to_string(),
+        updated_at: "2024-01-01T00:00:00Z".to_string(),
+    }
+}
+
+#[test]
+fn corrupt_trigger_scope_is_not_loaded_as_global() {
+    let path = temp_db("triggers");
+    let repo = SqliteTriggerRepository::new(&path).unwrap();
+    repo.create(rule(
+        "scoped",
+        TriggerScope::Connection {
+            connection_id: "conn-1".to_string(),
+        },
+    ))
+    .unwrap();
+    repo.create(rule("global", TriggerScope::Global)).unwrap();
+
+    Connection::open(&path)
+        .unwrap()
+        .execute(
+            "UPDATE trigger_rules SET scope = ?1 WHERE id = 'scoped'",
+            params!["{\"type\":\"connection\""],
+        )
+        .unwrap();
+
+    let ids: Vec<String> = repo.list().unwrap().into_iter().map(|r| r.id).collect();
+    assert_eq!(ids, vec!["global".to_string()]);
+    assert!(repo.get("scoped").is_err());
+
+    repo.delete("scoped").unwrap();
+    assert!(repo.get("scoped").unwrap().is_none());
+    let _ = std::fs::remove_file(&path);
+}
+
+fn automation_script(id: &str) -> AutomationScript {
+    AutomationScript {
+        id: id.to_string(),
+        name: id.to_string(),
+        description: None,
+        steps: vec![ScriptStep {
+            label: None,
+            action: ScriptAction::Send {
+                data: "uptime\n".to_string(),
+            },
+        }],
+        created_at: "2024-01-01T00:00:00Z".to_string(),
+        updated_at: "2024-01-01T00:00:00Z".to_string(),
+    }
+}
+
+#[test]
+fn corrupt_script_steps_are_not_loaded_as_empty() {
+    let path = temp_db("scripts");
+    let repo = SqliteScriptRepository::new(&path).unwrap();
+    repo.create(automation_script("broken")).unwrap();
+    repo.create(automation_script("intact")).unwrap();
+
+    Connection::open(&path)
+        .unwrap()
+        .execute(
+            "UPDATE automation_scripts SET steps = ?1 WHERE id = 'broken'",
+            params!["[{\"action\":"],
+        )
+        .unwrap();
+
+    let scripts = repo.list().unwrap();
+    assert_eq!(scripts.len(), 1);
+    assert_eq!(scripts[0].id, "intact");
+    assert_eq!(scripts[0].steps.len(), 1);
+    assert!(repo.get("broken").is_err());
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,1129 @@
+#[path = "support/ssh_server.rs"]
+mod ssh_server;
+mod support;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use russh_keys::key::{KeyPair, PublicKey, Signature};
+#[cfg(unix)]
+use russh_keys::PublicKeyBase64;
+use serde_json::Value;
+use tauri_app_lib::domain::models::{
+    AgentForwarding, AuthType, HostExecStatus, Protocol, StartupActions, StartupStep,
+    TriggerAction, TriggerRule, TriggerScope,
+};
+use tauri_app_lib::infra::session::events::Events;
+use tauri_app_lib::infra::session::exec::ExecRequest;
+use tauri_app_lib::infra::session::multi_exec::MultiExecOptions;
+use tauri_app_lib::infra::session::{SessionManager, SessionSecret};
+
+use ssh_server::{ExecReply, SshServerBuilder};
+use support::{ssh_connection, Collector};
+
+fn password(value: &str) -> Option<SessionSecret> {
+    Some(SessionSecret {
+        password: Some(value.into()),
+    })
+}
+
+fn states_of(collector: &Collector, session_id: &str) -> Vec<Value> {
+    collector
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(name, payload)| name == "session-state" && payload["session_id"] == session_id)
+        .map(|(_, payload)| payload["state"].clone())
+        .collect()
+}
+
+async fn wait_for_output(collector: &Collector, session_id: &str, needle: &str) {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+    while !collector.output_of(session_id).contains(needle) {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "等待输出 {needle:?} 超时，已收到: {:?}",
+            collector.output_of(session_id)
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn password_login_echoes_shell_input() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .start()
+        .await;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(ssh_connection(server.port)),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+
+    wait_for_output(&collector, &session_id, "welcome tester").await;
+    manager
+        .send_input(&session_id, "echo harness-ok\n")
+        .await
+        .unwrap();
+    wait_for_output(&collector, &session_id, "harness-ok\r\n$ ").await;
+
+    assert_eq!(
+        states_of(&collector, &session_id),
+        vec!["connecting", "authenticating", "connected"]
+    );
+    assert_eq!(server.stats.pty_terms.lock().unwrap().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn triggers_match_colored_output_split_across_reads() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .start()
+        .await;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    manager.set_trigger_rules(&[TriggerRule {
+        id: "rule-disk".into(),
+        name: "disk".into(),
+        pattern: "ERROR: disk".into(),
+        scope: TriggerScope::Global,
+        action: TriggerAction::Notify,
+        enabled: true,
+        created_at: String::new(),
+        updated_at: String::new(),
+    }]);
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(ssh_connection(server.port)),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+    wait_for_output(&collector, &session_id, "welcome tester").await;
+
+    // 服务器逐段回显输入：颜色序列和关键字都被拆在两次读取之间
+    for (input, echoed) in [
+        ("\x1b[3", "\x1b[3"),
+        ("1mERR", "1mERR"),
+        ("\x1b[0mOR: disk full\n", "OR: disk full\r\n"),
+    ] {
+        manager.send_input(&session_id, input).await.unwrap();
+        wait_for_output(&collector, &session_id, echoed).await;
+    }
+    let hit = collector
+        .wait_for("session-trigger", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(hit["rule_id"], "rule-disk");
+    assert_eq!(hit["matched"], "ERROR: disk");
+    assert_eq!(hit["line"], "$ ERROR: disk full");
+    assert_eq!(
+        (hit["start"].as_u64(), hit["end"].as_u64()),
+        (Some(2), Some(13))
+    );
+
+    wait_for_output(&collector, &session_id, "command not found").await;
+    let hits = collector
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(name, _)| name == "session-trigger")
+        .count();
+    assert_eq!(hits, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn keyboard_interactive_login_answers_prompts_with_password() {
+    // 服务器只开放 keyboard-interactive，客户端以同一密码回答每一条提示
+    let server = SshServerBuilder::new()
+        .keyboard_interactive_user(
+            "tester",
+            &[
+                ("Password: ", "otp-123"),
+                ("Verification code: ", "otp-123"),
+            ],
+        )
+        .start()
+        .await;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(ssh_connection(server.port)),
+            password("otp-123"),
+        )
+        .await
+        .unwrap();
+
+    collector
+        .wait_for("session-state", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["state"] == "connected"
+        })
+        .await;
+    wait_for_output(&collector, &session_id, "welcome tester").await;
+    assert_eq!(server.stats.auth_attempts.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn keyboard_interactive_wrong_answer_fails_with_auth_error() {
+    let server = SshServerBuilder::new()
+        .keyboard_interactive_user("tester", &[("Password: ", "otp-123")])
+        .start()
+        .await;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(ssh_connection(server.port)),
+            password("wrong"),
+        )
+        .await
+        .unwrap();
+
+    let failed = collector
+        .wait_for("session-state", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["state"] == "failed"
+        })
+        .await;
+    assert_eq!(failed["error_code"], "auth_failed");
+    assert!(server.stats.auth_attempts.load(Ordering::SeqCst) >= 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn wrong_password_fails_with_auth_error() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .start()
+        .await;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(ssh_connection(server.port)),
+            password("wrong"),
+        )
+        .await
+        .unwrap();
+
+    let failed = collector
+        .wait_for("session-state", |payload| {
+            payload["session_id"] == session_id.as_str() && payload["state"] == "failed"
+        })
+        .await;
+    assert_eq!(failed["error_code"], "auth_failed");
+    assert_eq!(
+        states_of(&collector, &session_id),
+        vec!["connecting", "authenticating", "failed"]
+    );
+    assert!(server.stats.auth_attempts.load(Ordering::SeqCst) >= 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn remote_exit_reports_status_and_closes_channel() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .start()
+        .await;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(ssh_connection(server.port)),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+
+    wait_for_output(&collector, &session_id, "welcome tester").await;
+    manager.send_input(&session_id, "exit 7\n").await.unwrap();
+    let closed = collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(closed["exit_code"], 7);
+    assert_eq!(closed["reason"], "remote_closed");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn closing_ssh_session_records_user_close() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .start()
+        .await;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(ssh_connection(server.port)),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+
+    wait_for_output(&collector, &session_id, "welcome tester").await;
+    manager.close_session(&session_id).await.unwrap();
+    let closed = collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(closed["reason"], "user_closed");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn slow_server_output_is_still_delivered() {
+    // 服务器每次回复前都停顿，客户端读取会多次遇到 WouldBlock。
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .reply_delay(Duration::from_millis(150))
+        .start()
+        .await;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(ssh_connection(server.port)),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+
+    wait_for_output(&collector, &session_id, "welcome tester").await;
+    for word in ["one", "two", "three"] {
+        manager
+            .send_input(&session_id, &format!("echo slow-{word}\n"))
+            .await
+            .unwrap();
+    }
+    wait_for_output(&collector, &session_id, "slow-three\r\n$ ").await;
+    let output = collector.output_of(&session_id);
+    let one = output.find("slow-one\r\n").unwrap();
+    let two = output.find("slow-two\r\n").unwrap();
+    assert!(one < two);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn startup_env_and_commands_run_after_login() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .accept_env(&["LANG"])
+        .start()
+        .await;
+    let mut connection = ssh_connection(server.port);
+    connection.startup = StartupActions {
+        env: HashMap::from([
+            ("LANG".to_string(), "C.UTF-8".to_string()),
+            ("APP_MODE".to_string(), "test".to_string()),
+        ]),
+        working_dir: Some("/srv/app".into()),
+        steps: vec![StartupStep::Command {
+            command: "printenv APP_MODE".into(),
+        }],
+    };
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(connection),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+
+    // APP_MODE 被 setenv 拒绝，退化为 export 后仍可见
+    wait_for_output(&collector, &session_id, "test\r\n$ ").await;
+    let env = server.stats.env.lock().unwrap().clone();
+    assert_eq!(env.get("LANG").map(String::as_str), Some("C.UTF-8"));
+    assert!(!env.contains_key("APP_MODE"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn startup_rejects_env_names_with_shell_syntax() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .start()
+        .await;
+    let mut connection = ssh_connection(server.port);
+    // 绕过保存时的校验（如旧版本写入的配置），发送前同样拒绝
+    connection.startup = StartupActions {
+        env: HashMap::from([("X=1; echo pwned;".to_string(), "v".to_string())]),
+        working_dir: None,
+        steps: vec![StartupStep::Command {
+            command: "echo startup-ran".into(),
+        }],
+    };
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(connection),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+
+    wait_for_output(&collector, &session_id, "启动动作未完成: 无效的环境变量名").await;
+    manager
+        .send_input(&session_id, "echo still-usable\n")
+        .await
+        .unwrap();
+    wait_for_output(&collector, &session_id, "still-usable\r\n$ ").await;
+    let output = collector.output_of(&session_id);
+    // 错误信息里带着原变量名，只检查没有真正执行
+    assert!(!output.contains("pwned\r\n"), "{output}");
+    assert!(!output.contains("startup-ran"), "{output}");
+    assert!(server.stats.env.lock().unwrap().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn closing_during_startup_expect_does_not_wait_for_timeout() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .start()
+        .await;
+    let mut connection = ssh_connection(server.port);
+    connection.startup = StartupActions {
+        env: HashMap::new(),
+        working_dir: None,
+        steps: vec![StartupStep::Expect {
+            pattern: "never-printed".into(),
+            send: "".into(),
+            timeout_ms: Some(8_000),
+        }],
+    };
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(connection),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+
+    wait_for_output(&collector, &session_id, "PTY 与 shell 已建立").await;
+    let started = Instant::now();
+    manager.close_session(&session_id).await.unwrap();
+    let closed = collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(closed["reason"], "user_closed");
+    assert!(
+        started.elapsed() < Duration::from_secs(3),
+        "{:?}",
+        started.elapsed()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn input_during_startup_expect_is_sent_afterwards() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .start()
+        .await;
+    let mut connection = ssh_connection(server.port);
+    connection.startup = StartupActions {
+        env: HashMap::new(),
+        working_dir: None,
+        steps: vec![StartupStep::Expect {
+            pattern: "never-printed".into(),
+            send: "".into(),
+            timeout_ms: Some(800),
+        }],
+    };
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(connection),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+
+    wait_for_output(&collector, &session_id, "PTY 与 shell 已建立").await;
+    manager
+        .send_input(&session_id, "echo typed-early\n")
+        .await
+        .unwrap();
+    wait_for_output(&collector, &session_id, "启动动作未完成").await;
+    wait_for_output(&collector, &session_id, "typed-early\r\n$ ").await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn refused_agent_forwarding_keeps_shell_usable() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .start()
+        .await;
+    let mut connection = ssh_connection(server.port);
+    connection.agent_forwarding = AgentForwarding::Confirm;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(connection),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+
+    // 测试服务器不支持 auth-agent-req，会话照常建立并提示转发未生效
+    wait_for_output(&collector, &session_id, "远端拒绝了 agent 转发请求").await;
+    manager
+        .send_input(&session_id, "echo agent-ok\n")
+        .await
+        .unwrap();
+    wait_for_output(&collector, &session_id, "agent-ok\r\n$ ").await;
+    assert!(manager
+        .respond_agent_request(&session_id, "agent-unknown", true)
+        .await
+        .is_ok());
+}
+
+/// 进程内的本地 ssh-agent：持有一把真实的 ed25519 密钥并如实签名，公钥登录与 agent 转发都经它完成。
+/// `SSH_AUTH_SOCK` 是进程级的环境变量，同一测试进程中的用例共用一个 agent。
+#[cfg(unix)]
+struct FakeAgent {
+    public_key: PublicKey,
+    /// 经转发通道（shell 中的 `ssh-sign`）完成的签名次数，不含登录时的签名。
+    forwarded_signs: AtomicUsize,
+}
+
+#[cfg(unix)]
+fn fake_agent() -> &'static FakeAgent {
+    use std::io::{Read, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::sync::OnceLock;
+
+    fn put_string(buffer: &mut Vec<u8>, value: &[u8]) {
+        buffer.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(value);
+    }
+
+    fn take_string<'d>(data: &'d [u8], pos: &mut usize) -> &'d [u8] {
+        let len = u32::from_be_bytes(data[*pos..*pos + 4].try_into().unwrap()) as usize;
+        *pos += 4 + len;
+        &data[*pos - len..*pos]
+    }
+
+    fn serve(mut stream: UnixStream, key: &KeyPair, agent: &FakeAgent) {
+        let mut header = [0u8; 4];
+        while stream.read_exact(&mut header).is_ok() {
+            let mut message = vec![0u8; u32::from_be_bytes(header) as usize];
+            stream.read_exact(&mut message).unwrap();
+            let mut reply = Vec::new();
+            if message[0] == 11 {
+                reply.push(12);
+                reply.extend_from_slice(&1u32.to_be_bytes());
+                put_string(&mut reply, &agent.public_key.public_key_bytes());
+                put_string(&mut reply, b"tester@laptop");
+            } else {
+                let mut pos = 1;
+                take_string(&message, &mut pos);
+                let data = take_string(&message, &mut pos);
+                if data == b"data to sign" {
+                    agent.forwarded_signs.fetch_add(1, Ordering::SeqCst);
+                }
+                let Ok(Signature::Ed25519(bytes)) = key.sign_detached(data) else {
+                    unreachable!("测试密钥是 ed25519");
+                };
+                let mut signature = Vec::new();
+                put_string(&mut signature, b"ssh-ed25519");
+                put_string(&mut signature, &bytes.0);
+                reply.push(14);
+                put_string(&mut reply, &signature);
+            }
+            let mut framed = Vec::new();
+            put_string(&mut framed, &reply);
+            stream.write_all(&framed).unwrap();
+        }
+    }
+
+    static AGENT: OnceLock<FakeAgent> = OnceLock::new();
+    AGENT.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("agent-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("agent.sock");
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        std::env::set_var("SSH_AUTH_SOCK", &socket);
+        let key = Arc::new(KeyPair::generate_ed25519().unwrap());
+        let agent = FakeAgent {
+            public_key: key.clone_public_key().unwrap(),
+            forwarded_signs: AtomicUsize::new(0),
+        };
+        std::thread::spawn(move || {
+            // 转发通道各自持有一条 agent 连接，每条连接一个线程
+            for stream in listener.incoming().map_while(Result::ok) {
+                let key = key.clone();
+                std::thread::spawn(move || serve(stream, &key, fake_agent()));
+            }
+        });
+        agent
+    })
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn public_key_login_signs_with_agent_identity() {
+    let agent = fake_agent();
+    let server = SshServerBuilder::new()
+        .key_user("tester", &agent.public_key)
+        .start()
+        .await;
+    let mut connection = ssh_connection(server.port);
+    connection.auth_type = AuthType::Agent;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(Events::new(collector.clone()), Some(connection), None)
+        .await
+        .unwrap();
+
+    wait_for_output(&collector, &session_id, "welcome tester").await;
+    assert_eq!(
+        states_of(&collector, &session_id),
+        vec!["connecting", "authenticating", "connected"]
+    );
+    assert!(server.stats.auth_attempts.load(Ordering::SeqCst) >= 1);
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn agent_requests_are_confirmed_by_the_forwarding_tab() {
+    let signed = &fake_agent().forwarded_signs;
+
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .agent_forwarding()
+        .start()
+        .await;
+    let mut connection = ssh_connection(server.port);
+    connection.agent_forwarding = AgentForwarding::Confirm;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let mut tabs = Vec::new();
+    for _ in 0..2 {
+        let session_id = manager
+            .create_shell_session(
+                Events::new(collector.clone()),
+                Some(connection.clone()),
+                password("secret"),
+            )
+            .await
+            .unwrap();
+        wait_for_output(&collector, &session_id, "welcome tester").await;
+        tabs.push(session_id);
+    }
+    let (first, second) = (&tabs[0], &tabs[1]);
+    assert_eq!(manager.pooled_connections(), 1);
+
+    // 列出密钥无需确认；签名请求只推给最近请求转发的标签
+    manager.send_input(second, "ssh-add -l\n").await.unwrap();
+    wait_for_output(&collector, second, "agent key: tester@laptop\r\n$ ").await;
+    manager.send_input(second, "ssh-sign\n").await.unwrap();
+    let request = collector.wait_for("session-agent-request", |_| true).await;
+    assert_eq!(request["session_id"], second.as_str());
+    assert_eq!(request["key_type"], "ssh-ed25519");
+    assert_eq!(request["comment"], "tester@laptop");
+    let request_id = request["request_id"].as_str().unwrap().to_string();
+    // 其他标签无法代为答复
+    manager
+        .respond_agent_request(first, &request_id, true)
+        .await
+        .unwrap();
+    manager
+        .respond_agent_request(second, &request_id, true)
+        .await
+        .unwrap();
+    wait_for_output(&collector, second, "agent: signed\r\n$ ").await;
+    assert_eq!(signed.load(Ordering::SeqCst), 1);
+
+    // 关闭第二个标签后，连接上的转发仍由第一个标签确认
+    manager.close_session(second).await.unwrap();
+    collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == second.as_str()
+        })
+        .await;
+    manager.send_input(first, "ssh-sign\n").await.unwrap();
+    let request = collector
+        .wait_for("session-agent-request", |payload| {
+            payload["session_id"] == first.as_str()
+        })
+        .await;
+    manager
+        .respond_agent_request(first, request["request_id"].as_str().unwrap(), false)
+        .await
+        .unwrap();
+    wait_for_output(&collector, first, "agent: refused\r\n$ ").await;
+    assert_eq!(signed.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exec_remote_collects_streams_and_exit_status() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .exec("uname -s", ExecReply::stdout("Linux\n"))
+        .exec("false", ExecReply::failure("boom\n", 1))
+        .start()
+        .await;
+    let manager = SessionManager::new();
+    let run = |command: &str| ExecRequest {
+        command: command.into(),
+        ..ExecRequest::default()
+    };
+
+    let output = manager
+        .exec_remote(
+            ssh_connection(server.port),
+            password("secret"),
+            run("uname -s"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(output.stdout, "Linux\n");
+    assert_eq!(output.exit_code, Some(0));
+
+    let output = manager
+        .exec_remote(
+            ssh_connection(server.port),
+            password("secret"),
+            run("false"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(output.stderr, "boom\n");
+    assert_eq!(output.exit_code, Some(1));
+
+    let output = manager
+        .exec_remote(
+            ssh_connection(server.port),
+            password("secret"),
+            run("missing"),
+        )
+        .await
+        .unwrap();
+    assert!(output.stderr.contains("command not found"));
+    assert_eq!(output.exit_code, Some(127));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exec_remote_passes_env_cwd_and_stdin() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .accept_env(&["LANG"])
+        .start()
+        .await;
+    let manager = SessionManager::new();
+
+    let output = manager
+        .exec_remote(
+            ssh_connection(server.port),
+            password("secret"),
+            ExecRequest {
+                command: "printenv LANG; printenv TOKEN".into(),
+                env: HashMap::from([
+                    ("LANG".to_string(), "C.UTF-8".to_string()),
+                    ("TOKEN".to_string(), "it's-ok".to_string()),
+                ]),
+                cwd: Some("/tmp".into()),
+                ..ExecRequest::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(output.stdout, "C.UTF-8\nit's-ok\n");
+
+    let output = manager
+        .exec_remote(
+            ssh_connection(server.port),
+            password("secret"),
+            ExecRequest {
+                command: "cat".into(),
+                stdin: Some("piped input\n".into()),
+                ..ExecRequest::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(output.stdout, "piped input\n");
+    assert_eq!(output.exit_code, Some(0));
+
+    // 变量名会拼进 export 命令，带 shell 语法的名字在发送前就被拒绝
+    let opened = server.stats.channels_opened.load(Ordering::SeqCst);
+    let err = manager
+        .exec_remote(
+            ssh_connection(server.port),
+            password("secret"),
+            ExecRequest {
+                command: "true".into(),
+                env: HashMap::from([("X=1; touch pwned;".to_string(), "v".to_string())]),
+                ..ExecRequest::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("无效的环境变量名"), "{err}");
+    assert_eq!(server.stats.channels_opened.load(Ordering::SeqCst), opened);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exec_remote_timeout_covers_unread_stdin() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .reply_delay(Duration::from_millis(20))
+        .start()
+        .await;
+    let manager = SessionManager::new();
+    let started = std::time::Instant::now();
+    let output = manager
+        .exec_remote(
+            ssh_connection(server.port),
+            password("secret"),
+            ExecRequest {
+                command: "cat".into(),
+                stdin: Some("x".repeat(16 * 1024 * 1024)),
+                timeout: Some(Duration::from_millis(300)),
+                ..ExecRequest::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert!(output.timed_out);
+    assert_eq!(output.exit_code, None);
+    assert!(
+        started.elapsed() < Duration::from_secs(3),
+        "超时未生效: {:?}",
+        started.elapsed()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exec_remote_rejects_non_ssh_connections() {
+    let mut connection = ssh_connection(1);
+    connection.protocol = Protocol::Ftp;
+    let err = SessionManager::new()
+        .exec_remote(
+            connection,
+            None,
+            ExecRequest {
+                command: "uname -s".into(),
+                ..ExecRequest::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("不是 SSH 连接"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exec_many_reports_each_host_and_stops_on_failure() {
+    let healthy = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .exec("deploy", ExecReply::stdout("deployed\n"))
+        .start()
+        .await;
+    let broken = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .exec("deploy", ExecReply::failure("disk full\n", 2))
+        .start()
+        .await;
+    let host = |id: &str, port: u16| {
+        let mut connection = ssh_connection(port);
+        connection.id = id.into();
+        connection.name = id.into();
+        connection
+    };
+    let mut ftp = host("conn-ftp", healthy.port);
+    ftp.protocol = Protocol::Ftp;
+    let secrets = |ids: &[&str]| -> HashMap<String, SessionSecret> {
+        ids.iter()
+            .map(|id| (id.to_string(), password("secret").unwrap()))
+            .collect()
+    };
+    let request = ExecRequest {
+        command: "deploy".into(),
+        ..ExecRequest::default()
+    };
+
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let summary = manager
+        .exec_many(
+            Events::new(collector.clone()),
+            "job-all".into(),
+            vec![
+                host("conn-ok", healthy.port),
+                host("conn-bad", broken.port),
+                ftp,
+            ],
+            secrets(&["conn-ok", "conn-bad", "conn-ftp"]),
+            request.clone(),
+            MultiExecOptions::default(),
+        )
+        .await;
+    assert_eq!(
+        (summary.succeeded, summary.failed, summary.skipped),
+        (1, 2, 0)
+    );
+    let result = |id: &str| {
+        summary
+            .results
+            .iter()
+            .find(|result| result.connection_id == id)
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(result("conn-ok").status, HostExecStatus::Succeeded);
+    assert_eq!(result("conn-bad").status, HostExecStatus::Failed);
+    assert_eq!(result("conn-bad").exit_code, Some(2));
+    assert!(result("conn-ftp")
+        .error
+        .is_some_and(|error| error.contains("不是 SSH 连接")));
+    collector
+        .wait_for("exec-output", |payload| {
+            payload["connection_id"] == "conn-ok" && payload["data"] == "deployed\n"
+        })
+        .await;
+    collector
+        .wait_for("exec-output", |payload| {
+            payload["connection_id"] == "conn-bad"
+                && payload["stream"] == "stderr"
+                && payload["data"] == "disk full\n"
+        })
+        .await;
+    let host_results = collector
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(name, _)| name == "exec-host-result")
+        .count();
+    assert_eq!(host_results, 3);
+    collector
+        .wait_for("exec-summary", |payload| payload["jobId"] == "job-all")
+        .await;
+
+    // 逐台执行时第一台失败，其余主机不再启动
+    let summary = manager
+        .exec_many(
+            Events::new(Collector::default()),
+            "job-stop".into(),
+            vec![host("conn-bad", broken.port), host("conn-ok", healthy.port)],
+            secrets(&["conn-ok", "conn-bad"]),
+            request,
+            MultiExecOptions {
+                concurrency: 1,
+                stop_on_failure: true,
+            },
+        )
+        .await;
+    assert_eq!(
+        (summary.succeeded, summary.failed, summary.skipped),
+        (0, 1, 1)
+    );
+    assert_eq!(summary.results[1].connection_id, "conn-ok");
+    assert_eq!(summary.results[1].status, HostExecStatus::Skipped);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shells_and_exec_share_one_pooled_connection() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .exec("hostname", ExecReply::stdout("box\n"))
+        .start()
+        .await;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    manager.set_pool_idle_timeout(Duration::from_millis(200));
+    let mut sessions = Vec::new();
+    for _ in 0..2 {
+        let session_id = manager
+            .create_shell_session(
+                Events::new(collector.clone()),
+                Some(ssh_connection(server.port)),
+                password("secret"),
+            )
+            .await
+            .unwrap();
+        wait_for_output(&collector, &session_id, "welcome tester").await;
+        sessions.push(session_id);
+    }
+    let output = manager
+        .exec_remote(
+            ssh_connection(server.port),
+            password("secret"),
+            ExecRequest {
+                command: "hostname".into(),
+                ..ExecRequest::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(output.stdout, "box\n");
+
+    assert!(collector
+        .output_of(&sessions[1])
+        .contains("复用已建立的 SSH 连接"));
+    assert_eq!(server.stats.auth_attempts.load(Ordering::SeqCst), 1);
+    assert_eq!(manager.pooled_connections(), 1);
+
+    // 两个 shell 互不影响：关闭其中一个后另一个仍可交互
+    manager.close_session(&sessions[0]).await.unwrap();
+    manager
+        .send_input(&sessions[1], "echo still-here\n")
+        .await
+        .unwrap();
+    wait_for_output(&collector, &sessions[1], "still-here\r\n$ ").await;
+
+    // 最后一个租约归还后，空闲超时到期才断开
+    manager.close_session(&sessions[1]).await.unwrap();
+    collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == sessions[1].as_str()
+        })
+        .await;
+    assert_eq!(manager.pooled_connections(), 1);
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(manager.pooled_connections(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_channel_opens_on_one_pooled_connection_stay_separate() {
+    const JOBS: usize = 8;
+    let mut builder = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .reply_delay(Duration::from_millis(30));
+    for job in 0..JOBS {
+        builder = builder.exec(
+            &format!("job-{job}"),
+            ExecReply::stdout(format!("out-{job}\n")),
+        );
+    }
+    let server = builder.start().await;
+    let port = server.port;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let shell = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(ssh_connection(server.port)),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+    wait_for_output(&collector, &shell, "welcome tester").await;
+
+    // 各租约在同一连接上同时开通道，每个命令都应拿到自己的通道与输出
+    let mut jobs = tokio::task::JoinSet::new();
+    for job in 0..JOBS {
+        let manager = manager.clone();
+        jobs.spawn(async move {
+            let output = manager
+                .exec_remote(
+                    ssh_connection(port),
+                    password("secret"),
+                    ExecRequest {
+                        command: format!("job-{job}"),
+                        ..ExecRequest::default()
+                    },
+                )
+                .await
+                .unwrap();
+            (job, output.stdout)
+        });
+    }
+    while let Some(result) = jobs.join_next().await {
+        let (job, stdout) = result.unwrap();
+        assert_eq!(stdout, format!("out-{job}\n"));
+    }
+
+    assert_eq!(manager.pooled_connections(), 1);
+    assert_eq!(server.stats.auth_attempts.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        server.stats.channels_opened.load(Ordering::SeqCst),
+        JOBS + 1
+    );
+    manager.send_input(&shell, "echo shell-ok\n").await.unwrap();
+    wait_for_output(&collector, &shell, "shell-ok\r\n$ ").await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn live_session_reports_latency_metrics() {
+    let server = SshServerBuilder::new()
+        .password_user("tester", "secret")
+        .start()
+        .await;
+    let collector = Collector::default();
+    let manager = SessionManager::new();
+    let session_id = manager
+        .create_shell_session(
+            Events::new(collector.clone()),
+            Some(ssh_connection(server.port)),
+            password("secret"),
+        )
+        .await
+        .unwrap();
+    wait_for_output(&collector, &session_id, "welcome tester").await;
+
+    // 刚有输出时通道还不安静，读到的下一段未必是回显，不计时
+    manager.send_input(&session_id, "echo ").await.unwrap();
+    wait_for_output(&collector, &session_id, "\necho ").await;
+    assert!(manager.latency_stats(&session_id).is_none());
+
+    // 通道安静之后以输入的回显计时，测量不额外开通道
+    tokio::time::sleep(Duration::from_millis(700)).await;
+    manager.send_input(&session_id, "still-").await.unwrap();
+    let metrics = collector
+        .wait_for("session-metrics", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert_eq!(metrics["samples"], 1);
+    let stats = manager.latency_stats(&session_id).unwrap();
+    assert!(stats.min_ms <= stats.p95_ms);
+
+    // 采样间隔内的输入不再计时
+    tokio::time::sleep(Duration::from_millis(700)).await;
+    manager.send_input(&session_id, "here\n").await.unwrap();
+    wait_for_output(&collector, &session_id, "still-here\r\n$ ").await;
+    assert_eq!(manager.latency_stats(&session_id).unwrap().samples, 1);
+    assert_eq!(server.stats.channels_opened.load(Ordering::SeqCst), 1);
+
+    manager.close_session(&session_id).await.unwrap();
+    collector
+        .wait_for("session-closed", |payload| {
+            payload["session_id"] == session_id.as_str()
+        })
+        .await;
+    assert!(manager.latency_stats(&session_id).is_none());
+}
@@ -0,0 +1,84 @@
+//! 集成测试共用的工具：内存事件收集器与测试连接。
+//!
+//! SSH 测试服务器（`ssh_server.rs`）只有 `ssh_session.rs` 使用，由它单独引入。
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::Value;
+use tauri_app_lib::domain::models::{
+    AgentForwarding, AuthType, Connection, ConnectionHealth, Protocol, StartupActions,
+};
+use tauri_app_lib::infra::session::events::EventSink;
+
+const WAIT: Duration = Duration::from_secs(10);
+
+/// 记录全部事件的内存收集器，替代测试中不存在的 `AppHandle`。
+#[derive(Clone, Default)]
+pub struct Collector {
+    pub events: Arc<Mutex<Vec<(String, Value)>>>,
+}
+
+impl EventSink for Collector {
+    fn emit_value(&self, event: &str, payload: Value) {
+        self.events
+            .lock()
+            .unwrap()
+            .push((event.to_string(), payload));
+    }
+}
+
+impl Collector {
+    pub async fn wait_for(&self, event: &str, matches: impl Fn(&Value) -> bool) -> Value {
+        let deadline = tokio::time::Instant::now() + WAIT;
+        loop {
+            let found = self
+                .events
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(name, payload)| name == event && matches(payload))
+                .map(|(_, payload)| payload.clone());
+            if let Some(payload) = found {
+                return payload;
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "等待 {event} 超时，已收到: {:?}",
+                self.events.lock().unwrap()
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    pub fn output_of(&self, session_id: &str) -> String {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, payload)| name == "session-data" && payload["session_id"] == session_id)
+            .filter_map(|(_, payload)| payload["data"].as_str().map(str::to_string))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+pub fn ssh_connection(port: u16) -> Connection {
+    Connection {
+        id: "conn-test".into(),
+        name: "test".into(),
+        protocol: Protocol::Ssh,
+        host: "127.0.0.1".into(),
+        port,
+        username: "tester".into(),
+        auth_type: AuthType::Password,
+        group_id: None,
+        group_name: None,
+        tags: vec![],
+        favorite: false,
+        status: ConnectionHealth::Idle,
+        last_connected_at: None,
+        startup: StartupActions::default(),
+        agent_forwarding: AgentForwarding::default(),
+    }
+}
@@ -0,0 +1,748 @@
+//! 进程内 SSH 测试服务器（基于 russh），只监听 127.0.0.1 的随机端口，不访问外部网络。
+//!
+//! 支持按用户配置密码、公钥与 keyboard-interactive 提示，按命令注册 exec 应答，
+//! 并提供一个会回显输入、理解少量内建命令（`echo`、`printenv`、`export`、`cd`、`exit`）的 shell。
+//! 开启 agent 转发后，shell 中的 `ssh-add -l` 与 `ssh-sign` 经客户端转发的 agent 完成。
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use russh::server::{self, Auth, Msg, Response, Session};
+use russh::{Channel, ChannelId, CryptoVec, MethodSet, Pty};
+use russh_keys::key::{KeyPair, PublicKey};
+use tokio::net::TcpListener;
+
+const PROMPT: &str = "$ ";
+
+/// exec 处理器的返回值。
+#[derive(Clone, Debug, Default)]
+pub struct ExecReply {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_status: u32,
+}
+
+impl ExecReply {
+    pub fn stdout(stdout: impl Into<String>) -> Self {
+        Self {
+            stdout: stdout.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn failure(stderr: impl Into<String>, exit_status: u32) -> Self {
+        Self {
+            stderr: stderr.into(),
+            exit_status,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct TestUser {
+    password: Option<String>,
+    key_fingerprints: Vec<String>,
+    /// (提示语, 期望的回答)
+    keyboard_interactive: Vec<(String, String)>,
+}
+
+#[derive(Clone, Default)]
+struct ServerSettings {
+    users: HashMap<String, TestUser>,
+    exec: HashMap<String, ExecReply>,
+    /// 为 None 时接受全部环境变量。
+    accepted_env: Option<Vec<String>>,
+    /// 每次回复前的延迟，用于让客户端在非阻塞读取时反复遇到 WouldBlock。
+    reply_delay: Duration,
+    /// 是否接受 `auth-agent-req@openssh.com`。
+    agent_forwarding: bool,
+}
+
+/// 服务器运行期间记录的统计，供测试断言。
+#[derive(Default)]
+pub struct ServerStats {
+    pub auth_attempts: AtomicUsize,
+    pub channels_opened: AtomicUsize,
+    pub channels_closed: AtomicUsize,
+    pub env: Mutex<HashMap<String, String>>,
+    pub pty_terms: Mutex<Vec<String>>,
+}
+
+#[derive(Default)]
+pub struct SshServerBuilder {
+    settings: ServerSettings,
+}
+
+impl SshServerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn password_user(mut self, user: &str, password: &str) -> Self {
+        self.user(user).password = Some(password.into());
+        self
+    }
+
+    pub fn key_user(mut self, user: &str, key: &PublicKey) -> Self {
+        self.user(user).key_fingerprints.push(key.fingerprint());
+        self
+    }
+
+    /// 只开放 keyboard-interactive：依次发出 `prompts` 中的提示并校验回答。
+    pub fn keyboard_interactive_user(mut self, user: &str, prompts: &[(&str, &str)]) -> Self {
+        self.user(user).keyboard_interactive = prompts
+            .iter()
+            .map(|(prompt, answer)| (prompt.to_string(), answer.to_string()))
+            .collect();
+        self
+    }
+
+    pub fn exec(mut self, command: &str, reply: ExecReply) -> Self {
+        self.settings.exec.insert(command.into(), reply);
+        self
+    }
+
+    pub fn accept_env(mut self, names: &[&str]) -> Self {
+        self.settings.accepted_env = Some(names.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    pub fn reply_delay(mut self, delay: Duration) -> Self {
+        self.settings.reply_delay = delay;
+        self
+    }
+
+    pub fn agent_forwarding(mut self) -> Self {
+        self.settings.agent_forwarding = true;
+        self
+    }
+
+    pub async fn start(self) -> TestSshServer {
+        let mut methods = MethodSet::empty();
+        for user in self.settings.users.values() {
+            if user.password.is_some() {
+                methods |= MethodSet::PASSWORD;
+            }
+            if !user.key_fingerprints.is_empty() {
+                methods |= MethodSet::PUBLICKEY;
+            }
+            if !user.keyboard_interactive.is_empty() {
+                methods |= MethodSet::KEYBOARD_INTERACTIVE;
+            }
+        }
+        let config = Arc::new(server::Config {
+            methods,
+            keys: vec![KeyPair::generate_ed25519().expect("生成主机密钥失败")],
+            auth_rejection_time: Duration::ZERO,
+            auth_rejection_time_initial: Some(Duration::ZERO),
+            inactivity_timeout: Some(Duration::from_secs(30)),
+            ..Default::default()
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("绑定测试端口失败");
+        let port = listener.local_addr().unwrap().port();
+        let settings = Arc::new(self.settings);
+        let stats = Arc::new(ServerStats::default());
+
+        let accept_stats = stats.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let handler = ConnectionHandler {
+                    settings: settings.clone(),
+                    stats: accept_stats.clone(),
+                    user: None,
+                    channels: HashMap::new(),
+                    agent_queries: HashMap::new(),
+                    agent_keys: Vec::new(),
+                };
+                let config = config.clone();
+                tokio::spawn(async move {
+                    if let Ok(session) = server::run_stream(config, socket, handler).await {
+                        let _ = session.await;
+                    }
+                });
+            }
+        });
+
+        TestSshServer { port, stats, task }
+    }
+
+    fn user(&mut self, name: &str) -> &mut TestUser {
+        self.settings.users.entry(name.to_string()).or_default()
+    }
+}
+
+pub struct TestSshServer {
+    pub port: u16,
+    pub stats: Arc<ServerStats>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for TestSshServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Default)]
+struct ChannelState {
+    env: HashMap<String, String>,
+    line: String,
+    /// exec 模式下等待 EOF 后才执行的命令（需要读取 stdin 的 `cat`）。
+    pending_exec: Option<String>,
+    stdin: Vec<u8>,
+    shell: bool,
+    /// 客户端已在该通道上请求 agent 转发。
+    agent: bool,
+}
+
+/// shell 命令经转发的 agent 发出的一次请求，应答到达后输出到 `shell`。
+struct AgentQuery {
+    shell: ChannelId,
+    request: Vec<u8>,
+    response: Vec<u8>,
+}
+
+struct ConnectionHandler {
+    settings: Arc<ServerSettings>,
+    stats: Arc<ServerStats>,
+    user: Option<String>,
+    channels: HashMap<ChannelId, ChannelState>,
+    /// 以服务器打开的 agent 通道为键。
+    agent_queries: HashMap<ChannelId, AgentQuery>,
+    /// 最近一次 `ssh-add -l` 列出的密钥，`ssh-sign` 用第一个签名。
+    agent_keys: Vec<Vec<u8>>,
+}
+
+/// 一行命令的执行结果；`exit` 为 Some 时 shell 应结束。
+#[derive(Default)]
+struct LineOutcome {
+    stdout: String,
+    stderr: String,
+    exit: Option<u32>,
+}
+
+impl ConnectionHandler {
+    fn reject() -> Auth {
+        Auth::Reject {
+            proceed_with_methods: None,
+        }
+    }
+
+    /// 依次执行以 `;` 或 `&&` 分隔的命令，支持少量内建命令与注册的 exec 处理器。
+    fn run_line(&self, channel: ChannelId, line: &str) -> LineOutcome {
+        let mut outcome = LineOutcome::default();
+        let segments: Vec<String> = line
+            .split("&&")
+            .flat_map(|part| part.split(';'))
+            .map(|part| part.trim().to_string())
+            .filter(|part| !part.is_empty())
+            .collect();
+        for segment in segments {
+            let env = &self.channels[&channel].env;
+            let mut words = segment.splitn(2, ' ');
+            let program = words.next().unwrap_or_default();
+            let args = words.next().unwrap_or_default().trim();
+            if let Some(reply) = self.settings.exec.get(&segment) {
+                outcome.stdout.push_str(&reply.stdout);
+                outcome.stderr.push_str(&reply.stderr);
+                if reply.exit_status != 0 {
+                    outcome.exit = Some(reply.exit_status);
+                    return outcome;
+                }
+                continue;
+            }
+            match program {
+                "echo" => outcome.stdout.push_str(&format!("{}\n", unquote(args))),
+                "printenv" => {
+                    if let Some(value) = env.get(args) {
+                        outcome.stdout.push_str(&format!("{value}\n"));
+                    }
+                }
+                // `export` 已由 apply_exports 写入通道环境
+                "cd" | "export" => {}
+                "exit" => {
+                    outcome.exit = Some(args.parse().unwrap_or(0));
+                    return outcome;
+                }
+                _ => {
+                    outcome
+                        .stderr
+                        .push_str(&format!("{program}: command not found\n"));
+                    outcome.exit = Some(127);
+                    return outcome;
+                }
+            }
+        }
+        outcome
+    }
+
+    /// `export` 写入的变量同时对后续命令生效。
+    fn apply_exports(&mut self, channel: ChannelId, line: &str) {
+        let Some(state) = self.channels.get_mut(&channel) else {
+            return;
+        };
+        for segment in line.split("&&").flat_map(|part| part.split(';')) {
+            if let Some(assignment) = segment.trim().strip_prefix("export ") {
+                if let Some((key, value)) = assignment.split_once('=') {
+                    state.env.insert(key.trim().to_string(), unquote(value));
+                }
+            }
+        }
+    }
+
+    async fn reply(&self, channel: ChannelId, data: &str, session: &mut Session) {
+        if !self.settings.reply_delay.is_zero() {
+            tokio::time::sleep(self.settings.reply_delay).await;
+        }
+        if !data.is_empty() {
+            session.data(channel, CryptoVec::from_slice(data.as_bytes()));
+        }
+    }
+
+    /// 打开 agent 通道发出请求；客户端未转发 agent 时像 OpenSSH 一样报错。
+    fn query_agent(
+        &mut self,
+        shell: ChannelId,
+        line: &str,
+        session: &mut Session,
+    ) -> Option<String> {
+        let request = match line {
+            "ssh-add -l" => vec![SSH_AGENTC_REQUEST_IDENTITIES],
+            "ssh-sign" => {
+                let Some(key) = self.agent_keys.first() else {
+                    return Some("ssh-sign: no keys listed\n".into());
+                };
+                let mut request = vec![SSH_AGENTC_SIGN_REQUEST];
+                put_string(&mut request, key);
+                put_string(&mut request, b"data to sign");
+                request.extend_from_slice(&0u32.to_be_bytes());
+                request
+            }
+            _ => return None,
+        };
+        if !self.channels[&shell].agent {
+            return Some("Could not open a connection to your authentication agent.\n".into());
+        }
+        let agent = session.channel_open_agent().expect("打开 agent 通道失败");
+        self.agent_queries.insert(
+            agent,
+            AgentQuery {
+                shell,
+                request,
+                response: Vec::new(),
+            },
+        );
+        Some(String::new())
+    }
+
+    /// 收到完整应答后按 `ssh-add -l` / 签名结果输出到 shell，并关闭 agent 通道。
+    async fn answer_agent(&mut self, agent: ChannelId, data: &[u8], session: &mut Session) {
+        let query = self
+            .agent_queries
+            .get_mut(&agent)
+            .expect("agent 通道已登记");
+        query.response.extend_from_slice(data);
+        let Some(length) = query
+            .response
+            .get(..4)
+            .map(|header| u32::from_be_bytes(header.try_into().unwrap()) as usize)
+        else {
+            return;
+        };
+        if query.response.len() < 4 + length {
+            return;
+        }
+        let query = self.agent_queries.remove(&agent).unwrap();
+        let message = &query.response[4..4 + length];
+        let output = match message.first().copied() {
+            Some(SSH_AGENT_IDENTITIES_ANSWER) => {
+                let mut pos = 5;
+                let mut output = String::new();
+                self.agent_keys.clear();
+                while let (Some(blob), Some(comment)) = (
+                    take_string(message, &mut pos),
+                    take_string(message, &mut pos),
+                ) {
+                    self.agent_keys.push(blob.to_vec());
+                    output.push_str(&format!(
+                        "agent key: {}\n",
+                        String::from_utf8_lossy(comment)
+                    ));
+                }
+                output
+            }
+            Some(SSH_AGENT_SIGN_RESPONSE) => "agent: signed\n".to_string(),
+            _ => "agent: refused\n".to_string(),
+        };
+        session.eof(agent);
+        session.close(agent);
+        self.reply(
+            query.shell,
+            &format!("{}{PROMPT}", output.replace('\n', "\r\n")),
+            session,
+        )
+        .await;
+    }
+
+    fn finish(&self, channel: ChannelId, exit_status: u32, session: &mut Session) {
+        session.exit_status_request(channel, exit_status);
+        session.eof(channel);
+        session.close(channel);
+    }
+}
+
+#[async_trait]
+impl server::Handler for ConnectionHandler {
+    type Error = russh::Error;
+
+    async fn auth_password(&mut self, user: &str, password: &str) -> Result<Auth, Self::Error> {
+        self.stats.auth_attempts.fetch_add(1, Ordering::SeqCst);
+        let accepted = self
+            .settings
+            .users
+            .get(user)
+            .and_then(|test_user| test_user.password.as_deref())
+            == Some(password);
+        if accepted {
+            self.user = Some(user.to_string());
+            return Ok(Auth::Accept);
+        }
+        Ok(Self::reject())
+    }
+
+    async fn auth_publickey(
+        &mut self,
+        user: &str,
+        public_key: &PublicKey,
+    ) -> Result<Auth, Self::Error> {
+        self.stats.auth_attempts.fetch_add(1, Ordering::SeqCst);
+        let fingerprint = public_key.fingerprint();
+        let accepted = self
+            .settings
+            .users
+            .get(user)
+            .is_some_and(|test_user| test_user.key_fingerprints.contains(&fingerprint));
+        if accepted {
+            self.user = Some(user.to_string());
+            return Ok(Auth::Accept);
+        }
+        Ok(Self::reject())
+    }
+
+    async fn auth_keyboard_interactive(
+        &mut self,
+        user: &str,
+        _submethods: &str,
+        response: Option<Response<'async_trait>>,
+    ) -> Result<Auth, Self::Error> {
+        let Some(test_user) = self.settings.users.get(user) else {
+            return Ok(Self::reject());
+        };
+        if test_user.keyboard_interactive.is_empty() {
+            return Ok(Self::reject());
+        }
+        let Some(response) = response else {
+            let prompts: Vec<(Cow<'static, str>, bool)> = test_user
+                .keyboard_interactive
+                .iter()
+                .map(|(prompt, _)| (Cow::Owned(prompt.clone()), false))
+                .collect();
+            return Ok(Auth::Partial {
+                name: Cow::Borrowed("test-server"),
+                instructions: Cow::Borrowed(""),
+                prompts: Cow::Owned(prompts),
+            });
+        };
+
+        self.stats.auth_attempts.fetch_add(1, Ordering::SeqCst);
+        let answers: Vec<String> = response
+            .map(|answer| String::from_utf8_lossy(answer).into_owned())
+            .collect();
+        let expected: Vec<String> = test_user
+            .keyboard_interactive
+            .iter()
+            .map(|(_, answer)| answer.clone())
+            .collect();
+        if answers == expected {
+            self.user = Some(user.to_string());
+            return Ok(Auth::Accept);
+        }
+        Ok(Self::reject())
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        // 慢速服务器的开通道确认同样延迟，客户端的打开会多次遇到 EAGAIN
+        if !self.settings.reply_delay.is_zero() {
+            tokio::time::sleep(self.settings.reply_delay).await;
+        }
+        self.stats.channels_opened.fetch_add(1, Ordering::SeqCst);
+        self.channels.insert(channel.id(), ChannelState::default());
+        Ok(true)
+    }
+
+    async fn channel_open_confirmation(
+        &mut self,
+        id: ChannelId,
+        _max_packet_size: u32,
+        _window_size: u32,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        if let Some(query) = self.agent_queries.get(&id) {
+            let mut framed = Vec::new();
+            put_string(&mut framed, &query.request);
+            session.data(id, CryptoVec::from_slice(&framed));
+        }
+        Ok(())
+    }
+
+    async fn agent_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        // russh 只回全局的 REQUEST_SUCCESS/FAILURE，libssh2 等待的是通道回复，需要另外发送
+        let accepted = self.settings.agent_forwarding;
+        match self.channels.get_mut(&channel) {
+            Some(state) if accepted => {
+                state.agent = true;
+                session.channel_success(channel);
+            }
+            _ => session.channel_failure(channel),
+        }
+        Ok(accepted)
+    }
+
+    async fn channel_close(
+        &mut self,
+        channel: ChannelId,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.stats.channels_closed.fetch_add(1, Ordering::SeqCst);
+        self.channels.remove(&channel);
+        Ok(())
+    }
+
+    async fn env_request(
+        &mut self,
+        channel: ChannelId,
+        variable_name: &str,
+        variable_value: &str,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let accepted = self
+            .settings
+            .accepted_env
+            .as_ref()
+            .is_none_or(|names| names.iter().any(|name| name == variable_name));
+        match self.channels.get_mut(&channel) {
+            Some(state) if accepted => {
+                state
+                    .env
+                    .insert(variable_name.to_string(), variable_value.to_string());
+                self.stats
+                    .env
+                    .lock()
+                    .unwrap()
+                    .insert(variable_name.to_string(), variable_value.to_string());
+                session.channel_success(channel);
+            }
+            _ => session.channel_failure(channel),
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn pty_request(
+        &mut self,
+        channel: ChannelId,
+        term: &str,
+        _col_width: u32,
+        _row_height: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _modes: &[(Pty, u32)],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.stats.pty_terms.lock().unwrap().push(term.to_string());
+        session.channel_success(channel);
+        Ok(())
+    }
+
+    async fn shell_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let Some(state) = self.channels.get_mut(&channel) else {
+            session.channel_failure(channel);
+            return Ok(());
+        };
+        state.shell = true;
+        session.channel_success(channel);
+        let greeting = format!(
+            "welcome {}\r\n{PROMPT}",
+            self.user.clone().unwrap_or_default()
+        );
+        self.reply(channel, &greeting, session).await;
+        Ok(())
+    }
+
+    async fn exec_request(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let command = String::from_utf8_lossy(data).into_owned();
+        session.channel_success(channel);
+        // 需要 stdin 的命令等客户端发送 EOF 后再执行
+        if command.split(['&', ';']).any(|part| part.trim() == "cat") {
+            if let Some(state) = self.channels.get_mut(&channel) {
+                state.pending_exec = Some(command);
+            }
+            return Ok(());
+        }
+        self.apply_exports(channel, &command);
+        let outcome = self.run_line(channel, &command);
+        self.reply(channel, &outcome.stdout, session).await;
+        if !outcome.stderr.is_empty() {
+            session.extended_data(channel, 1, CryptoVec::from_slice(outcome.stderr.as_bytes()));
+        }
+        self.finish(channel, outcome.exit.unwrap_or(0), session);
+        Ok(())
+    }
+
+    async fn data(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        if self.agent_queries.contains_key(&channel) {
+            self.answer_agent(channel, data, session).await;
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(data).into_owned();
+        let Some(state) = self.channels.get_mut(&channel) else {
+            return Ok(());
+        };
+        if !state.shell {
+            state.stdin.extend_from_slice(data);
+            // 慢速服务器读取 exec 输入同样缓慢，积压的输入会让客户端写入反复 WouldBlock
+            if !self.settings.reply_delay.is_zero() {
+                tokio::time::sleep(self.settings.reply_delay).await;
+            }
+            return Ok(());
+        }
+
+        // 模拟开启回显的 PTY：先原样回显，遇到换行再执行整行
+        self.reply(channel, &text.replace('\n', "\r\n"), session)
+            .await;
+        let mut lines = Vec::new();
+        let state = self.channels.get_mut(&channel).unwrap();
+        for ch in text.chars() {
+            match ch {
+                '\r' | '\n' => lines.push(std::mem::take(&mut state.line)),
+                _ => state.line.push(ch),
+            }
+        }
+        for line in lines.into_iter().filter(|line| !line.trim().is_empty()) {
+            match self.query_agent(channel, line.trim(), session) {
+                // 应答到达后再输出结果与提示符
+                Some(output) if output.is_empty() => continue,
+                Some(output) => {
+                    let output = format!("{output}{PROMPT}").replace('\n', "\r\n");
+                    self.reply(channel, &output, session).await;
+                    continue;
+                }
+                None => {}
+            }
+            self.apply_exports(channel, &line);
+            let outcome = self.run_line(channel, &line);
+            let output = format!("{}{}", outcome.stdout, outcome.stderr).replace('\n', "\r\n");
+            if let Some(status) = outcome.exit.filter(|_| line.trim().starts_with("exit")) {
+                self.reply(channel, &output, session).await;
+                self.finish(channel, status, session);
+                return Ok(());
+            }
+            self.reply(channel, &format!("{output}{PROMPT}"), session)
+                .await;
+        }
+        Ok(())
+    }
+
+    async fn channel_eof(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let Some(state) = self.channels.get_mut(&channel) else {
+            return Ok(());
+        };
+        let Some(command) = state.pending_exec.take() else {
+            // 与 sshd 一致：shell 读到输入结束即退出并关闭通道。russh 不会回应客户端发起的 CLOSE，
+            // 客户端关闭前先发的 EOF 是唯一能回应的时机
+            if state.shell {
+                session.eof(channel);
+                session.close(channel);
+            }
+            return Ok(());
+        };
+        let stdin = String::from_utf8_lossy(&state.stdin).into_owned();
+        self.apply_exports(channel, &command);
+        let rest: Vec<&str> = command
+            .split("&&")
+            .flat_map(|part| part.split(';'))
+            .map(str::trim)
+            .filter(|part| *part != "cat" && !part.is_empty())
+            .collect();
+        let outcome = self.run_line(channel, &rest.join(";"));
+        self.reply(channel, &format!("{}{stdin}", outcome.stdout), session)
+            .await;
+        self.finish(channel, outcome.exit.unwrap_or(0), session);
+        Ok(())
+    }
+}
+
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+fn put_string(buffer: &mut Vec<u8>, value: &[u8]) {
+    buffer.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(value);
+}
+
+fn take_string<'d>(data: &'d [u8], pos: &mut usize) -> Option<&'d [u8]> {
+    let length = u32::from_be_bytes(data.get(*pos..*pos + 4)?.try_into().ok()?) as usize;
+    let value = data.get(*pos + 4..*pos + 4 + length)?;
+    *pos += 4 + length;
+    Some(value)
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix('\'')
+        .and_then(|inner| inner.strip_suffix('\''))
+        .or_else(|| {
+            value
+                .strip_prefix('"')
+                .and_then(|inner| inner.strip_suffix('"'))
+        })
+        .unwrap_or(value)
+        .replace(r"'\''", "'")
+}
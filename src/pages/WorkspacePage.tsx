@@ -548,7 +548,7 @@ export const WorkspacePage = () => {
                     </Text>
                   </div>
                   <Badge variant="dot" color="teal">
-                    {session.latencyMs} ms
+                    {session.latency ? `${Math.round(session.latency.avgMs)} ms` : "—"}
                   </Badge>
                 </Group>
               ))}
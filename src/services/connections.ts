@@ -1,7 +1,12 @@
 import { invoke } from "@tauri-apps/api/core";
 
 import { invokeOrFallback, isTauri } from "./tauriBridge";
-import type { Connection, NewConnectionPayload, UpdateConnectionPayload } from "../shared/types";
+import type {
+  AgentForwarding,
+  Connection,
+  NewConnectionPayload,
+  UpdateConnectionPayload,
+} from "../shared/types";
 
 const delay = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
 
@@ -105,6 +110,21 @@ export async function updateConnection(payload: UpdateConnectionPayload): Promis
   throw new Error("连接不存在（mock 环境）");
 }
 
+export async function updateConnectionAgentForwarding(
+  id: string,
+  agentForwarding: AgentForwarding,
+): Promise<Connection> {
+  if (isTauri) {
+    return invoke<Connection>("update_connection_agent_forwarding", { id, agentForwarding });
+  }
+  const idx = connectionFixtures.findIndex((item) => item.id === id);
+  if (idx >= 0) {
+    connectionFixtures[idx] = { ...connectionFixtures[idx], agentForwarding };
+    return connectionFixtures[idx];
+  }
+  throw new Error("连接不存在（mock 环境）");
+}
+
 export async function deleteConnection(id: string): Promise<void> {
   if (isTauri) {
     await invoke("delete_connection", { id });
@@ -1,5 +1,17 @@
-import { invokeOrFallback } from "./tauriBridge";
-import type { SessionSummary } from "../shared/types";
+import { invoke } from "@tauri-apps/api/core";
+
+import { invokeOrFallback, isTauri } from "./tauriBridge";
+import type {
+  ObserverAttachment,
+  ScrollbackMatch,
+  ScrollbackQuery,
+  SessionPolicySettings,
+  SessionRecord,
+  SessionSummary,
+  SessionTransfer,
+  ShellProfile,
+  ShellProfileSettings,
+} from "../shared/types";
 
 const delay = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
 
@@ -8,25 +20,31 @@ const sessionFixtures: SessionSummary[] = [
     id: "session-prod-api",
     connectionId: "conn-prod-api",
     title: "prod-api-01",
-    latencyMs: 42,
+    state: "connected",
+    startedAt: new Date().toISOString(),
     statusLabel: "Live",
     updatedAt: new Date().toISOString(),
+    latency: { lastMs: 42, minMs: 38, avgMs: 42, p95Ms: 47, samples: 30 },
   },
   {
     id: "session-payments-edge",
     connectionId: "conn-payments-edge",
     title: "payments-edge",
-    latencyMs: 51,
+    state: "connected",
+    startedAt: new Date().toISOString(),
     statusLabel: "Deploying",
     updatedAt: new Date().toISOString(),
+    latency: { lastMs: 51, minMs: 45, avgMs: 51, p95Ms: 60, samples: 30 },
   },
   {
     id: "session-analytics",
     connectionId: "conn-analytics",
     title: "analytics-pipeline",
-    latencyMs: 68,
+    state: "connected",
+    startedAt: new Date().toISOString(),
     statusLabel: "Idle",
     updatedAt: new Date().toISOString(),
+    latency: { lastMs: 68, minMs: 61, avgMs: 68, p95Ms: 80, samples: 30 },
   },
 ];
 
@@ -36,3 +54,93 @@ export async function listSessionSummaries(): Promise<SessionSummary[]> {
     return sessionFixtures;
   });
 }
+
+export async function listRecentSessions(
+  connectionId?: string,
+  limit = 50,
+): Promise<SessionRecord[]> {
+  if (!isTauri) {
+    return [];
+  }
+  return invoke<SessionRecord[]>("list_recent_sessions", { connectionId, limit });
+}
+
+export async function searchSessions(
+  query: ScrollbackQuery,
+  sessionId?: string,
+): Promise<ScrollbackMatch[]> {
+  if (!isTauri) {
+    return [];
+  }
+  return invoke<ScrollbackMatch[]>("search_sessions", { sessionId, query });
+}
+
+export async function getActiveTransfer(sessionId: string): Promise<SessionTransfer | null> {
+  if (!isTauri) {
+    return null;
+  }
+  return invoke<SessionTransfer | null>("get_active_transfer", { sessionId });
+}
+
+/** 上传时传入本地文件，下载时传入唯一的保存目录。 */
+export async function acceptFileTransfer(sessionId: string, paths: string[]): Promise<void> {
+  await invoke("accept_file_transfer", { sessionId, paths });
+}
+
+export async function cancelFileTransfer(sessionId: string): Promise<void> {
+  await invoke("cancel_file_transfer", { sessionId });
+}
+
+/** 以只读观察者身份把当前窗口附加到会话，之后该会话的输出也会推送到本窗口。 */
+export async function attachSessionObserver(sessionId: string): Promise<ObserverAttachment> {
+  return invoke<ObserverAttachment>("attach_session_observer", { sessionId });
+}
+
+export async function detachSessionObserver(attachmentId: string): Promise<void> {
+  await invoke("detach_session_observer", { attachmentId });
+}
+
+export async function listSessionObservers(sessionId: string): Promise<ObserverAttachment[]> {
+  return invoke<ObserverAttachment[]>("list_session_observers", { sessionId });
+}
+
+export async function getSessionPolicySettings(): Promise<SessionPolicySettings> {
+  return invoke<SessionPolicySettings>("get_session_policy_settings");
+}
+
+export async function updateSessionPolicySettings(settings: SessionPolicySettings): Promise<void> {
+  await invoke("update_session_policy_settings", { settings });
+}
+
+export async function listShellProfiles(): Promise<ShellProfileSettings> {
+  return invoke<ShellProfileSettings>("list_shell_profiles");
+}
+
+export async function saveShellProfile(profile: ShellProfile): Promise<ShellProfile> {
+  return invoke<ShellProfile>("save_shell_profile", { profile });
+}
+
+export async function deleteShellProfile(id: string): Promise<void> {
+  await invoke("delete_shell_profile", { id });
+}
+
+export async function setDefaultShellProfile(id: string | null): Promise<void> {
+  await invoke("set_default_shell_profile", { id });
+}
+
+export async function setPersistentLocalSessions(enabled: boolean): Promise<void> {
+  await invoke("set_persistent_local_sessions", { enabled });
+}
+
+/** 应用启动后调用，返回重新附加的本地会话 id。 */
+export async function reattachLocalSessions(): Promise<string[]> {
+  return invoke<string[]>("reattach_local_sessions");
+}
+
+export async function respondAgentRequest(
+  sessionId: string,
+  requestId: string,
+  allow: boolean,
+): Promise<void> {
+  await invoke("respond_agent_request", { sessionId, requestId, allow });
+}
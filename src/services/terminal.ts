@@ -1,14 +1,24 @@
 import { invoke } from "@tauri-apps/api/core";
 
+import type { CommandOutput, RunCommandOptions } from "../shared/types";
 import { isTauri } from "./tauriBridge";
 
-export async function runLocalCommand(command: string, connectionId?: string): Promise<string> {
+export async function runLocalCommand(
+  command: string,
+  options: RunCommandOptions = {},
+): Promise<CommandOutput> {
   if (!isTauri) {
     throw new Error("当前运行在浏览器预览环境，无法调用 Tauri 命令");
   }
 
-  const result = await invoke<string>("run_local_command", {
-    payload: { command, connectionId },
+  return invoke<CommandOutput>("run_local_command", {
+    payload: { command, ...options },
   });
-  return result;
+}
+
+export async function cancelCommand(jobId: string): Promise<boolean> {
+  if (!isTauri) {
+    return false;
+  }
+  return invoke<boolean>("cancel_command", { jobId });
 }
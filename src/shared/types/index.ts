@@ -18,15 +18,231 @@ export interface Connection {
   favorite: boolean;
   status: ConnectionHealth;
   lastConnectedAt?: string;
+  startup?: StartupActions;
+  agentForwarding?: AgentForwarding;
 }
 
+export type AgentForwarding = "disabled" | "allow" | "confirm";
+
+export type StartupStep =
+  | { type: "command"; command: string }
+  | { type: "expect"; pattern: string; send: string; timeoutMs?: number };
+
+export interface StartupActions {
+  env: Record<string, string>;
+  workingDir?: string;
+  steps: StartupStep[];
+}
+
+export type ScriptJump =
+  | { kind: "next" }
+  | { kind: "goto"; label: string }
+  | { kind: "succeed" }
+  | { kind: "fail"; message?: string };
+
+export type ScriptStep = { label?: string } & (
+  | { type: "send"; data: string }
+  | {
+      type: "expect";
+      branches: { pattern: string; then?: ScriptJump }[];
+      timeoutMs?: number;
+      onTimeout?: ScriptJump;
+    }
+  | { type: "sleep"; ms: number }
+  | { type: "jump"; to: ScriptJump }
+);
+
+export interface AutomationScript {
+  id: string;
+  name: string;
+  description?: string;
+  steps: ScriptStep[];
+  createdAt: string;
+  updatedAt: string;
+}
+
+export type TriggerScope =
+  | { type: "global" }
+  | { type: "group"; groupId: string }
+  | { type: "connection"; connectionId: string };
+
+export interface TriggerRule {
+  id: string;
+  name: string;
+  pattern: string;
+  scope: TriggerScope;
+  action: "highlight" | "notify" | "autofill";
+  enabled: boolean;
+  createdAt: string;
+  updatedAt: string;
+}
+
+export interface TranscriptSettings {
+  enabled: boolean;
+  directory?: string;
+  maxFileBytes: number;
+  retentionDays: number;
+}
+
+export type SessionState =
+  | "connecting"
+  | "authenticating"
+  | "connected"
+  | "reconnecting"
+  | "closed"
+  | "failed";
+
 export interface SessionSummary {
   id: string;
-  connectionId: string;
+  connectionId?: string | null;
   title: string;
-  latencyMs: number;
+  state: SessionState;
+  startedAt: string;
   statusLabel: string;
   updatedAt: string;
+  latency?: LatencyStats | null;
+}
+
+export interface LatencyStats {
+  lastMs: number;
+  minMs: number;
+  avgMs: number;
+  p95Ms: number;
+  samples: number;
+}
+
+export interface ShellCommand {
+  id: string;
+  sessionId: string;
+  connectionId?: string | null;
+  command: string;
+  cwd?: string | null;
+  startedAt: string;
+  finishedAt?: string | null;
+  exitCode?: number | null;
+  durationMs?: number | null;
+}
+
+export interface ScreenSnapshot {
+  rows: number;
+  cols: number;
+  cursorRow: number;
+  cursorCol: number;
+  cursorVisible: boolean;
+  alternateScreen: boolean;
+  title: string;
+  lines: string[];
+  formatted: string;
+}
+
+export interface ObserverAttachment {
+  attachmentId: string;
+  sessionId: string;
+  window: string;
+  attachedAt: string;
+  snapshot?: ScreenSnapshot;
+}
+
+export interface SessionObservers {
+  sessionId: string;
+  windows: string[];
+}
+
+/** 字段为空表示沿用上一级，为 0 表示不限制。 */
+export interface SessionPolicy {
+  idleTimeoutSecs?: number | null;
+  maxDurationSecs?: number | null;
+}
+
+/** 逐项按 连接 > 分组 > 全局 取最具体的一级。 */
+export interface SessionPolicySettings {
+  global: SessionPolicy;
+  groups: Record<string, SessionPolicy>;
+  connections: Record<string, SessionPolicy>;
+  warningSecs: number;
+}
+
+export interface ShellProfile {
+  id: string;
+  name: string;
+  program: string;
+  args: string[];
+  env: Record<string, string>;
+  cwd?: string | null;
+  login: boolean;
+  /** 从 /etc/shells 检测得到、尚未保存。 */
+  detected: boolean;
+}
+
+export interface ShellProfileSettings {
+  profiles: ShellProfile[];
+  defaultProfileId?: string | null;
+  /** 本地会话由后台守护进程托管，应用退出后继续运行（仅 macOS / Linux）。 */
+  persistentSessions: boolean;
+}
+
+export interface SessionPolicyWarning {
+  session_id: string;
+  reason: "idle_timeout" | "max_duration";
+  seconds_left: number;
+}
+
+export interface ScrollbackQuery {
+  pattern: string;
+  regex?: boolean;
+  caseSensitive?: boolean;
+  contextLines?: number | null;
+  limit?: number | null;
+}
+
+export interface ScrollbackMatch {
+  sessionId: string;
+  sessionTitle: string;
+  lineNumber: number;
+  line: string;
+  matchStart: number;
+  matchEnd: number;
+  before: string[];
+  after: string[];
+}
+
+export type TransferProtocol = "zmodem" | "trzsz";
+
+export type TransferDirection = "upload" | "download";
+
+export type TransferState = "pending" | "running" | "completed" | "cancelled" | "failed";
+
+/** `session-transfer` 事件与 `get_active_transfer` 的返回值，字段沿用事件的下划线命名。 */
+export interface SessionTransfer {
+  session_id: string;
+  transfer_id: string;
+  protocol: TransferProtocol;
+  direction: TransferDirection;
+  state: TransferState;
+  files: string[];
+  message?: string | null;
+}
+
+export interface SessionAgentRequest {
+  session_id: string;
+  request_id: string;
+  key_type: string;
+  comment?: string | null;
+}
+
+export interface SessionRecord {
+  sessionId: string;
+  connectionId?: string | null;
+  title: string;
+  startedAt: string;
+  endedAt?: string | null;
+  exitCode?: number | null;
+  exitSignal?: string | null;
+  closeReason?: string | null;
+  message?: string | null;
+  durationMs?: number | null;
+  bytesIn: number;
+  bytesOut: number;
 }
 
 export interface NewConnectionPayload {
@@ -40,3 +256,24 @@ export interface NewConnectionPayload {
 export interface UpdateConnectionPayload extends NewConnectionPayload {
   id: string;
 }
+
+export interface CommandOutput {
+  stdout: string;
+  stderr: string;
+  exitCode?: number;
+  exitSignal?: string;
+  timedOut: boolean;
+  cancelled: boolean;
+  durationMs: number;
+}
+
+export interface RunCommandOptions {
+  connectionId?: string;
+  jobId?: string;
+  stream?: boolean;
+  stdin?: string;
+  env?: Record<string, string>;
+  cwd?: string;
+  timeoutMs?: number;
+  secret?: { password?: string };
+}